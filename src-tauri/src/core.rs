@@ -1,8 +1,17 @@
+pub mod analysis;
 pub mod database;
+pub mod decoder;
+pub mod loudness;
 pub mod migrator;
 pub mod player;
+pub mod resampler;
+pub mod silence;
+pub mod spectrogram;
+pub mod time_stretch;
+pub mod wav;
 pub mod waveform;
 
 pub use database::{Database, Entry, EntryId, Filter, TagId};
 pub use player::Player;
+pub use spectrogram::SpectrogramGenerator;
 pub use waveform::WaveformGenerator;
@@ -0,0 +1,73 @@
+use super::decoder::{Error, SampleDecoder};
+use super::loudness::{Loudness, LoudnessMeter};
+
+use std::path::Path;
+
+/// The level from which a sample is clipped, i.e. an over, which is the highest level of 16-bit
+/// samples, so that full scale samples of any integer format are clipped as well.
+pub const CLIP_LEVEL: f32 = 32767. / 32768.;
+
+/// Level statistics of the audio of a file, besides its [`Loudness`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Analysis {
+    /// Sample peak in dBFS.
+    pub peak: f32,
+    /// RMS level of all channels in dBFS.
+    pub rms: f32,
+    /// Loudness range in LU.
+    pub loudness_range: f32,
+    /// The mean of all samples.
+    pub dc_offset: f32,
+    /// The number of samples at or above the full scale.
+    pub clipped_samples: u64,
+}
+
+/// Analyze the default audio track of a file.
+#[allow(clippy::cast_possible_truncation)]
+#[allow(clippy::cast_precision_loss)]
+pub fn analyze(path: &Path) -> Result<(Loudness, Analysis), Error> {
+    let mut decoder = SampleDecoder::open(path)?;
+    let spec = *decoder.spec();
+
+    let mut meter = LoudnessMeter::new(spec.n_channels, spec.sample_rate);
+    let mut peak = 0f32;
+    let mut sum = 0f64;
+    let mut sum_squares = 0f64;
+    let mut n_samples = 0u64;
+    let mut clipped_samples = 0;
+
+    while let Some(samples) = decoder.next_samples()? {
+        meter.process(samples);
+
+        for &sample in samples {
+            let abs = sample.abs();
+            peak = peak.max(abs);
+            if abs >= CLIP_LEVEL {
+                clipped_samples += 1;
+            }
+            sum += f64::from(sample);
+            sum_squares += f64::from(sample) * f64::from(sample);
+        }
+        n_samples += samples.len() as u64;
+    }
+
+    let (rms, dc_offset) = if n_samples == 0 {
+        (0., 0.)
+    } else {
+        (
+            (sum_squares / n_samples as f64).sqrt(),
+            sum / n_samples as f64,
+        )
+    };
+
+    Ok((
+        meter.finalize(),
+        Analysis {
+            peak: 20. * peak.log10(),
+            rms: 20. * (rms as f32).log10(),
+            loudness_range: meter.loudness_range(),
+            dc_offset: dc_offset as f32,
+            clipped_samples,
+        },
+    ))
+}
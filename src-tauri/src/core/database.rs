@@ -1,20 +1,39 @@
+mod analysis;
 mod entry;
+mod export;
 mod file_watcher;
 mod files;
 mod filter;
 mod folder;
+mod hash;
+mod import;
 mod tag;
 
 #[cfg(test)]
 mod tests;
 
-pub use entry::{Entry, EntryId};
-pub use filter::Filter;
+use entry::StoredFileData;
+pub use entry::{Entry, EntryId, FileInfo};
+pub use export::{
+    ConvertOptions, ExportOptions, ExportProgress, ExportResult, ExportStatus, ManifestFormat,
+};
+pub use files::Region;
+pub use filter::{Filter, ValueRange};
 pub use folder::{Folder, FolderId};
+use hash::compute_hash;
+pub use hash::HashMode;
+pub use import::{
+    CollisionPolicy, ImportMode, ImportOptions, ImportProgress, ImportResult, ImportStatus,
+};
 pub use tag::{Tag, TagId};
 
+use crate::core::analysis::Analysis;
+use crate::core::loudness::Loudness;
+use crate::core::waveform::WaveformCache;
+
 use std::collections::{HashMap, HashSet};
 use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::AtomicBool;
 use std::sync::{Arc, Mutex, RwLock};
 
 use crossbeam_channel;
@@ -30,6 +49,8 @@ pub struct Database<E> {
 
     emitter: Arc<E>,
     stop_tx: crossbeam_channel::Sender<()>,
+    /// Whether the background analysis thread is running.
+    analysis_running: AtomicBool,
 }
 
 pub struct DatabaseData {
@@ -46,6 +67,56 @@ struct FileDiff {
     pub deleted_entries: Vec<EntryId>,
 }
 
+/// An entry in the database whose file no longer exists at its recorded path.
+struct MissingEntry {
+    id: EntryId,
+    stored: StoredFileData,
+}
+
+/// The state of matching the new files of a scan with the entries moved or renamed
+/// while the database was closed.
+#[derive(Default)]
+struct MovedEntries {
+    /// The IDs of the entries removed during the scan.
+    removed: HashSet<EntryId>,
+    /// The entries which may have been moved, queried when the first new file is matched.
+    missing: Option<Vec<MissingEntry>>,
+    /// The new files that can only be matched by their content hash.
+    pending: Vec<PathBuf>,
+    /// The content hashes of the pending files and their file information when hashed,
+    /// or `None` if they have not been computed yet.
+    hashes: Option<HashMap<PathBuf, (FileInfo, String)>>,
+}
+
+impl MovedEntries {
+    /// Compute the content hashes of the pending files.
+    fn compute_hashes(&mut self, base_path: &Path) {
+        let hashes = self
+            .pending
+            .iter()
+            .filter_map(|path| {
+                let abs_path = base_path.join(path);
+                let file_info = FileInfo::read(&abs_path).ok()?;
+                let hash = compute_hash(&abs_path, HashMode::Content)
+                    .inspect_err(|err| {
+                        warn!("Failed to compute hash of {}: {err}", abs_path.display());
+                    })
+                    .ok()?;
+                Some((path.clone(), (file_info, hash)))
+            })
+            .collect();
+        self.hashes = Some(hashes);
+    }
+}
+
+/// The result of matching a new file with the missing entries.
+enum MovedEntryMatch {
+    Found(MissingEntry),
+    /// The content hash of the file is needed to match it.
+    Pending,
+    NotFound,
+}
+
 struct FolderDiff {
     pub new_folders: Vec<PathBuf>,
     pub deleted_folders: Vec<FolderId>,
@@ -55,7 +126,9 @@ const ROOT_FOLDER_ID: FolderId = -1;
 const ROOT_TAG_ID: TagId = -1;
 
 pub const SQLITE_DB_PATH: &str = ".soundmanager.db";
-const DATABASE_VERSION: i32 = 1;
+/// The folder of the data derived from the files, e.g. waveforms, next to the database file.
+pub const CACHE_DIR: &str = ".soundmanager.cache";
+const DATABASE_VERSION: i32 = 5;
 
 #[derive(Error, Debug)]
 pub enum Error {
@@ -63,6 +136,8 @@ pub enum Error {
     DatabaseNotFound(String),
     #[error("database already exists: {0}")]
     DatabaseAlreadyExists(String),
+    #[error("unsupported database version: {0}")]
+    UnsupportedDatabaseVersion(i32),
     #[error("tag already exists: {0}")]
     TagAlreadyExists(String),
     #[error("tag {0} already exists for entry {1}")]
@@ -71,12 +146,24 @@ pub enum Error {
     FileAlreadyExists(String),
     #[error("folder already exists: {0}")]
     FolderAlreadyExists(String),
+    #[error("folder not found: {0}")]
+    FolderNotFound(FolderId),
+    #[error("invalid name: {0}")]
+    InvalidName(String),
+    #[error("the root folder cannot be deleted")]
+    DeleteRootFolder,
+    #[error("invalid region: {0}s - {1}s")]
+    InvalidRegion(f32, f32),
+    #[error("sample rate out of range: {0} Hz")]
+    InvalidSampleRate(u32),
     #[error("io error: {0}")]
     Io(#[from] std::io::Error),
     #[error("database error: {0}")]
     Rusqlite(#[from] rusqlite::Error),
     #[error("symphonia error: {0}")]
     Symphonia(#[from] symphonia::core::errors::Error),
+    #[error("decoder error: {0}")]
+    Decoder(#[from] crate::core::decoder::Error),
     #[error("notify error: {0}")]
     Notify(#[from] notify_debouncer_full::notify::Error),
     #[error("trash error: {0}")]
@@ -106,7 +193,8 @@ where
             ));
         }
 
-        let db = Connection::open(database_file)?;
+        let mut db = Connection::open(database_file)?;
+        Self::upgrade(&mut db)?;
 
         let tags = Self::read_tags(&db)?;
         let folders = HashMap::from([(
@@ -131,13 +219,10 @@ where
             db: Mutex::new(db),
             emitter,
             stop_tx,
+            analysis_running: AtomicBool::new(false),
         };
 
-        database
-            .data
-            .write()
-            .unwrap()
-            .scan(&mut database.db.lock().unwrap())?;
+        database.scan()?;
 
         let database = Arc::new(database);
         database.prune()?;
@@ -166,10 +251,8 @@ where
             )",
             (),
         )?;
-        tx.execute(
-            "INSERT INTO metadata (version) VALUES (?)",
-            [DATABASE_VERSION],
-        )?;
+        // create the schema of version 1, and then upgrade it to the latest version
+        tx.execute("INSERT INTO metadata (version) VALUES (?)", [1])?;
         tx.execute_batch(
             "CREATE TABLE entries (
                 id INTEGER PRIMARY KEY AUTOINCREMENT,
@@ -211,6 +294,7 @@ where
             (ROOT_TAG_ID, "", ROOT_TAG_ID, 0),
         )?;
         tx.commit()?;
+        Self::upgrade(&mut db)?;
 
         let folders = HashMap::from([(
             ROOT_FOLDER_ID,
@@ -245,13 +329,10 @@ where
             db: Mutex::new(db),
             emitter,
             stop_tx,
+            analysis_running: AtomicBool::new(false),
         };
 
-        database
-            .data
-            .write()
-            .unwrap()
-            .scan(&mut database.db.lock().unwrap())?;
+        database.scan()?;
 
         let database = Arc::new(database);
         database.prune()?;
@@ -261,11 +342,30 @@ where
     }
 
     pub fn refresh(&self) -> Result<()> {
-        self.data
+        self.scan()?;
+        self.emitter.on_files_updated(true);
+        Ok(())
+    }
+
+    /// Scan the entire directory of the database.
+    ///
+    /// The content hashes of the new files that may be moved entries are computed
+    /// without holding the lock of the data.
+    fn scan(&self) -> Result<()> {
+        let mut moved = self
+            .data
             .write()
             .unwrap()
             .scan(&mut self.db.lock().unwrap())?;
-        self.emitter.on_files_updated(true);
+
+        let base_path = self.data.read().unwrap().base_path.clone();
+        moved.compute_hashes(&base_path);
+
+        let mut data = self.data.write().unwrap();
+        let db = self.db.lock().unwrap();
+        data.add_pending_entries(moved, &db)?;
+        data.sync_deleted(&db)?;
+
         Ok(())
     }
 
@@ -290,6 +390,52 @@ impl<E> Database<E> {
         let _ = self.stop_tx.send(());
     }
 
+    /// Upgrade the schema of the database to [`DATABASE_VERSION`].
+    fn upgrade(db: &mut Connection) -> Result<()> {
+        let version: i32 = db.query_row("SELECT version FROM metadata", [], |row| row.get(0))?;
+        if version > DATABASE_VERSION {
+            return Err(Error::UnsupportedDatabaseVersion(version));
+        }
+        if version == DATABASE_VERSION {
+            return Ok(());
+        }
+
+        info!("Upgrading database from version {version} to {DATABASE_VERSION}");
+
+        let tx = db.transaction()?;
+        if version < 2 {
+            tx.execute_batch(
+                "ALTER TABLE entries ADD COLUMN file_size INTEGER DEFAULT NULL;
+                ALTER TABLE entries ADD COLUMN file_mtime INTEGER DEFAULT NULL;
+                ALTER TABLE entries ADD COLUMN content_hash TEXT DEFAULT NULL;
+                ALTER TABLE entries ADD COLUMN audio_hash TEXT DEFAULT NULL;
+                CREATE INDEX entries_content_hash ON entries(content_hash);",
+            )?;
+        }
+        if version < 3 {
+            tx.execute_batch("ALTER TABLE entries ADD COLUMN file_id INTEGER DEFAULT NULL;")?;
+        }
+        if version < 4 {
+            tx.execute_batch(
+                "ALTER TABLE entries ADD COLUMN loudness REAL DEFAULT NULL;
+                ALTER TABLE entries ADD COLUMN true_peak REAL DEFAULT NULL;",
+            )?;
+        }
+        if version < 5 {
+            tx.execute_batch(
+                "ALTER TABLE entries ADD COLUMN peak REAL DEFAULT NULL;
+                ALTER TABLE entries ADD COLUMN rms REAL DEFAULT NULL;
+                ALTER TABLE entries ADD COLUMN loudness_range REAL DEFAULT NULL;
+                ALTER TABLE entries ADD COLUMN dc_offset REAL DEFAULT NULL;
+                ALTER TABLE entries ADD COLUMN clipped_samples INTEGER DEFAULT NULL;",
+            )?;
+        }
+        tx.execute("UPDATE metadata SET version = ?", [DATABASE_VERSION])?;
+        tx.commit()?;
+
+        Ok(())
+    }
+
     fn read_tags(db: &Connection) -> Result<HashMap<TagId, Tag>> {
         let mut tags = db
             .prepare("SELECT id, name, parent, position, color FROM tags WHERE deleted IS NULL")?
@@ -461,14 +607,15 @@ impl DatabaseData {
         })
     }
 
-    /// Scan the entire directory of the database.
-    fn scan(&mut self, db: &mut Connection) -> Result<()> {
+    /// Scan the entire directory of the database, except for the new files pending to be matched
+    /// with the moved entries.
+    fn scan(&mut self, db: &mut Connection) -> Result<MovedEntries> {
         let diff = self.read_dir(&self.base_path, ROOT_FOLDER_ID.into())?;
 
-        self.sync_changes(diff, db)?;
-        self.sync_deleted(db)?;
+        let mut moved = MovedEntries::default();
+        self.sync_changes(diff, Some(&mut moved), db)?;
 
-        Ok(())
+        Ok(moved)
     }
 
     /// Scan a specific directory in the database.
@@ -478,7 +625,7 @@ impl DatabaseData {
         let folder_id = self.get_folder_by_path(path).map(|folder| folder.id);
         let diff = self.read_dir(&self.to_absolute_path(path), folder_id)?;
 
-        self.sync_changes(diff, db)?;
+        self.sync_changes(diff, None, db)?;
 
         Ok(())
     }
@@ -581,23 +728,54 @@ impl DatabaseData {
         Ok(())
     }
 
-    fn sync_changes(&mut self, diff: FileDiff, db: &mut Connection) -> Result<()> {
+    fn sync_changes(
+        &mut self,
+        diff: FileDiff,
+        mut moved: Option<&mut MovedEntries>,
+        db: &mut Connection,
+    ) -> Result<()> {
         // remove deleted entries and folders
+        if let Some(moved) = moved.as_deref_mut() {
+            moved.removed.extend(&diff.deleted_entries);
+        }
         self.remove_entries(diff.deleted_entries, db)?;
         self.remove_folders(diff.deleted_folders, db);
 
         // update existing entries metadata
+        let mut modified_entries = HashSet::new();
         for entry in self.entries.values_mut() {
+            let file_info = entry.file_info;
             entry.read_file(&self.base_path);
+            if entry.file_info != file_info {
+                modified_entries.insert(entry.id);
+            }
         }
+        Self::store_file_data(
+            self.entries
+                .values()
+                .filter(|entry| modified_entries.contains(&entry.id)),
+            db,
+        )?;
 
         // read new entries and folders
         self.add_folders(&diff.new_folders, db)?;
-        self.add_entries(&diff.new_entries, db)?;
+        self.add_entries(&diff.new_entries, moved, db)?;
 
         Ok(())
     }
 
+    /// Add the new files of a scan pending to be matched with the moved entries,
+    /// after their content hashes have been computed.
+    fn add_pending_entries(&mut self, mut moved: MovedEntries, db: &Connection) -> Result<()> {
+        let mut paths = std::mem::take(&mut moved.pending);
+        // the files may have been added while the data was unlocked
+        paths.retain(|path| self.get_entry_id(path).is_none());
+        // the candidates may have changed while the data was unlocked
+        moved.missing = None;
+
+        self.add_entries(&paths, Some(&mut moved), db)
+    }
+
     /// Sync deleted entries, folders and tags in the database (e.g. since last luanch).
     fn sync_deleted(&self, db: &Connection) -> Result<()> {
         // mark deleted entries
@@ -864,6 +1042,12 @@ impl DatabaseData {
             [folder_id],
         )?;
 
+        // remove folder from the database
+        db.execute(
+            "UPDATE folders SET deleted = datetime('now') WHERE id = ?",
+            [folder_id],
+        )?;
+
         // remove folder
         let folder = self.folders.remove(&folder_id).unwrap();
 
@@ -1042,7 +1226,16 @@ impl DatabaseData {
             .map(|entry| self.to_absolute_path(&entry.path))
     }
 
-    fn add_entries(&mut self, paths: &[PathBuf], db: &Connection) -> Result<()> {
+    /// Add the entries of new files.
+    ///
+    /// If `moved` is given, the files not found in the database are matched with the entries
+    /// which may have been moved or renamed while the database was closed.
+    fn add_entries(
+        &mut self,
+        paths: &[PathBuf],
+        moved: Option<&mut MovedEntries>,
+        db: &Connection,
+    ) -> Result<()> {
         info!("Adding entries: {paths:#?}");
 
         debug_assert!(
@@ -1054,17 +1247,22 @@ impl DatabaseData {
         if paths.len() * 4 < self.entries.len() {
             // small number of new entries
             trace!("add_entries: small number of new entries: {}", paths.len());
-            self.add_entries_serial(paths, db)?;
+            self.add_entries_serial(paths, moved, db)?;
         } else {
             // large number of new entries
             trace!("add_entries: large number of new entries: {}", paths.len());
-            self.add_entries_batch(paths, db)?;
+            self.add_entries_batch(paths, moved, db)?;
         }
 
         Ok(())
     }
 
-    fn add_entries_serial(&mut self, paths: &[PathBuf], db: &Connection) -> Result<()> {
+    fn add_entries_serial(
+        &mut self,
+        paths: &[PathBuf],
+        mut moved: Option<&mut MovedEntries>,
+        db: &Connection,
+    ) -> Result<()> {
         // read entries file metadata
         let mut new_entries = paths
             .iter()
@@ -1087,53 +1285,105 @@ impl DatabaseData {
         let mut stmt_insert =
             db.prepare("INSERT INTO entries (file_name, folder_id) VALUES (?, ?)")?;
         let mut stmt_restore = db.prepare("UPDATE entries SET deleted = NULL WHERE id = ?")?;
+        let mut stmt_move = db.prepare(
+            "UPDATE entries SET folder_id = ?, file_name = ?, deleted = NULL WHERE id = ?",
+        )?;
+
+        let mut pending_entries = HashSet::new();
+        let mut modified_entries = HashSet::new();
 
         for entry in &mut new_entries {
             let query_row = db
                 .query_row(
-                    "SELECT id, deleted FROM entries WHERE folder_id = ? AND file_name = ?",
+                    &format!(
+                        "SELECT id, deleted, {STORED_FILE_DATA_COLUMNS}
+                        FROM entries WHERE folder_id = ? AND file_name = ?"
+                    ),
                     (entry.folder_id, entry.file_name.to_string_lossy()),
                     |row| {
                         Ok((
                             row.get::<_, EntryId>(0)?,                  // id
                             row.get::<_, Option<String>>(1)?.is_some(), // deleted
+                            read_stored_file_data(row, 2)?,
                         ))
                     },
                 )
                 .optional()?;
 
-            if let Some((id, deleted)) = query_row {
+            if let Some((id, deleted, stored)) = query_row {
                 // entry exists in database
                 entry.id = id;
+                if stored.file_info != entry.file_info {
+                    modified_entries.insert(entry.id);
+                }
+                entry.restore(stored);
 
                 if deleted {
                     // entry is deleted, restore it
                     stmt_restore.execute([entry.id])?;
                 }
-
-                entry.tag_ids = db
-                    .prepare(
-                        "SELECT entry_tag.tag_id
-                            FROM entry_tag
-                            JOIN entries ON entry_tag.entry_id = entries.id
-                            JOIN tags ON entry_tag.tag_id = tags.id
-                            WHERE entry_tag.entry_id = ?
-                            AND entries.deleted IS NULL
-                            AND tags.deleted IS NULL",
-                    )?
-                    .query_map([&entry.id], |row| row.get::<_, TagId>(0))?
-                    .filter_map(std::result::Result::ok)
-                    .collect();
             } else {
-                // entry does not exist in database
-                let id: EntryId = stmt_insert
-                    .insert((entry.file_name.to_string_lossy(), entry.folder_id))?
-                    .try_into()
-                    .unwrap();
-                entry.id = id;
+                let moved_entry = match moved.as_deref_mut() {
+                    Some(moved) => self.take_moved_entry(entry, moved, db)?,
+                    None => MovedEntryMatch::NotFound,
+                };
+
+                match moved_entry {
+                    MovedEntryMatch::Found(moved) => {
+                        // entry has been moved or renamed while the database was closed
+                        info!("Found moved entry {} at {}", moved.id, entry.path.display());
+                        entry.id = moved.id;
+                        entry.restore(moved.stored);
+                        stmt_move.execute((
+                            entry.folder_id,
+                            entry.file_name.to_string_lossy(),
+                            entry.id,
+                        ))?;
+                    }
+                    MovedEntryMatch::Pending => {
+                        // entry is added after its content hash is computed
+                        pending_entries.insert(entry.path.clone());
+                        continue;
+                    }
+                    MovedEntryMatch::NotFound => {
+                        // entry does not exist in database
+                        let id: EntryId = stmt_insert
+                            .insert((entry.file_name.to_string_lossy(), entry.folder_id))?
+                            .try_into()
+                            .unwrap();
+                        entry.id = id;
+                    }
+                }
+                modified_entries.insert(entry.id);
             }
+
+            entry.tag_ids = db
+                .prepare(
+                    "SELECT entry_tag.tag_id
+                        FROM entry_tag
+                        JOIN entries ON entry_tag.entry_id = entries.id
+                        JOIN tags ON entry_tag.tag_id = tags.id
+                        WHERE entry_tag.entry_id = ?
+                        AND entries.deleted IS NULL
+                        AND tags.deleted IS NULL",
+                )?
+                .query_map([&entry.id], |row| row.get::<_, TagId>(0))?
+                .filter_map(std::result::Result::ok)
+                .collect();
         }
 
+        new_entries.retain(|entry| !pending_entries.contains(&entry.path));
+        if let Some(moved) = moved {
+            moved.pending.extend(pending_entries);
+        }
+
+        Self::store_file_data(
+            new_entries
+                .iter()
+                .filter(|entry| modified_entries.contains(&entry.id)),
+            db,
+        )?;
+
         // Add entries to the data in memory
         for entry in &new_entries {
             self.folders
@@ -1148,7 +1398,12 @@ impl DatabaseData {
         Ok(())
     }
 
-    fn add_entries_batch(&mut self, paths: &[PathBuf], db: &Connection) -> Result<()> {
+    fn add_entries_batch(
+        &mut self,
+        paths: &[PathBuf],
+        mut moved: Option<&mut MovedEntries>,
+        db: &Connection,
+    ) -> Result<()> {
         // read entries file metadata
         let mut new_entries = paths
             .iter()
@@ -1170,48 +1425,109 @@ impl DatabaseData {
 
         // query all entry ids from database in one batch
         // and store them into a path - entry_id map
-        let query_rows = db
-            .prepare("SELECT id, file_name, folder_id, deleted FROM entries")?
+        let mut query_rows = db
+            .prepare(&format!(
+                "SELECT id, file_name, folder_id, deleted, {STORED_FILE_DATA_COLUMNS}
+                    FROM entries"
+            ))?
             .query_map([], |row| {
                 let entry_id = row.get::<_, EntryId>(0)?;
                 let file_name = row.get::<_, String>(1)?;
                 let folder_id = row.get::<_, FolderId>(2)?;
                 let deleted = row.get::<_, Option<String>>(3)?.is_some();
-                Ok(((folder_id, file_name), (entry_id, deleted)))
+                let stored = read_stored_file_data(row, 4)?;
+                Ok(((folder_id, file_name), (entry_id, deleted, stored)))
             })?
             .filter_map(std::result::Result::ok)
-            .collect::<HashMap<(FolderId, String), (EntryId, bool)>>();
+            .collect::<HashMap<(FolderId, String), (EntryId, bool, StoredFileData)>>();
 
         // match queried rows with entries and perform corresponding actions
 
         let mut stmt_insert =
             db.prepare("INSERT INTO entries (file_name, folder_id) VALUES (?, ?)")?;
         let mut stmt_restore = db.prepare("UPDATE entries SET deleted = NULL WHERE id = ?")?;
+        let mut stmt_move = db.prepare(
+            "UPDATE entries SET folder_id = ?, file_name = ?, deleted = NULL WHERE id = ?",
+        )?;
 
-        for entry in &mut new_entries {
+        let mut unmatched_entries = Vec::new();
+        let mut modified_entries = HashSet::new();
+
+        for (i, entry) in new_entries.iter_mut().enumerate() {
             // find matching entry in queried rows
             let file_name = entry.file_name.to_string_lossy();
-            if let Some((id, deleted)) = query_rows
-                .get(&(entry.folder_id, file_name.into()))
-                .copied()
+            if let Some((id, deleted, stored)) =
+                query_rows.remove(&(entry.folder_id, file_name.into()))
             {
                 // if entry already exists in database, set id
                 entry.id = id;
+                if stored.file_info != entry.file_info {
+                    modified_entries.insert(entry.id);
+                }
+                entry.restore(stored);
 
                 if deleted {
                     // entry is deleted, restore it
                     stmt_restore.execute([entry.id])?;
                 }
             } else {
-                // if entry does not exist in database, insert entry and set id
-                let id: EntryId = stmt_insert
-                    .insert((entry.file_name.to_string_lossy(), entry.folder_id))?
-                    .try_into()
-                    .unwrap();
-                entry.id = id;
+                unmatched_entries.push(i);
+            }
+        }
+
+        // match the remaining entries by file identity
+        // after all entries matched by path have been claimed
+        let mut pending_entries = HashSet::new();
+
+        for i in unmatched_entries {
+            let entry = &mut new_entries[i];
+
+            let moved_entry = match moved.as_deref_mut() {
+                Some(moved) => self.take_moved_entry(entry, moved, db)?,
+                None => MovedEntryMatch::NotFound,
+            };
+
+            match moved_entry {
+                MovedEntryMatch::Found(moved) => {
+                    // entry has been moved or renamed while the database was closed
+                    info!("Found moved entry {} at {}", moved.id, entry.path.display());
+                    entry.id = moved.id;
+                    entry.restore(moved.stored);
+                    stmt_move.execute((
+                        entry.folder_id,
+                        entry.file_name.to_string_lossy(),
+                        entry.id,
+                    ))?;
+                }
+                MovedEntryMatch::Pending => {
+                    // entry is added after its content hash is computed
+                    pending_entries.insert(entry.path.clone());
+                    continue;
+                }
+                MovedEntryMatch::NotFound => {
+                    // if entry does not exist in database, insert entry and set id
+                    let id: EntryId = stmt_insert
+                        .insert((entry.file_name.to_string_lossy(), entry.folder_id))?
+                        .try_into()
+                        .unwrap();
+                    entry.id = id;
+                }
             }
+            modified_entries.insert(entry.id);
+        }
+
+        new_entries.retain(|entry| !pending_entries.contains(&entry.path));
+        if let Some(moved) = moved {
+            moved.pending.extend(pending_entries);
         }
 
+        Self::store_file_data(
+            new_entries
+                .iter()
+                .filter(|entry| modified_entries.contains(&entry.id)),
+            db,
+        )?;
+
         // Add entries to the data in memory
         for entry in &new_entries {
             self.folders
@@ -1249,6 +1565,151 @@ impl DatabaseData {
         Ok(())
     }
 
+    /// Query the entries in the database whose files no longer exist at their recorded paths,
+    /// which may have been moved or renamed.
+    ///
+    /// Only the entries missing since the database was closed and the entries in `removed`
+    /// are queried, so that the entries deleted before are not restored.
+    fn query_missing_entries(
+        &self,
+        removed: &HashSet<EntryId>,
+        db: &Connection,
+    ) -> Result<Vec<MissingEntry>> {
+        let missing_entries = db
+            .prepare(&format!(
+                "SELECT id, folder_id, file_name, deleted, {STORED_FILE_DATA_COLUMNS}
+                    FROM entries WHERE file_size IS NOT NULL"
+            ))?
+            .query_map([], |row| {
+                Ok((
+                    row.get::<_, EntryId>(0)?,
+                    row.get::<_, FolderId>(1)?,
+                    row.get::<_, String>(2)?,
+                    row.get::<_, Option<String>>(3)?.is_some(),
+                    read_stored_file_data(row, 4)?,
+                ))
+            })?
+            .filter_map(std::result::Result::ok)
+            .filter(|(id, folder_id, file_name, deleted, _)| {
+                !self.entries.contains_key(id)
+                    && (!deleted || removed.contains(id))
+                    && self.folders.get(folder_id).is_none_or(|folder| {
+                        !self.to_absolute_path(&folder.path.join(file_name)).exists()
+                    })
+            })
+            .map(|(id, _, _, _, stored)| MissingEntry { id, stored })
+            .collect();
+
+        Ok(missing_entries)
+    }
+
+    /// Find the missing entry with the same file identity as `entry`,
+    /// and remove it from the missing entries.
+    ///
+    /// An entry is matched by its file ID, size and modification time first,
+    /// and then by its content hash. If the content hash of the file has not been computed yet,
+    /// the entry is pending to be matched after it is computed.
+    fn take_moved_entry(
+        &self,
+        entry: &Entry,
+        moved: &mut MovedEntries,
+        db: &Connection,
+    ) -> Result<MovedEntryMatch> {
+        let Some(file_info) = entry.file_info else {
+            return Ok(MovedEntryMatch::NotFound);
+        };
+
+        if moved.missing.is_none() {
+            moved.missing = Some(self.query_missing_entries(&moved.removed, db)?);
+        }
+        let missing_entries = moved.missing.as_mut().unwrap();
+
+        if let Some(pos) = missing_entries.iter().position(|missing| {
+            file_info.file_id.is_some() && missing.stored.file_info == Some(file_info)
+        }) {
+            return Ok(MovedEntryMatch::Found(missing_entries.swap_remove(pos)));
+        }
+
+        let mut candidates = missing_entries
+            .iter()
+            .enumerate()
+            .filter(|(_, missing)| {
+                missing.stored.content_hash.is_some()
+                    && missing.stored.file_info.map(|info| info.size) == Some(file_info.size)
+            })
+            .peekable();
+        if candidates.peek().is_none() {
+            return Ok(MovedEntryMatch::NotFound);
+        }
+
+        let Some(hashes) = &moved.hashes else {
+            return Ok(MovedEntryMatch::Pending);
+        };
+        // the hash is outdated if the file has been modified since it was computed
+        let hash = hashes
+            .get(&entry.path)
+            .filter(|(info, _)| *info == file_info)
+            .map(|(_, hash)| hash);
+        let pos = candidates
+            .find(|(_, missing)| hash.is_some() && missing.stored.content_hash.as_ref() == hash)
+            .map(|(pos, _)| pos);
+
+        Ok(pos.map_or(MovedEntryMatch::NotFound, |pos| {
+            MovedEntryMatch::Found(missing_entries.swap_remove(pos))
+        }))
+    }
+
+    /// Store the file information and the data computed from the file of entries into the database.
+    fn store_file_data<'a>(
+        entries: impl IntoIterator<Item = &'a Entry>,
+        db: &Connection,
+    ) -> Result<()> {
+        let tx = db.unchecked_transaction()?;
+        {
+            let mut stmt = tx.prepare(
+                "UPDATE entries
+                    SET file_size = ?, file_mtime = ?, file_id = ?, content_hash = ?, audio_hash = ?,
+                        loudness = ?, true_peak = ?, peak = ?, rms = ?, loudness_range = ?,
+                        dc_offset = ?, clipped_samples = ?
+                    WHERE id = ?",
+            )?;
+            for entry in entries {
+                let file_info = entry.file_info;
+                stmt.execute((
+                    file_info.map(|info| info.size),
+                    file_info.map(|info| info.modified),
+                    file_info.and_then(|info| info.file_id),
+                    &entry.content_hash,
+                    &entry.audio_hash,
+                    entry.loudness.map(|loudness| loudness.integrated),
+                    entry.loudness.map(|loudness| loudness.true_peak),
+                    entry.analysis.map(|analysis| analysis.peak),
+                    entry.analysis.map(|analysis| analysis.rms),
+                    entry.analysis.map(|analysis| analysis.loudness_range),
+                    entry.analysis.map(|analysis| analysis.dc_offset),
+                    entry.analysis.map(|analysis| analysis.clipped_samples),
+                    entry.id,
+                ))?;
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Reread the file of an existing entry, e.g. after the file is modified.
+    fn reread_entry(&mut self, entry_id: EntryId, db: &Connection) -> Result<()> {
+        let entry = self.entries.get_mut(&entry_id).unwrap();
+        let file_info = entry.file_info;
+        entry.read_file(&self.base_path);
+
+        if entry.file_info != file_info {
+            Self::store_file_data([&*entry], db)?;
+            WaveformCache::new(&self.base_path).invalidate(entry_id);
+        }
+
+        Ok(())
+    }
+
     fn remove_entry(&mut self, entry_id: EntryId, db: &Connection) -> Result<()> {
         info!("Removing entry: {entry_id}");
 
@@ -1717,6 +2178,45 @@ impl DatabaseData {
     }
 }
 
+/// The columns of [`StoredFileData`], in the order read by [`read_stored_file_data`].
+const STORED_FILE_DATA_COLUMNS: &str = "file_size, file_mtime, file_id, content_hash, audio_hash, \
+    loudness, true_peak, peak, rms, loudness_range, dc_offset, clipped_samples";
+
+/// Read the [`StoredFileData`] from the columns [`STORED_FILE_DATA_COLUMNS`] starting at column `idx`.
+fn read_stored_file_data(row: &rusqlite::Row, idx: usize) -> rusqlite::Result<StoredFileData> {
+    let size = row.get::<_, Option<u64>>(idx)?;
+    let modified = row.get::<_, Option<i64>>(idx + 1)?;
+    let file_id = row.get::<_, Option<u64>>(idx + 2)?;
+    let integrated = row.get::<_, Option<f32>>(idx + 5)?;
+    let true_peak = row.get::<_, Option<f32>>(idx + 6)?;
+    let peak = row.get::<_, Option<f32>>(idx + 7)?;
+    Ok(StoredFileData {
+        file_info: size.zip(modified).map(|(size, modified)| FileInfo {
+            size,
+            modified,
+            file_id,
+        }),
+        content_hash: row.get(idx + 3)?,
+        audio_hash: row.get(idx + 4)?,
+        loudness: integrated
+            .zip(true_peak)
+            .map(|(integrated, true_peak)| Loudness {
+                integrated,
+                true_peak,
+            }),
+        analysis: match peak {
+            Some(peak) => Some(Analysis {
+                peak,
+                rms: row.get(idx + 8)?,
+                loudness_range: row.get(idx + 9)?,
+                dc_offset: row.get(idx + 10)?,
+                clipped_samples: row.get(idx + 11)?,
+            }),
+            None => None,
+        },
+    })
+}
+
 fn is_audio_file(path: &Path) -> bool {
     match path.extension() {
         None => false,
@@ -0,0 +1,199 @@
+use super::entry::FileInfo;
+use super::{Database, DatabaseData, DatabaseEmitter, EntryId, Result};
+use crate::core::analysis::{analyze, Analysis};
+use crate::core::loudness::Loudness;
+use crate::core::silence::{detect_silence, Silence, SilenceOptions};
+
+use log::{debug, info, warn};
+use std::collections::HashSet;
+use std::path::PathBuf;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::thread::spawn;
+
+/// An entry to be analyzed.
+struct AnalysisJob {
+    entry_id: EntryId,
+    /// Absolute path to the file.
+    path: PathBuf,
+    file_info: Option<FileInfo>,
+}
+
+impl<E> Database<E>
+where
+    E: DatabaseEmitter + Send + Sync + 'static,
+{
+    /// Get the loudness of an entry, analyzing it if it has not been analyzed yet.
+    ///
+    /// Returns `None` if the entry does not exist.
+    pub fn get_loudness(&self, entry_id: EntryId) -> Result<Option<Loudness>> {
+        // analyze the file without holding the lock
+        let job = {
+            let data = self.data.read().unwrap();
+            let Some(entry) = data.get_entry(entry_id) else {
+                return Ok(None);
+            };
+            if let Some(loudness) = entry.loudness {
+                return Ok(Some(loudness));
+            }
+            AnalysisJob {
+                entry_id,
+                path: data.to_absolute_path(&entry.path),
+                file_info: entry.file_info,
+            }
+        };
+
+        let (loudness, analysis) = analyze(&job.path)?;
+        if self.set_analysis(&job, loudness, analysis)? {
+            self.emitter.on_files_updated(false);
+        }
+
+        Ok(Some(loudness))
+    }
+
+    /// Get the leading and trailing silence of an entry detected with `options`,
+    /// detecting it if it has not been detected with these options yet.
+    ///
+    /// Returns `None` if the entry does not exist.
+    pub fn get_silence(
+        &self,
+        entry_id: EntryId,
+        options: SilenceOptions,
+    ) -> Result<Option<Silence>> {
+        // detect the silence without holding the lock
+        let job = {
+            let data = self.data.read().unwrap();
+            let Some(entry) = data.get_entry(entry_id) else {
+                return Ok(None);
+            };
+            if let Some(silence) = entry.silence.filter(|silence| silence.options == options) {
+                return Ok(Some(silence));
+            }
+            AnalysisJob {
+                entry_id,
+                path: data.to_absolute_path(&entry.path),
+                file_info: entry.file_info,
+            }
+        };
+
+        let silence = detect_silence(&job.path, options)?;
+
+        // only cached in memory, as it depends on the options
+        let mut data = self.data.write().unwrap();
+        if let Some(entry) = data.entries.get_mut(&entry_id) {
+            if entry.file_info == job.file_info {
+                entry.silence = Some(silence);
+            }
+        }
+
+        Ok(Some(silence))
+    }
+
+    /// Start analyzing all entries that have not been analyzed yet in a background thread,
+    /// if it is not running already.
+    ///
+    /// The thread stops when all entries have been analyzed, or the database has been dropped.
+    pub fn start_analysis(self: &Arc<Self>) {
+        if self.analysis_running.swap(true, Ordering::AcqRel) {
+            return;
+        }
+
+        let database = Arc::downgrade(self);
+        spawn(move || {
+            debug!("start analysis thread");
+
+            // entries failed to be analyzed, which are not retried in this run
+            let mut failed_entries = HashSet::new();
+            let mut n_analyzed = 0;
+
+            'outer: loop {
+                let jobs = {
+                    let Some(database) = database.upgrade() else {
+                        break;
+                    };
+                    let jobs = database
+                        .data
+                        .read()
+                        .unwrap()
+                        .get_analysis_jobs(&failed_entries);
+                    if jobs.is_empty() {
+                        database.analysis_running.store(false, Ordering::Release);
+                        break;
+                    }
+                    jobs
+                };
+
+                for job in jobs {
+                    let Some(database) = database.upgrade() else {
+                        break 'outer;
+                    };
+
+                    let result =
+                        analyze(&job.path)
+                            .map_err(Into::into)
+                            .and_then(|(loudness, analysis)| {
+                                database.set_analysis(&job, loudness, analysis)
+                            });
+                    match result {
+                        Ok(_) => {
+                            n_analyzed += 1;
+                            database.emitter.on_files_updated(false);
+                        }
+                        Err(err) => {
+                            warn!("Failed to analyze {}: {err}", job.path.display());
+                            failed_entries.insert(job.entry_id);
+                        }
+                    }
+                }
+            }
+
+            info!("Analyzed {n_analyzed} entries");
+            debug!("stop analysis thread");
+        });
+    }
+
+    /// Store the analysis of an entry, if the file has not been modified since it was analyzed.
+    ///
+    /// Returns whether the analysis has been stored.
+    fn set_analysis(
+        &self,
+        job: &AnalysisJob,
+        loudness: Loudness,
+        analysis: Analysis,
+    ) -> Result<bool> {
+        let mut data = self.data.write().unwrap();
+        let db = self.db.lock().unwrap();
+
+        let Some(entry) = data.entries.get_mut(&job.entry_id) else {
+            return Ok(false);
+        };
+        if entry.file_info != job.file_info {
+            debug!("File of entry {} modified while analyzing", job.entry_id);
+            return Ok(false);
+        }
+
+        entry.loudness = Some(loudness);
+        entry.analysis = Some(analysis);
+        DatabaseData::store_file_data([&*entry], &db)?;
+
+        Ok(true)
+    }
+}
+
+impl DatabaseData {
+    /// Return the entries that have not been analyzed yet, except `skipped_entries`.
+    fn get_analysis_jobs(&self, skipped_entries: &HashSet<EntryId>) -> Vec<AnalysisJob> {
+        self.entries
+            .values()
+            .filter(|entry| {
+                (entry.loudness.is_none() || entry.analysis.is_none())
+                    && !skipped_entries.contains(&entry.id)
+            })
+            .map(|entry| AnalysisJob {
+                entry_id: entry.id,
+                path: self.to_absolute_path(&entry.path),
+                file_info: entry.file_info,
+            })
+            .collect()
+    }
+}
@@ -1,12 +1,17 @@
 use super::folder::FolderId;
+use super::hash::HashMode;
 use super::tag::TagId;
 use super::Result;
+use crate::core::analysis::Analysis;
+use crate::core::loudness::Loudness;
 use crate::core::player::get_format_reader;
+use crate::core::silence::Silence;
 
 use log::warn;
 use std::collections::HashSet;
 use std::ffi::OsString;
 use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
 
 use symphonia::core::formats::TrackType;
 use symphonia::core::meta::StandardTag;
@@ -21,6 +26,17 @@ pub struct Entry {
     pub file_name: OsString,
     pub metadata: Option<Metadata>,
     pub tag_ids: HashSet<TagId>,
+    pub file_info: Option<FileInfo>,
+    /// Hash of the file content, computed lazily.
+    pub content_hash: Option<String>,
+    /// Hash of the decoded audio samples, computed lazily.
+    pub audio_hash: Option<String>,
+    /// Loudness of the audio, measured lazily.
+    pub loudness: Option<Loudness>,
+    /// Level statistics of the audio, measured together with the loudness.
+    pub analysis: Option<Analysis>,
+    /// Leading and trailing silence of the audio, detected lazily and not stored in the database.
+    pub silence: Option<Silence>,
 }
 
 pub struct Metadata {
@@ -30,6 +46,54 @@ pub struct Metadata {
     pub duration: Option<f32>,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FileInfo {
+    pub size: u64,
+    /// Last modification time in nanoseconds since the Unix epoch.
+    pub modified: i64,
+    /// The ID of the file in the file system (i.e. inode number), if supported by the platform.
+    pub file_id: Option<u64>,
+}
+
+impl FileInfo {
+    pub fn read(path: &Path) -> Result<Self> {
+        let metadata = path.metadata()?;
+        let modified = metadata
+            .modified()?
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |duration| {
+                i64::try_from(duration.as_nanos()).unwrap_or(i64::MAX)
+            });
+
+        Ok(Self {
+            size: metadata.len(),
+            modified,
+            file_id: get_file_id(&metadata),
+        })
+    }
+}
+
+#[cfg(unix)]
+fn get_file_id(metadata: &std::fs::Metadata) -> Option<u64> {
+    use std::os::unix::fs::MetadataExt;
+    Some(metadata.ino())
+}
+
+#[cfg(not(unix))]
+fn get_file_id(_metadata: &std::fs::Metadata) -> Option<u64> {
+    // file index on Windows is not available in stable Rust
+    None
+}
+
+/// The columns of an entry stored in the database that are only valid for a specific version of the file.
+pub struct StoredFileData {
+    pub file_info: Option<FileInfo>,
+    pub content_hash: Option<String>,
+    pub audio_hash: Option<String>,
+    pub loudness: Option<Loudness>,
+    pub analysis: Option<Analysis>,
+}
+
 impl Entry {
     pub fn new(path: PathBuf, folder_id: FolderId) -> Self {
         debug_assert!(path.is_relative(), "Path must be relative");
@@ -43,10 +107,35 @@ impl Entry {
             file_name,
             metadata: None,
             tag_ids: HashSet::new(),
+            file_info: None,
+            content_hash: None,
+            audio_hash: None,
+            loudness: None,
+            analysis: None,
+            silence: None,
         }
     }
 
     pub fn read_file(&mut self, base_path: &Path) {
+        let file_info = FileInfo::read(&base_path.join(&self.path))
+            .inspect_err(|err| {
+                warn!(
+                    "Failed to read file info of file {}: {:?}",
+                    base_path.join(&self.path).display(),
+                    err
+                );
+            })
+            .ok();
+        if file_info != self.file_info {
+            // file modified, invalidate data computed from the file
+            self.content_hash = None;
+            self.audio_hash = None;
+            self.loudness = None;
+            self.analysis = None;
+            self.silence = None;
+        }
+        self.file_info = file_info;
+
         let metadata = self.read_metadata(base_path);
 
         match metadata {
@@ -59,6 +148,24 @@ impl Entry {
         }
     }
 
+    /// Restore the data stored in the database, if it was computed from the current file.
+    pub fn restore(&mut self, stored: StoredFileData) {
+        if stored.file_info.is_none() || stored.file_info != self.file_info {
+            return;
+        }
+        self.content_hash = stored.content_hash;
+        self.audio_hash = stored.audio_hash;
+        self.loudness = stored.loudness;
+        self.analysis = stored.analysis;
+    }
+
+    pub fn get_hash(&self, mode: HashMode) -> Option<&str> {
+        match mode {
+            HashMode::Content => self.content_hash.as_deref(),
+            HashMode::Audio => self.audio_hash.as_deref(),
+        }
+    }
+
     #[allow(clippy::cast_precision_loss)]
     #[allow(clippy::cast_possible_truncation)]
     fn read_metadata(&self, base_path: &Path) -> Result<Metadata> {
@@ -0,0 +1,467 @@
+use super::import::get_available_path;
+use super::{CollisionPolicy, Database, DatabaseData, DatabaseEmitter, EntryId, Error, Result};
+use crate::core::decoder::SampleDecoder;
+use crate::core::resampler::Resampler;
+use crate::core::wav::{SampleFormat, WavSpec, WavWriter};
+
+use log::{info, warn};
+use std::collections::HashSet;
+use std::ffi::OsStr;
+use std::fmt::Write;
+use std::fs::{copy, create_dir_all, remove_file, write};
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+/// Characters that are not allowed in file names on common file systems.
+const INVALID_FILE_NAME_CHARS: &[char] = &['/', '\\', ':', '*', '?', '"', '<', '>', '|'];
+/// The lowest and the highest sample rates in Hz the files can be converted to.
+const MIN_SAMPLE_RATE: u32 = 8000;
+const MAX_SAMPLE_RATE: u32 = 768_000;
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum ManifestFormat {
+    Csv,
+    Json,
+}
+
+/// Options to convert the exported files to WAV.
+#[derive(Deserialize, Debug, Clone, Copy)]
+#[serde(rename_all = "camelCase")]
+pub struct ConvertOptions {
+    pub sample_format: SampleFormat,
+    /// The sample rate of the exported files. The original sample rate is kept if not set.
+    pub sample_rate: Option<u32>,
+}
+
+impl ConvertOptions {
+    pub fn is_valid(self) -> bool {
+        self.sample_rate
+            .is_none_or(|sample_rate| (MIN_SAMPLE_RATE..=MAX_SAMPLE_RATE).contains(&sample_rate))
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportOptions {
+    /// Recreate the folder structure of the entries in the target directory.
+    #[serde(default)]
+    pub preserve_structure: bool,
+    /// Convert the files to WAV. The files are copied as is if not set.
+    pub convert: Option<ConvertOptions>,
+    /// The pattern of the exported file names without extension, e.g. `{index}_{name}`.
+    ///
+    /// Supported placeholders are `{name}`, `{folder}`, `{index}`, `{title}`, `{artist}`,
+    /// `{album}` and `{tags}`.
+    pub rename_pattern: Option<String>,
+    /// Write a manifest of the exported files into the target directory.
+    pub manifest: Option<ManifestFormat>,
+    pub collision_policy: CollisionPolicy,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "kind", content = "message")]
+#[serde(rename_all = "camelCase")]
+pub enum ExportStatus {
+    Exported,
+    Overwritten,
+    Renamed,
+    Skipped(String),
+    Failed(String),
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportResult {
+    pub entry_id: EntryId,
+    /// Absolute path to the exported file, if it has been exported.
+    pub dst_path: Option<PathBuf>,
+    pub status: ExportStatus,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportProgress {
+    pub processed: usize,
+    pub total: usize,
+    pub result: ExportResult,
+}
+
+/// A row of the manifest.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct ManifestRecord {
+    /// Path to the exported file, relative to the target directory.
+    file: String,
+    /// Path to the source file, relative to the database.
+    source: String,
+    title: Option<String>,
+    artist: Option<String>,
+    album: Option<String>,
+    duration: Option<f32>,
+    tags: Vec<String>,
+}
+
+/// A single entry to be exported.
+struct ExportJob {
+    entry_id: EntryId,
+    /// Absolute path to the source file.
+    src_path: PathBuf,
+    /// Relative path to the source file.
+    relative_path: PathBuf,
+    title: Option<String>,
+    artist: Option<String>,
+    album: Option<String>,
+    duration: Option<f32>,
+    tags: Vec<String>,
+}
+
+impl<E> Database<E>
+where
+    E: DatabaseEmitter + Send + Sync + 'static,
+{
+    /// Export entries into the directory `target_dir`.
+    ///
+    /// # Arguments
+    ///
+    /// * `entry_ids` - The entries to export.
+    /// * `target_dir` - The absolute path to the directory to export into.
+    /// * `options` - The export options.
+    /// * `on_progress` - Called after each entry has been processed.
+    pub fn export_entries<F>(
+        &self,
+        entry_ids: &[EntryId],
+        target_dir: &Path,
+        options: &ExportOptions,
+        on_progress: F,
+    ) -> Result<Vec<ExportResult>>
+    where
+        F: Fn(ExportProgress),
+    {
+        info!(
+            "Exporting {} entries to {} with {options:?}",
+            entry_ids.len(),
+            target_dir.display()
+        );
+
+        if let Some(convert) = options.convert.filter(|convert| !convert.is_valid()) {
+            return Err(Error::InvalidSampleRate(
+                convert.sample_rate.unwrap_or_default(),
+            ));
+        }
+
+        // collect the entries to export, and release the lock before processing the files
+        let jobs = self.data.read().unwrap().collect_export_jobs(entry_ids);
+
+        create_dir_all(target_dir)?;
+
+        let total = jobs.len();
+        let mut results = Vec::new();
+        let mut records = Vec::new();
+        let mut exported_paths = HashSet::new();
+        for (index, job) in jobs.into_iter().enumerate() {
+            let result = export_job(&job, index, total, target_dir, options, &exported_paths)
+                .unwrap_or_else(|err| {
+                    warn!("Failed to export {}: {err}", job.src_path.display());
+                    ExportResult {
+                        entry_id: job.entry_id,
+                        dst_path: None,
+                        status: ExportStatus::Failed(err.to_string()),
+                    }
+                });
+
+            if let Some(dst_path) = &result.dst_path {
+                exported_paths.insert(dst_path.clone());
+                records.push(ManifestRecord {
+                    file: dst_path
+                        .strip_prefix(target_dir)
+                        .unwrap_or(dst_path)
+                        .to_string_lossy()
+                        .into(),
+                    source: job.relative_path.to_string_lossy().into(),
+                    title: job.title,
+                    artist: job.artist,
+                    album: job.album,
+                    duration: job.duration,
+                    tags: job.tags,
+                });
+            }
+
+            results.push(result.clone());
+            on_progress(ExportProgress {
+                processed: results.len(),
+                total,
+                result,
+            });
+        }
+
+        match options.manifest {
+            Some(ManifestFormat::Csv) => {
+                write(target_dir.join("manifest.csv"), to_csv(&records))?;
+            }
+            Some(ManifestFormat::Json) => {
+                write(
+                    target_dir.join("manifest.json"),
+                    serde_json::to_string_pretty(&records).unwrap(),
+                )?;
+            }
+            None => {}
+        }
+
+        Ok(results)
+    }
+}
+
+impl DatabaseData {
+    fn collect_export_jobs(&self, entry_ids: &[EntryId]) -> Vec<ExportJob> {
+        entry_ids
+            .iter()
+            .filter_map(|entry_id| {
+                let Some(entry) = self.entries.get(entry_id) else {
+                    warn!("Failed to export entry {entry_id}: entry not found");
+                    return None;
+                };
+                let metadata = entry.metadata.as_ref();
+
+                let mut tags = self
+                    .get_tags_for_entry(entry.id)
+                    .into_iter()
+                    .map(|tag| tag.name.clone())
+                    .collect::<Vec<_>>();
+                tags.sort_unstable();
+
+                Some(ExportJob {
+                    entry_id: entry.id,
+                    src_path: self.to_absolute_path(&entry.path),
+                    relative_path: entry.path.clone(),
+                    title: metadata.and_then(|metadata| metadata.title.clone()),
+                    artist: metadata.and_then(|metadata| metadata.artist.clone()),
+                    album: metadata.and_then(|metadata| metadata.album.clone()),
+                    duration: metadata.and_then(|metadata| metadata.duration),
+                    tags,
+                })
+            })
+            .collect()
+    }
+}
+
+/// Export a single entry.
+///
+/// A file already written by this export, in `exported_paths`, is never overwritten nor skipped for,
+/// but the entry is renamed, e.g. if the rename pattern gives several entries the same name.
+fn export_job(
+    job: &ExportJob,
+    index: usize,
+    total: usize,
+    target_dir: &Path,
+    options: &ExportOptions,
+    exported_paths: &HashSet<PathBuf>,
+) -> Result<ExportResult> {
+    let mut dst_dir = target_dir.to_owned();
+    if options.preserve_structure {
+        if let Some(parent) = job.relative_path.parent() {
+            dst_dir.push(parent);
+        }
+    }
+    create_dir_all(&dst_dir)?;
+
+    let stem = job.relative_path.file_stem().unwrap_or_default();
+    let mut file_name = match &options.rename_pattern {
+        Some(pattern) => {
+            let file_name = render_pattern(pattern, job, index, total);
+            if file_name.is_empty() {
+                stem.to_owned()
+            } else {
+                file_name.into()
+            }
+        }
+        None => stem.to_owned(),
+    };
+    let extension = if options.convert.is_some() {
+        Some(OsStr::new("wav"))
+    } else {
+        job.relative_path.extension()
+    };
+    if let Some(extension) = extension {
+        file_name.push(".");
+        file_name.push(extension);
+    }
+
+    let mut dst_path = dst_dir.join(file_name);
+    let mut status = ExportStatus::Exported;
+
+    if exported_paths.contains(&dst_path) {
+        dst_path = get_available_path(&dst_path);
+        status = ExportStatus::Renamed;
+    } else if dst_path.exists() {
+        match options.collision_policy {
+            CollisionPolicy::Skip => {
+                return Ok(ExportResult {
+                    entry_id: job.entry_id,
+                    dst_path: None,
+                    status: ExportStatus::Skipped("file already exists".into()),
+                });
+            }
+            CollisionPolicy::Overwrite => status = ExportStatus::Overwritten,
+            CollisionPolicy::Rename => {
+                dst_path = get_available_path(&dst_path);
+                status = ExportStatus::Renamed;
+            }
+        }
+    }
+
+    let exported = match options.convert {
+        Some(convert) => convert_file(&job.src_path, &dst_path, convert),
+        None => copy(&job.src_path, &dst_path)
+            .map(|_| ())
+            .map_err(Into::into),
+    };
+    if let Err(err) = exported {
+        // remove the partially written file
+        let _ = remove_file(&dst_path);
+        return Err(err);
+    }
+
+    info!(
+        "Exported {} to {}",
+        job.src_path.display(),
+        dst_path.display()
+    );
+
+    Ok(ExportResult {
+        entry_id: job.entry_id,
+        dst_path: Some(dst_path),
+        status,
+    })
+}
+
+/// Decode the file at `src_path` and write it to `dst_path` as WAV.
+fn convert_file(src_path: &Path, dst_path: &Path, options: ConvertOptions) -> Result<()> {
+    let mut decoder = SampleDecoder::open(src_path)?;
+    let spec = *decoder.spec();
+    let sample_rate = options.sample_rate.unwrap_or(spec.sample_rate);
+
+    let mut writer = WavWriter::create(
+        dst_path,
+        WavSpec {
+            n_channels: spec.n_channels,
+            sample_rate,
+            sample_format: options.sample_format,
+        },
+    )?;
+
+    let mut resampler = (sample_rate != spec.sample_rate)
+        .then(|| Resampler::new(spec.n_channels, spec.sample_rate, sample_rate));
+    let mut resampled = Vec::new();
+
+    while let Some(samples) = decoder.next_samples()? {
+        if let Some(resampler) = &mut resampler {
+            resampled.clear();
+            resampler.process(samples, &mut resampled);
+            writer.write_samples(&resampled)?;
+        } else {
+            writer.write_samples(samples)?;
+        }
+    }
+
+    if let Some(resampler) = &mut resampler {
+        resampled.clear();
+        resampler.flush(&mut resampled);
+        writer.write_samples(&resampled)?;
+    }
+
+    writer.finalize()?;
+    Ok(())
+}
+
+/// Replace the placeholders in `pattern` with the values of the entry.
+///
+/// Unknown placeholders are kept as is.
+/// Characters that are invalid in file names are replaced with `_`.
+fn render_pattern(pattern: &str, job: &ExportJob, index: usize, total: usize) -> String {
+    let mut result = String::new();
+    let mut rest = pattern;
+
+    while let Some(start) = rest.find('{') {
+        result.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        let Some(end) = rest.find('}') else {
+            break;
+        };
+        let placeholder = &rest[1..end];
+        match placeholder {
+            "name" => {
+                result.push_str(
+                    &job.relative_path
+                        .file_stem()
+                        .unwrap_or_default()
+                        .to_string_lossy(),
+                );
+            }
+            "folder" => {
+                let folder = job
+                    .relative_path
+                    .parent()
+                    .and_then(Path::file_name)
+                    .unwrap_or_default();
+                result.push_str(&folder.to_string_lossy());
+            }
+            "index" => {
+                // pad the index to the same width for all entries
+                let width = total.to_string().len();
+                write!(result, "{:0width$}", index + 1).unwrap();
+            }
+            "title" => result.push_str(job.title.as_deref().unwrap_or_default()),
+            "artist" => result.push_str(job.artist.as_deref().unwrap_or_default()),
+            "album" => result.push_str(job.album.as_deref().unwrap_or_default()),
+            "tags" => result.push_str(&job.tags.join(",")),
+            _ => result.push_str(&rest[..=end]),
+        }
+        rest = &rest[end + 1..];
+    }
+    result.push_str(rest);
+
+    result
+        .chars()
+        .map(|c| {
+            if c.is_control() || INVALID_FILE_NAME_CHARS.contains(&c) {
+                '_'
+            } else {
+                c
+            }
+        })
+        .collect::<String>()
+        .trim()
+        .to_owned()
+}
+
+fn to_csv(records: &[ManifestRecord]) -> String {
+    fn escape(field: &str) -> String {
+        if field.contains([',', '"', '\n', '\r']) {
+            format!("\"{}\"", field.replace('"', "\"\""))
+        } else {
+            field.to_owned()
+        }
+    }
+
+    let mut csv = String::from("file,source,title,artist,album,duration,tags\n");
+    for record in records {
+        let fields = [
+            escape(&record.file),
+            escape(&record.source),
+            escape(record.title.as_deref().unwrap_or_default()),
+            escape(record.artist.as_deref().unwrap_or_default()),
+            escape(record.album.as_deref().unwrap_or_default()),
+            record
+                .duration
+                .map(|duration| duration.to_string())
+                .unwrap_or_default(),
+            escape(&record.tags.join(";")),
+        ];
+        csv.push_str(&fields.join(","));
+        csv.push('\n');
+    }
+    csv
+}
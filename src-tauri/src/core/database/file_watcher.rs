@@ -72,13 +72,9 @@ where
                             if event.need_rescan() {
                                 debug!("Rescanning directory");
 
-                                self.data
-                                    .write()
-                                    .unwrap()
-                                    .scan(&mut self.db.lock().unwrap())
-                                    .unwrap_or_else(|err| {
-                                        warn!("Failed to scan directory: {err}");
-                                    });
+                                self.scan().unwrap_or_else(|err| {
+                                    warn!("Failed to scan directory: {err}");
+                                });
 
                                 break; // skip the rest of the events
                             }
@@ -93,6 +89,7 @@ where
 
                         if updated {
                             self.emitter.on_files_updated(true);
+                            self.start_analysis();
                         }
                     }
                 }
@@ -281,15 +278,11 @@ fn file_created<E>(path: &Path, database: &Database<E>) -> FileWatcherResult<()>
 
     match data.get_entry_id(&relative_path) {
         // entry does not exist, add it
-        None => data.add_entries(&[relative_path], &database.db.lock().unwrap())?,
+        None => data.add_entries(&[relative_path], None, &database.db.lock().unwrap())?,
 
         // entry already exists, reread it
         Some(entry_id) => {
-            let base_path = data.base_path.clone();
-            data.entries
-                .get_mut(&entry_id)
-                .unwrap()
-                .read_file(&base_path);
+            data.reread_entry(entry_id, &database.db.lock().unwrap())?;
         }
     }
     Ok(())
@@ -359,7 +352,7 @@ fn file_moved<E>(
         )?;
     } else {
         // if old entry does not exist, add new entry
-        data.add_entries(&[relative_new_path], &database.db.lock().unwrap())?;
+        data.add_entries(&[relative_new_path], None, &database.db.lock().unwrap())?;
     }
     Ok(())
 }
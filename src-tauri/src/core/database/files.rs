@@ -1,11 +1,41 @@
-use super::{Database, DatabaseData, DatabaseEmitter, EntryId, Error, FolderId, Result};
+use super::{
+    is_hidden_file, Database, DatabaseData, DatabaseEmitter, EntryId, Error, FolderId, Result,
+    ROOT_FOLDER_ID,
+};
 
-use log::warn;
-use std::fs::{copy, rename};
-use std::path::Path;
+use crate::core::decoder::SampleDecoder;
+use crate::core::wav::{SampleFormat, WavSpec, WavWriter};
+
+use log::{info, warn};
+use std::env::temp_dir;
+use std::ffi::OsStr;
+use std::fs::{copy, create_dir, create_dir_all, remove_file, rename};
+use std::path::{Component, Path, PathBuf};
 use trash::delete;
 
 use open;
+use serde::Deserialize;
+
+/// Name of the directory in the system temporary directory to render regions into.
+const TEMP_DIR_NAME: &str = "sound-manager";
+
+/// A time range of a file to be rendered.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Region {
+    /// Start time in seconds.
+    pub start: f32,
+    /// End time in seconds.
+    pub end: f32,
+    /// Duration of the fade-in in seconds.
+    #[serde(default)]
+    pub fade_in: f32,
+    /// Duration of the fade-out in seconds.
+    #[serde(default)]
+    pub fade_out: f32,
+    /// Appended to the file stem of the rendered file, e.g. `_region`.
+    pub suffix: Option<String>,
+}
 
 impl<E> Database<E>
 where
@@ -25,7 +55,7 @@ where
 
         copy(path, &dst_absolute_path)?;
 
-        data.add_entries(&[dst_relative_path.into()], &self.db.lock().unwrap())?;
+        data.add_entries(&[dst_relative_path.into()], None, &self.db.lock().unwrap())?;
         self.emitter.on_files_updated(false);
 
         Ok(())
@@ -101,20 +131,79 @@ where
 
         Ok(())
     }
-}
 
-impl DatabaseData {
+    /// Create a new folder named `name` under the folder `parent_id`.
+    ///
+    /// The folder is added to the database immediately,
+    /// without waiting for the file watcher to pick it up.
+    pub fn create_folder(&self, parent_id: FolderId, name: &str) -> Result<FolderId> {
+        let mut data = self.data.write().unwrap();
+
+        if !is_valid_file_name(name) {
+            return Err(Error::InvalidName(name.to_owned()));
+        }
+
+        let parent_folder = data.folders.get(&parent_id).unwrap();
+        let relative_path = parent_folder.path.join(name);
+
+        if parent_folder.sub_folders.contains_key(OsStr::new(name)) {
+            return Err(Error::FolderAlreadyExists(
+                relative_path.to_string_lossy().into(),
+            ));
+        }
+
+        let absolute_path = data.to_absolute_path(&relative_path);
+        if absolute_path.exists() {
+            return Err(Error::FolderAlreadyExists(
+                absolute_path.to_string_lossy().into(),
+            ));
+        }
+
+        create_dir(&absolute_path)?;
+
+        data.add_folders(&[relative_path.clone()], &self.db.lock().unwrap())?;
+        let folder_id = data.get_folder_by_path(&relative_path).unwrap().id;
+        self.emitter.on_files_updated(true);
+
+        info!("Created folder {folder_id} at {}", relative_path.display());
+
+        Ok(folder_id)
+    }
+
+    /// Move a folder and all its contents to the trash.
+    pub fn delete_folder(&self, folder_id: FolderId) -> Result<()> {
+        if folder_id == ROOT_FOLDER_ID {
+            return Err(Error::DeleteRootFolder);
+        }
+
+        let mut data = self.data.write().unwrap();
+
+        let path = data.get_folder_path(folder_id).unwrap();
+        delete(path)?;
+
+        data.remove_folder(folder_id, &mut self.db.lock().unwrap())?;
+        self.emitter.on_files_updated(true);
+
+        info!("Deleted folder {folder_id}");
+
+        Ok(())
+    }
+
     pub fn spot(
         &self,
         entry_id: EntryId,
         save_path: Option<&Path>,
         open_in_application: Option<&Path>,
+        region: Option<&Region>,
         force: bool,
     ) -> Result<()> {
-        let entry_path = self.get_entry_path(entry_id).unwrap();
+        let entry_path = self.data.read().unwrap().get_entry_path(entry_id).unwrap();
 
         // file to be opened in application
-        let src_file_path = if let Some(save_path) = save_path {
+        let src_file_path = if let Some(region) = region {
+            // render the region to the new location, or to a temporary file
+            self.render_region(entry_id, region, save_path, force)?
+        } else if let Some(save_path) = save_path {
             // save file to the new location
             let to_path = save_path.join(entry_path.file_name().unwrap());
 
@@ -137,4 +226,185 @@ impl DatabaseData {
 
         Ok(())
     }
+
+    /// Render a region of an entry to a new WAV file.
+    ///
+    /// The region is cut at the end of the file, which is faded out instead.
+    ///
+    /// # Arguments
+    ///
+    /// * `save_path` - The directory to save the file into.
+    ///   If not set, the file is saved into a temporary directory, overwriting any existing file.
+    ///
+    /// Returns the absolute path to the rendered file.
+    pub fn render_region(
+        &self,
+        entry_id: EntryId,
+        region: &Region,
+        save_path: Option<&Path>,
+        force: bool,
+    ) -> Result<PathBuf> {
+        if !(region.start >= 0. && region.end > region.start && region.end.is_finite()) {
+            return Err(Error::InvalidRegion(region.start, region.end));
+        }
+        if let Some(suffix) = &region.suffix {
+            if !suffix.is_empty() && !is_valid_file_name(suffix) {
+                return Err(Error::InvalidName(suffix.clone()));
+            }
+        }
+
+        // release the lock before decoding the file
+        let entry_path = self.data.read().unwrap().get_entry_path(entry_id).unwrap();
+
+        let (save_path, force) = match save_path {
+            Some(save_path) => (save_path.to_owned(), force),
+            None => {
+                let temp_path = temp_dir().join(TEMP_DIR_NAME);
+                create_dir_all(&temp_path)?;
+                (temp_path, true)
+            }
+        };
+
+        let mut file_name = entry_path.file_stem().unwrap().to_owned();
+        if let Some(suffix) = &region.suffix {
+            file_name.push(suffix);
+        }
+        file_name.push(".wav");
+        let to_path = save_path.join(file_name);
+
+        // never overwrite the source file
+        if (!force && to_path.exists()) || to_path == entry_path {
+            return Err(Error::FileAlreadyExists(to_path.to_string_lossy().into()));
+        }
+
+        if let Err(err) = render_region(&entry_path, &to_path, region) {
+            // remove the partially written file
+            let _ = remove_file(&to_path);
+            return Err(err);
+        }
+
+        info!(
+            "Rendered region {}s - {}s of {} to {}",
+            region.start,
+            region.end,
+            entry_path.display(),
+            to_path.display()
+        );
+
+        Ok(to_path)
+    }
+}
+
+/// Decode the file at `src_path` and write `region` of it to `dst_path` as WAV.
+#[allow(clippy::cast_possible_truncation)]
+#[allow(clippy::cast_sign_loss)]
+fn render_region(src_path: &Path, dst_path: &Path, region: &Region) -> Result<()> {
+    let mut decoder = SampleDecoder::open(src_path)?;
+    let spec = *decoder.spec();
+    let n_channels = spec.n_channels;
+
+    let to_frames = |secs: f32| (f64::from(secs) * f64::from(spec.sample_rate)).round() as u64;
+    let start_frame = to_frames(region.start);
+    let mut end_frame = to_frames(region.end);
+    if let Some(n_frames) = spec.n_frames {
+        if start_frame >= n_frames {
+            return Err(Error::InvalidRegion(region.start, region.end));
+        }
+        end_frame = end_frame.min(n_frames);
+    }
+    let fade_in_frames = to_frames(region.fade_in);
+    let fade_out_frames = to_frames(region.fade_out);
+
+    let mut writer = WavWriter::create(
+        dst_path,
+        WavSpec {
+            n_channels,
+            sample_rate: spec.sample_rate,
+            sample_format: SampleFormat::Float32,
+        },
+    )?;
+
+    decoder.seek(start_frame)?;
+
+    // The frames of the fade-out are held back until the end of the region is decoded,
+    // since the file may end before it if its length is unknown.
+    let mut pending = Vec::new();
+    let mut n_decoded = 0; // number of frames of the region decoded
+    let mut n_written = 0; // number of frames of the region written
+    while n_decoded < end_frame - start_frame {
+        let Some(samples) = decoder.next_samples()? else {
+            break; // end of the file
+        };
+        let packet_frames =
+            ((samples.len() / n_channels) as u64).min(end_frame - start_frame - n_decoded);
+        pending.extend_from_slice(&samples[..packet_frames as usize * n_channels]);
+        n_decoded += packet_frames;
+
+        let n_ready = (pending.len() / n_channels).saturating_sub(fade_out_frames as usize);
+        let ready = &mut pending[..n_ready * n_channels];
+        apply_fades(ready, n_channels, n_written, None, fade_in_frames, 0);
+        writer.write_samples(ready)?;
+        pending.drain(..n_ready * n_channels);
+        n_written += n_ready as u64;
+    }
+
+    if n_decoded == 0 {
+        // the region starts after the end of the file
+        return Err(Error::InvalidRegion(region.start, region.end));
+    }
+
+    apply_fades(
+        &mut pending,
+        n_channels,
+        n_written,
+        Some(n_decoded),
+        fade_in_frames,
+        fade_out_frames,
+    );
+    writer.write_samples(&pending)?;
+
+    writer.finalize()?;
+    Ok(())
+}
+
+/// Apply linear fades to the interleaved frames from `pos` of a region of `n_frames` frames.
+///
+/// The fade-out is only applied if `n_frames` is known.
+#[allow(clippy::cast_precision_loss)]
+fn apply_fades(
+    samples: &mut [f32],
+    n_channels: usize,
+    pos: u64,
+    n_frames: Option<u64>,
+    fade_in_frames: u64,
+    fade_out_frames: u64,
+) {
+    for (pos, frame) in (pos..).zip(samples.chunks_exact_mut(n_channels)) {
+        let mut gain = 1.;
+        if pos < fade_in_frames {
+            gain *= pos as f32 / fade_in_frames as f32;
+        }
+        if let Some(n_frames) = n_frames {
+            let remaining = n_frames - pos - 1;
+            if remaining < fade_out_frames {
+                gain *= remaining as f32 / fade_out_frames as f32;
+            }
+        }
+        if gain < 1. {
+            for sample in frame {
+                *sample *= gain;
+            }
+        }
+    }
+}
+
+/// Whether `name` can be used as the name of a file or folder in the database,
+/// i.e. it is a single, non-hidden path component.
+fn is_valid_file_name(name: &str) -> bool {
+    let path = Path::new(name);
+    let mut components = path.components();
+    matches!(
+        (components.next(), components.next()),
+        (Some(Component::Normal(_)), None)
+    ) && !is_hidden_file(path)
 }
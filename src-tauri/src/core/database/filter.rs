@@ -12,6 +12,35 @@ pub struct Filter {
     pub no_tags: bool,
     pub folder_id: Option<FolderId>,
     pub include_subfolders: bool,
+    /// Integrated loudness in LUFS.
+    pub loudness: Option<ValueRange>,
+    /// Sample peak in dBFS.
+    pub peak: Option<ValueRange>,
+    /// RMS level in dBFS.
+    pub rms: Option<ValueRange>,
+    /// Loudness range in LU.
+    pub loudness_range: Option<ValueRange>,
+    /// The absolute value of the DC offset.
+    pub dc_offset: Option<ValueRange>,
+    /// Only keep entries with clipped samples.
+    #[serde(default)]
+    pub clipped: bool,
+}
+
+/// An inclusive range of values, unbounded on the sides not specified.
+#[derive(serde::Deserialize, Debug, Clone, Copy)]
+pub struct ValueRange {
+    pub min: Option<f32>,
+    pub max: Option<f32>,
+}
+
+impl ValueRange {
+    /// Whether the value is in the range. Entries that have not been analyzed yet never match.
+    fn contains(self, value: Option<f32>) -> bool {
+        value.is_some_and(|value| {
+            self.min.is_none_or(|min| value >= min) && self.max.is_none_or(|max| value <= max)
+        })
+    }
 }
 
 impl DatabaseData {
@@ -55,7 +84,16 @@ impl DatabaseData {
             })
             .map(|vec| vec.into_iter().collect::<HashSet<_>>());
 
-        if filter.search.is_empty() && tag_ids.is_empty() && !filter.no_tags && folder_ids.is_none()
+        if filter.search.is_empty()
+            && tag_ids.is_empty()
+            && !filter.no_tags
+            && folder_ids.is_none()
+            && filter.loudness.is_none()
+            && filter.peak.is_none()
+            && filter.rms.is_none()
+            && filter.loudness_range.is_none()
+            && filter.dc_offset.is_none()
+            && !filter.clipped
         {
             return None;
         }
@@ -75,6 +113,27 @@ impl DatabaseData {
                     keep &= !entry.tag_ids.is_disjoint(&tag_ids);
                 }
 
+                if let Some(range) = filter.loudness {
+                    keep &= range.contains(entry.loudness.map(|loudness| loudness.integrated));
+                }
+                if let Some(range) = filter.peak {
+                    keep &= range.contains(entry.analysis.map(|analysis| analysis.peak));
+                }
+                if let Some(range) = filter.rms {
+                    keep &= range.contains(entry.analysis.map(|analysis| analysis.rms));
+                }
+                if let Some(range) = filter.loudness_range {
+                    keep &= range.contains(entry.analysis.map(|analysis| analysis.loudness_range));
+                }
+                if let Some(range) = filter.dc_offset {
+                    keep &= range.contains(entry.analysis.map(|analysis| analysis.dc_offset.abs()));
+                }
+                if filter.clipped {
+                    keep &= entry
+                        .analysis
+                        .is_some_and(|analysis| analysis.clipped_samples > 0);
+                }
+
                 if !search.is_empty() {
                     let search = search.to_lowercase();
 
@@ -0,0 +1,258 @@
+use super::entry::FileInfo;
+use super::{Database, DatabaseData, DatabaseEmitter, EntryId, Result};
+use crate::core::decoder::SampleDecoder;
+
+use log::{debug, info, warn};
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::copy;
+use std::path::{Path, PathBuf};
+
+use rusqlite::Connection;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum HashMode {
+    /// Hash of the whole file content.
+    Content,
+    /// Hash of the decoded audio samples, ignoring metadata and container differences.
+    Audio,
+}
+
+/// An entry whose hash needs to be computed.
+struct HashJob {
+    entry_id: EntryId,
+    path: PathBuf,
+    file_info: Option<FileInfo>,
+}
+
+impl<E> Database<E>
+where
+    E: DatabaseEmitter + Send + Sync + 'static,
+{
+    /// Find groups of entries with identical content.
+    ///
+    /// Missing hashes are computed lazily, only for the entries that may have duplicates.
+    pub fn find_duplicates(&self, mode: HashMode) -> Result<Vec<Vec<EntryId>>> {
+        info!("Finding duplicates with {mode:?}");
+
+        let candidates = self.data.read().unwrap().get_duplicate_candidates(mode);
+
+        // compute missing hashes without holding the lock
+        let jobs = {
+            let data = self.data.read().unwrap();
+            candidates
+                .iter()
+                .filter_map(|entry_id| {
+                    let entry = data.get_entry(*entry_id)?;
+                    if entry.get_hash(mode).is_some() {
+                        return None;
+                    }
+                    Some(HashJob {
+                        entry_id: *entry_id,
+                        path: data.to_absolute_path(&entry.path),
+                        file_info: entry.file_info,
+                    })
+                })
+                .collect::<Vec<_>>()
+        };
+
+        debug!("Computing {} hashes", jobs.len());
+
+        let hashes = compute_hashes(jobs, mode);
+
+        let mut data = self.data.write().unwrap();
+        let db = self.db.lock().unwrap();
+        for (job, hash) in hashes {
+            data.set_hash(job.entry_id, job.file_info, mode, hash, &db)?;
+        }
+
+        // group candidates by hash
+        let mut groups = HashMap::<&str, Vec<EntryId>>::new();
+        for entry_id in &candidates {
+            let Some(hash) = data.get_entry(*entry_id).and_then(|e| e.get_hash(mode)) else {
+                continue;
+            };
+            groups.entry(hash).or_default().push(*entry_id);
+        }
+
+        let mut duplicates = groups
+            .into_values()
+            .filter(|group| group.len() > 1)
+            .map(|mut group| {
+                group.sort_unstable();
+                group
+            })
+            .collect::<Vec<_>>();
+        duplicates.sort_unstable();
+
+        info!("Found {} groups of duplicates", duplicates.len());
+
+        Ok(duplicates)
+    }
+
+    /// Find the entries with the same content as the file at `path`.
+    ///
+    /// Missing hashes are computed without holding the lock,
+    /// skipping the entries whose files cannot be read.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The absolute path to the file.
+    pub(super) fn find_entries_with_same_content(&self, path: &Path) -> Result<Vec<EntryId>> {
+        let size = path.metadata()?.len();
+        let hash = compute_hash(path, HashMode::Content)?;
+
+        let mut duplicates = Vec::new();
+        let mut jobs = Vec::new();
+        {
+            let data = self.data.read().unwrap();
+            let candidates = data
+                .entries
+                .values()
+                .filter(|entry| entry.file_info.is_some_and(|info| info.size == size));
+            for entry in candidates {
+                match &entry.content_hash {
+                    Some(entry_hash) if *entry_hash == hash => duplicates.push(entry.id),
+                    Some(_) => {}
+                    None => jobs.push(HashJob {
+                        entry_id: entry.id,
+                        path: data.to_absolute_path(&entry.path),
+                        file_info: entry.file_info,
+                    }),
+                }
+            }
+        }
+
+        let hashes = compute_hashes(jobs, HashMode::Content);
+        if !hashes.is_empty() {
+            let mut data = self.data.write().unwrap();
+            let db = self.db.lock().unwrap();
+            for (job, entry_hash) in hashes {
+                if entry_hash == hash {
+                    duplicates.push(job.entry_id);
+                }
+                data.set_hash(
+                    job.entry_id,
+                    job.file_info,
+                    HashMode::Content,
+                    entry_hash,
+                    &db,
+                )?;
+            }
+        }
+
+        duplicates.sort_unstable();
+        Ok(duplicates)
+    }
+}
+
+impl DatabaseData {
+    /// Return the entries that may have duplicates,
+    /// i.e. that share the file size (or the duration for [`HashMode::Audio`]) with another entry.
+    fn get_duplicate_candidates(&self, mode: HashMode) -> HashSet<EntryId> {
+        let mut groups = HashMap::<Option<u64>, Vec<EntryId>>::new();
+        for entry in self.entries.values() {
+            let key = match mode {
+                HashMode::Content => entry.file_info.map(|info| info.size),
+                HashMode::Audio => entry
+                    .metadata
+                    .as_ref()
+                    .and_then(|metadata| metadata.duration)
+                    .map(|duration| u64::from(duration.to_bits())),
+            };
+            groups.entry(key).or_default().push(entry.id);
+        }
+
+        groups
+            .into_values()
+            .filter(|group| group.len() > 1)
+            .flatten()
+            .collect()
+    }
+
+    /// Store the hash of an entry, if the file has not been modified since the hash was computed.
+    fn set_hash(
+        &mut self,
+        entry_id: EntryId,
+        file_info: Option<FileInfo>,
+        mode: HashMode,
+        hash: String,
+        db: &Connection,
+    ) -> Result<()> {
+        let Some(entry) = self.entries.get_mut(&entry_id) else {
+            return Ok(());
+        };
+        if entry.file_info != file_info {
+            debug!("File of entry {entry_id} modified while computing hash");
+            return Ok(());
+        }
+
+        let column = match mode {
+            HashMode::Content => "content_hash",
+            HashMode::Audio => "audio_hash",
+        };
+        db.execute(
+            format!(
+                "UPDATE entries SET {column} = ?, file_size = ?, file_mtime = ?, file_id = ?
+                WHERE id = ?"
+            )
+            .as_str(),
+            (
+                &hash,
+                file_info.map(|info| info.size),
+                file_info.map(|info| info.modified),
+                file_info.and_then(|info| info.file_id),
+                entry_id,
+            ),
+        )?;
+
+        match mode {
+            HashMode::Content => entry.content_hash = Some(hash),
+            HashMode::Audio => entry.audio_hash = Some(hash),
+        }
+
+        Ok(())
+    }
+}
+
+/// Compute the hashes of the files of the jobs, skipping the files that fail.
+fn compute_hashes(jobs: Vec<HashJob>, mode: HashMode) -> Vec<(HashJob, String)> {
+    jobs.into_iter()
+        .filter_map(|job| match compute_hash(&job.path, mode) {
+            Ok(hash) => Some((job, hash)),
+            Err(err) => {
+                warn!("Failed to compute hash of {}: {err}", job.path.display());
+                None
+            }
+        })
+        .collect()
+}
+
+/// Compute the hash of a file.
+///
+/// # Arguments
+///
+/// * `path` - The absolute path to the file.
+pub fn compute_hash(path: &Path, mode: HashMode) -> Result<String> {
+    let mut hasher = Sha256::new();
+
+    match mode {
+        HashMode::Content => {
+            let mut file = File::open(path)?;
+            copy(&mut file, &mut hasher)?;
+        }
+        HashMode::Audio => {
+            let mut decoder = SampleDecoder::open(path)?;
+            while let Some(samples) = decoder.next_samples()? {
+                for sample in samples {
+                    hasher.update(sample.to_le_bytes());
+                }
+            }
+        }
+    }
+
+    Ok(format!("{:x}", hasher.finalize()))
+}
@@ -0,0 +1,470 @@
+use super::{
+    is_audio_file, is_hidden_file, Database, DatabaseData, DatabaseEmitter, EntryId, Error,
+    FolderId, Result,
+};
+
+use log::{info, warn};
+use std::ffi::OsString;
+use std::fs::{copy, create_dir_all, remove_file, rename};
+use std::path::{Path, PathBuf};
+
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum ImportMode {
+    Copy,
+    Move,
+}
+
+/// What to do when a file with the same name already exists in the destination.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum CollisionPolicy {
+    Skip,
+    Overwrite,
+    /// Append a number to the file name, e.g. `name (1).wav`.
+    Rename,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportOptions {
+    pub mode: ImportMode,
+    pub collision_policy: CollisionPolicy,
+    /// Report the entries in the library that have the same content as each imported file.
+    #[serde(default)]
+    pub check_duplicates: bool,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "kind", content = "message")]
+#[serde(rename_all = "camelCase")]
+pub enum ImportStatus {
+    Imported,
+    Overwritten,
+    Renamed,
+    Skipped(String),
+    Failed(String),
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportResult {
+    /// Absolute path to the source file.
+    pub src_path: PathBuf,
+    /// Absolute path to the imported file, if it has been imported.
+    pub dst_path: Option<PathBuf>,
+    pub status: ImportStatus,
+    /// The existing entries with the same content as the file.
+    pub duplicates: Vec<EntryId>,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportProgress {
+    pub processed: usize,
+    pub total: usize,
+    pub result: ImportResult,
+}
+
+/// The number of files imported before their entries are inserted at once.
+const IMPORT_BATCH_SIZE: usize = 64;
+
+/// A single file to be imported.
+struct ImportJob {
+    /// Absolute path to the source file.
+    src_path: PathBuf,
+    /// Relative path to the destination file.
+    dst_path: PathBuf,
+}
+
+/// A file copied or moved into the library, whose entry is not inserted yet.
+struct ImportedFile {
+    /// Relative path to the destination file, if it has been imported.
+    dst_path: Option<PathBuf>,
+    status: ImportStatus,
+    duplicates: Vec<EntryId>,
+}
+
+impl<E> Database<E>
+where
+    E: DatabaseEmitter + Send + Sync + 'static,
+{
+    /// Import files and directories into the folder `target_folder_id`.
+    ///
+    /// Directories are imported recursively, recreating their structure in the target folder.
+    /// Non-audio and hidden files are skipped,
+    /// and directories containing the library or inside it fail, as they would import it into itself.
+    ///
+    /// The files are copied or moved without locking the database,
+    /// which is locked only to insert the entries of each batch of files.
+    ///
+    /// # Arguments
+    ///
+    /// * `paths` - The absolute paths to the files and directories to import.
+    /// * `target_folder_id` - The folder to import into.
+    /// * `options` - The import options.
+    /// * `on_progress` - Called after each file has been processed.
+    pub fn import_paths<F>(
+        &self,
+        paths: &[PathBuf],
+        target_folder_id: FolderId,
+        options: ImportOptions,
+        on_progress: F,
+    ) -> Result<Vec<ImportResult>>
+    where
+        F: Fn(ImportProgress),
+    {
+        info!("Importing {paths:?} into folder {target_folder_id} with {options:?}");
+
+        let (base_path, target_path) = {
+            let data = self.data.read().unwrap();
+            let target_path = data
+                .folders
+                .get(&target_folder_id)
+                .ok_or(Error::FolderNotFound(target_folder_id))?
+                .path
+                .clone();
+            (data.base_path.clone(), target_path)
+        };
+
+        // collect the files to import
+        let mut jobs = Vec::new();
+        let mut folders = Vec::new();
+        let mut results = Vec::new();
+        for path in paths {
+            let Some(file_name) = path.file_name() else {
+                warn!("Failed to import {}: invalid path", path.display());
+                results.push(ImportResult {
+                    src_path: path.clone(),
+                    dst_path: None,
+                    status: ImportStatus::Failed("invalid path".into()),
+                    duplicates: Vec::new(),
+                });
+                continue;
+            };
+            if let Some(reason) = library_overlap(path, &base_path) {
+                warn!("Failed to import {}: {reason}", path.display());
+                results.push(ImportResult {
+                    src_path: path.clone(),
+                    dst_path: None,
+                    status: ImportStatus::Failed(reason.into()),
+                    duplicates: Vec::new(),
+                });
+                continue;
+            }
+            collect_import_jobs(
+                path,
+                &target_path.join(file_name),
+                &mut jobs,
+                &mut folders,
+                &mut results,
+            );
+        }
+
+        // create folders
+        for folder in &folders {
+            create_dir_all(base_path.join(folder))?;
+        }
+        {
+            let mut data = self.data.write().unwrap();
+            let db = self.db.lock().unwrap();
+            data.add_folders(&folders, &db)?;
+        }
+
+        let total = jobs.len() + results.len();
+        for (processed, result) in results.iter().enumerate() {
+            on_progress(ImportProgress {
+                processed: processed + 1,
+                total,
+                result: result.clone(),
+            });
+        }
+
+        // import files
+        for batch in jobs.chunks(IMPORT_BATCH_SIZE) {
+            let imported = batch
+                .iter()
+                .map(|job| self.import_file(job, &base_path, options))
+                .collect::<Vec<_>>();
+
+            let batch_results = {
+                let mut data = self.data.write().unwrap();
+                let mut db = self.db.lock().unwrap();
+                batch
+                    .iter()
+                    .zip(imported)
+                    .map(|(job, imported)| {
+                        imported
+                            .and_then(|imported| data.add_imported_file(job, imported, &mut db))
+                            .unwrap_or_else(|err| {
+                                warn!("Failed to import {}: {err}", job.src_path.display());
+                                ImportResult {
+                                    src_path: job.src_path.clone(),
+                                    dst_path: None,
+                                    status: ImportStatus::Failed(err.to_string()),
+                                    duplicates: Vec::new(),
+                                }
+                            })
+                    })
+                    .collect::<Vec<_>>()
+            };
+
+            for result in batch_results {
+                results.push(result.clone());
+                on_progress(ImportProgress {
+                    processed: results.len(),
+                    total,
+                    result,
+                });
+            }
+        }
+
+        self.emitter.on_files_updated(true);
+
+        Ok(results)
+    }
+
+    /// Copy or move the file of `job` into the library, without inserting its entry.
+    fn import_file(
+        &self,
+        job: &ImportJob,
+        base_path: &Path,
+        options: ImportOptions,
+    ) -> Result<ImportedFile> {
+        let mut dst_path = job.dst_path.clone();
+        let mut status = ImportStatus::Imported;
+
+        let duplicates = if options.check_duplicates {
+            let duplicates = self.find_entries_with_same_content(&job.src_path)?;
+            if !duplicates.is_empty() {
+                warn!(
+                    "File {} already exists in the library as entries {duplicates:?}",
+                    job.src_path.display()
+                );
+            }
+            duplicates
+        } else {
+            Vec::new()
+        };
+
+        if base_path.join(&dst_path).exists() {
+            match options.collision_policy {
+                CollisionPolicy::Skip => {
+                    return Ok(ImportedFile {
+                        dst_path: None,
+                        status: ImportStatus::Skipped("file already exists".into()),
+                        duplicates,
+                    });
+                }
+                CollisionPolicy::Overwrite => status = ImportStatus::Overwritten,
+                CollisionPolicy::Rename => {
+                    dst_path = get_available_path(&base_path.join(&dst_path))
+                        .strip_prefix(base_path)
+                        .unwrap()
+                        .to_owned();
+                    status = ImportStatus::Renamed;
+                }
+            }
+        }
+
+        let dst_absolute_path = base_path.join(&dst_path);
+        match options.mode {
+            ImportMode::Copy => {
+                copy(&job.src_path, &dst_absolute_path)?;
+            }
+            ImportMode::Move => {
+                if rename(&job.src_path, &dst_absolute_path).is_err() {
+                    // fallback for moving across file systems
+                    copy(&job.src_path, &dst_absolute_path)?;
+                    remove_file(&job.src_path)?;
+                }
+            }
+        }
+
+        info!(
+            "Imported {} to {}",
+            job.src_path.display(),
+            dst_absolute_path.display()
+        );
+
+        Ok(ImportedFile {
+            dst_path: Some(dst_path),
+            status,
+            duplicates,
+        })
+    }
+}
+
+impl DatabaseData {
+    /// Insert the entry of a file imported, or update the entry already at its path.
+    ///
+    /// The entry of a file moved from inside the library is moved along with it,
+    /// replacing the entry at the destination if overwritten.
+    fn add_imported_file(
+        &mut self,
+        job: &ImportJob,
+        imported: ImportedFile,
+        db: &mut Connection,
+    ) -> Result<ImportResult> {
+        let ImportedFile {
+            dst_path,
+            status,
+            duplicates,
+        } = imported;
+        let Some(dst_path) = dst_path else {
+            return Ok(ImportResult {
+                src_path: job.src_path.clone(),
+                dst_path: None,
+                status,
+                duplicates,
+            });
+        };
+
+        let dst_entry_id = self.get_entry_id(&dst_path);
+        let moved_entry_id = job
+            .src_path
+            .strip_prefix(&self.base_path)
+            .ok()
+            .and_then(|src_path| self.get_entry_id(src_path))
+            .filter(|entry_id| Some(*entry_id) != dst_entry_id && !job.src_path.exists());
+
+        match (moved_entry_id, dst_entry_id) {
+            (Some(entry_id), overwritten) => {
+                self.move_entry(entry_id, dst_path.clone(), db)?;
+                if let Some(overwritten) = overwritten {
+                    // its row has been replaced by the moved entry
+                    self.entries.remove(&overwritten);
+                }
+                self.reread_entry(entry_id, db)?;
+            }
+            // entry already exists, reread it
+            (None, Some(entry_id)) => self.reread_entry(entry_id, db)?,
+            (None, None) => self.add_entries(&[dst_path.clone()], None, db)?,
+        }
+
+        Ok(ImportResult {
+            src_path: job.src_path.clone(),
+            dst_path: Some(self.to_absolute_path(&dst_path)),
+            status,
+            duplicates,
+        })
+    }
+}
+
+/// Find a path that does not exist yet by appending a number to the file stem.
+///
+/// # Arguments
+///
+/// * `path` - The absolute path to the file.
+pub(super) fn get_available_path(path: &Path) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let extension = path.extension();
+
+    (1..)
+        .map(|i| {
+            let mut file_name = OsString::from(format!("{stem} ({i})"));
+            if let Some(extension) = extension {
+                file_name.push(".");
+                file_name.push(extension);
+            }
+            path.with_file_name(file_name)
+        })
+        .find(|path| !path.exists())
+        .unwrap()
+}
+
+/// Why the directory at `src_path` cannot be imported into the library at `base_path`,
+/// if it contains the library or is inside it.
+fn library_overlap(src_path: &Path, base_path: &Path) -> Option<&'static str> {
+    if !src_path.is_dir() {
+        return None;
+    }
+    let canonicalize = |path: &Path| path.canonicalize().unwrap_or_else(|_| path.to_owned());
+    let (src_path, base_path) = (canonicalize(src_path), canonicalize(base_path));
+    if base_path.starts_with(&src_path) {
+        Some("directory contains the library")
+    } else if src_path.starts_with(&base_path) {
+        Some("directory is inside the library")
+    } else {
+        None
+    }
+}
+
+/// Recursively collect the files to import from `src_path`.
+///
+/// # Arguments
+///
+/// * `src_path` - The absolute path to the source file or directory.
+/// * `dst_path` - The relative path to the destination file or folder.
+fn collect_import_jobs(
+    src_path: &Path,
+    dst_path: &Path,
+    jobs: &mut Vec<ImportJob>,
+    folders: &mut Vec<PathBuf>,
+    results: &mut Vec<ImportResult>,
+) {
+    let skip = |reason: &str, results: &mut Vec<ImportResult>| {
+        results.push(ImportResult {
+            src_path: src_path.to_owned(),
+            dst_path: None,
+            status: ImportStatus::Skipped(reason.into()),
+            duplicates: Vec::new(),
+        });
+    };
+
+    if is_hidden_file(Path::new(src_path.file_name().unwrap_or_default())) {
+        skip("hidden file", results);
+        return;
+    }
+
+    if src_path.is_dir() {
+        folders.push(dst_path.to_owned());
+
+        let read_dir = match src_path.read_dir() {
+            Ok(read_dir) => read_dir,
+            Err(err) => {
+                warn!("Failed to read directory {}: {err}", src_path.display());
+                results.push(ImportResult {
+                    src_path: src_path.to_owned(),
+                    dst_path: None,
+                    status: ImportStatus::Failed(err.to_string()),
+                    duplicates: Vec::new(),
+                });
+                return;
+            }
+        };
+
+        for dir_entry in read_dir {
+            let dir_entry = match dir_entry {
+                Ok(dir_entry) => dir_entry,
+                Err(err) => {
+                    warn!("Failed to read directory entry: {err}");
+                    continue;
+                }
+            };
+            let file_name = dir_entry.file_name();
+            collect_import_jobs(
+                &src_path.join(&file_name),
+                &dst_path.join(&file_name),
+                jobs,
+                folders,
+                results,
+            );
+        }
+    } else if src_path.is_file() {
+        if !is_audio_file(src_path) {
+            skip("not an audio file", results);
+            return;
+        }
+
+        jobs.push(ImportJob {
+            src_path: src_path.to_owned(),
+            dst_path: dst_path.to_owned(),
+        });
+    } else {
+        skip("unknown file type", results);
+    }
+}
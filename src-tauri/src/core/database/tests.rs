@@ -1,4 +1,11 @@
-use super::{Database, DatabaseEmitter, Error, ROOT_FOLDER_ID, ROOT_TAG_ID};
+use super::{
+    CollisionPolicy, ConvertOptions, Database, DatabaseEmitter, Error, ExportOptions, ExportStatus,
+    Filter, HashMode, ImportMode, ImportOptions, ImportStatus, ManifestFormat, ValueRange,
+    ROOT_FOLDER_ID, ROOT_TAG_ID,
+};
+use crate::core::decoder::SampleDecoder;
+use crate::core::silence::SilenceOptions;
+use crate::core::wav::{SampleFormat, WavSpec, WavWriter};
 
 use std::collections::{HashMap, HashSet};
 use std::ffi::{OsStr, OsString};
@@ -85,6 +92,28 @@ fn setup_files(dir: &Path) -> PathBuf {
     base_path
 }
 
+/// Write a stereo sine wave of `n_frames` frames to a WAV file.
+#[allow(clippy::cast_precision_loss)]
+fn write_sine_wav(path: &Path, sample_rate: u32, n_frames: usize, sample_format: SampleFormat) {
+    let mut writer = WavWriter::create(
+        path,
+        WavSpec {
+            n_channels: 2,
+            sample_rate,
+            sample_format,
+        },
+    )
+    .unwrap();
+    let samples = (0..n_frames)
+        .flat_map(|i| {
+            let sample = (i as f32 * 440. * std::f32::consts::TAU / sample_rate as f32).sin() * 0.5;
+            [sample, sample]
+        })
+        .collect::<Vec<_>>();
+    writer.write_samples(&samples).unwrap();
+    writer.finalize().unwrap();
+}
+
 fn setup_database(dir: &Path) -> (PathBuf, Arc<Database<TestEmitter>>, Arc<TestEmitter>) {
     let base_path = setup_files(dir);
     let emitter = Arc::new(TestEmitter::new());
@@ -747,6 +776,1087 @@ fn test_delete_tag() {
     assert_eq!(tags[&tag_ids[5]].children.len(), 0);
 }
 
+#[test]
+fn test_create_folder() {
+    let (base_path, database, _emitter) = setup_database(testdir!().as_path());
+
+    let folder1_id = database
+        .data
+        .read()
+        .unwrap()
+        .get_folder_by_path(Path::new("folder1"))
+        .unwrap()
+        .id;
+
+    let new_folder_id = database.create_folder(folder1_id, "new_folder").unwrap();
+    assert!(base_path.join("folder1/new_folder").is_dir());
+
+    {
+        let data = database.data.read().unwrap();
+        let folders = data.get_folders();
+
+        let new_folder = &folders[&new_folder_id];
+        assert_eq!(new_folder.name, "new_folder");
+        assert_eq!(new_folder.parent_id, folder1_id);
+        assert_eq!(new_folder.path, Path::new("folder1/new_folder"));
+        assert_eq!(
+            folders[&folder1_id].sub_folders[OsStr::new("new_folder")],
+            new_folder_id
+        );
+    }
+
+    assert_err!(
+        database.create_folder(folder1_id, "new_folder"),
+        Err(Error::FolderAlreadyExists(..))
+    );
+    assert_err!(
+        database.create_folder(ROOT_FOLDER_ID, "a/b"),
+        Err(Error::InvalidName(..))
+    );
+    assert_err!(
+        database.create_folder(ROOT_FOLDER_ID, ".hidden"),
+        Err(Error::InvalidName(..))
+    );
+    assert_err!(
+        database.create_folder(ROOT_FOLDER_ID, ".."),
+        Err(Error::InvalidName(..))
+    );
+    drop(database);
+
+    // Reopen the database to verify the folder keeps its ID
+    let database = Database::open(base_path, Arc::new(TestEmitter::new())).unwrap();
+    let data = database.data.read().unwrap();
+    assert_eq!(
+        data.get_folder_by_path(Path::new("folder1/new_folder"))
+            .unwrap()
+            .id,
+        new_folder_id
+    );
+}
+
+#[test]
+fn test_delete_folder() {
+    let (base_path, database, _emitter) = setup_database(testdir!().as_path());
+
+    let folder1_id = database
+        .data
+        .read()
+        .unwrap()
+        .get_folder_by_path(Path::new("folder1"))
+        .unwrap()
+        .id;
+
+    database.delete_folder(folder1_id).unwrap();
+    assert!(!base_path.join("folder1").exists());
+
+    let data = database.data.read().unwrap();
+    assert!(data.get_folder_by_path(Path::new("folder1")).is_none());
+    assert!(data
+        .get_folder_by_path(Path::new("folder1/folder1-1"))
+        .is_none());
+    assert!(!data.get_folders()[&ROOT_FOLDER_ID]
+        .sub_folders
+        .contains_key(OsStr::new("folder1")));
+
+    // 8 existing files - 3 files in folder1
+    assert_eq!(data.get_entries().len(), 5);
+    assert!(data
+        .get_entry_id(Path::new("folder1/wave_audio_2.wav"))
+        .is_none());
+    drop(data);
+
+    assert_err!(
+        database.delete_folder(ROOT_FOLDER_ID),
+        Err(Error::DeleteRootFolder)
+    );
+}
+
+#[test]
+fn test_import_paths() {
+    let (base_path, database, _emitter) = setup_database(testdir!().as_path());
+
+    // prepare files to import
+    let import_dir = testdir!().join("import");
+    create_dir(&import_dir).unwrap();
+    create_dir(import_dir.join("sub")).unwrap();
+    File::create(import_dir.join("wave_audio_1.wav")).unwrap();
+    File::create(import_dir.join("readme.txt")).unwrap();
+    File::create(import_dir.join("sub/flac_audio_3.flac")).unwrap();
+    let single_file = testdir!().join("wave_audio_2.wav");
+    File::create(&single_file).unwrap();
+
+    let folder1_id = database
+        .data
+        .read()
+        .unwrap()
+        .get_folder_by_path(Path::new("folder1"))
+        .unwrap()
+        .id;
+
+    let progress = Mutex::new(Vec::new());
+    let results = database
+        .import_paths(
+            &[import_dir.clone(), single_file.clone()],
+            folder1_id,
+            ImportOptions {
+                mode: ImportMode::Copy,
+                collision_policy: CollisionPolicy::Rename,
+                check_duplicates: false,
+            },
+            |p| progress.lock().unwrap().push((p.processed, p.total)),
+        )
+        .unwrap();
+
+    // 3 audio files + 1 skipped non-audio file
+    assert_eq!(results.len(), 4);
+    assert_eq!(
+        progress.into_inner().unwrap(),
+        vec![(1, 4), (2, 4), (3, 4), (4, 4)]
+    );
+
+    let status_of = |path: &Path| {
+        &results
+            .iter()
+            .find(|result| result.src_path == path)
+            .unwrap()
+            .status
+    };
+    assert!(matches!(
+        status_of(&import_dir.join("readme.txt")),
+        ImportStatus::Skipped(_)
+    ));
+    assert_eq!(
+        status_of(&import_dir.join("wave_audio_1.wav")),
+        &ImportStatus::Imported
+    );
+    assert_eq!(
+        status_of(&import_dir.join("sub/flac_audio_3.flac")),
+        &ImportStatus::Imported
+    );
+    // folder1/wave_audio_2.wav already exists
+    assert_eq!(status_of(&single_file), &ImportStatus::Renamed);
+
+    // source files are kept when copying
+    assert!(single_file.exists());
+
+    let data = database.data.read().unwrap();
+    assert_eq!(data.get_entries().len(), 11); // 8 existing + 3 new files
+    for path in [
+        "folder1/import/wave_audio_1.wav",
+        "folder1/import/sub/flac_audio_3.flac",
+        "folder1/wave_audio_2 (1).wav",
+    ] {
+        assert!(base_path.join(path).is_file());
+        assert!(data.get_entry_id(Path::new(path)).is_some());
+    }
+    assert!(!base_path.join("folder1/import/readme.txt").exists());
+    assert!(data
+        .get_folder_by_path(Path::new("folder1/import/sub"))
+        .is_some());
+}
+
+#[test]
+fn test_import_paths_move_and_skip() {
+    let (base_path, database, _emitter) = setup_database(testdir!().as_path());
+
+    let file_1 = testdir!().join("wave_audio_1.wav");
+    let file_2 = testdir!().join("new_mp3_audio.mp3");
+    File::create(&file_1).unwrap();
+    File::create(&file_2).unwrap();
+
+    let results = database
+        .import_paths(
+            &[file_1.clone(), file_2.clone()],
+            ROOT_FOLDER_ID,
+            ImportOptions {
+                mode: ImportMode::Move,
+                collision_policy: CollisionPolicy::Skip,
+                check_duplicates: false,
+            },
+            |_| {},
+        )
+        .unwrap();
+
+    assert!(matches!(results[0].status, ImportStatus::Skipped(_)));
+    assert_eq!(results[1].status, ImportStatus::Imported);
+
+    // skipped file is not moved
+    assert!(file_1.exists());
+    assert!(!file_2.exists());
+    assert!(base_path.join("new_mp3_audio.mp3").is_file());
+
+    let data = database.data.read().unwrap();
+    assert_eq!(data.get_entries().len(), 9); // 8 existing + 1 new files
+}
+
+#[test]
+fn test_import_paths_move_inside_library() {
+    let (base_path, database, _emitter) = setup_database(testdir!().as_path());
+
+    let entry_id_of = |path: &str| database.data.read().unwrap().get_entry_id(Path::new(path));
+    let entry_id = entry_id_of("wave_audio_1.wav").unwrap();
+    let overwritten_id = entry_id_of("folder1/wave_audio_2.wav").unwrap();
+    let folder1_id = database
+        .data
+        .read()
+        .unwrap()
+        .get_folder_by_path(Path::new("folder1"))
+        .unwrap()
+        .id;
+
+    // move an entry into another folder, and another one over an existing entry
+    File::create(base_path.join("folder2/wave_audio_2.wav")).unwrap();
+    database
+        .data
+        .write()
+        .unwrap()
+        .add_entries(
+            &[PathBuf::from("folder2/wave_audio_2.wav")],
+            None,
+            &database.db.lock().unwrap(),
+        )
+        .unwrap();
+    let moved_id = entry_id_of("folder2/wave_audio_2.wav").unwrap();
+
+    let results = database
+        .import_paths(
+            &[
+                base_path.join("wave_audio_1.wav"),
+                base_path.join("folder2/wave_audio_2.wav"),
+            ],
+            folder1_id,
+            ImportOptions {
+                mode: ImportMode::Move,
+                collision_policy: CollisionPolicy::Overwrite,
+                check_duplicates: false,
+            },
+            |_| {},
+        )
+        .unwrap();
+    assert_eq!(results[0].status, ImportStatus::Imported);
+    assert_eq!(results[1].status, ImportStatus::Overwritten);
+
+    // the entries are moved along with their files, keeping their IDs
+    assert_eq!(entry_id_of("wave_audio_1.wav"), None);
+    assert_eq!(entry_id_of("folder1/wave_audio_1.wav"), Some(entry_id));
+    assert_eq!(entry_id_of("folder2/wave_audio_2.wav"), None);
+    assert_eq!(entry_id_of("folder1/wave_audio_2.wav"), Some(moved_id));
+
+    let data = database.data.read().unwrap();
+    assert!(data.get_entry(overwritten_id).is_none());
+    assert_eq!(data.get_entries().len(), 8); // 9 existing - 1 overwritten
+    drop(data);
+    drop(database);
+
+    // the moves persist
+    let database = Database::open(base_path, Arc::new(TestEmitter::new())).unwrap();
+    let data = database.data.read().unwrap();
+    assert_eq!(data.get_entries().len(), 8);
+    assert_eq!(
+        data.get_entry_id(Path::new("folder1/wave_audio_1.wav")),
+        Some(entry_id)
+    );
+}
+
+#[test]
+fn test_find_duplicates() {
+    let base_path = setup_files(testdir!().as_path());
+    std::fs::write(base_path.join("wave_audio_1.wav"), b"duplicated").unwrap();
+    std::fs::write(base_path.join("folder2/ogg_audio_2.ogg"), b"duplicated").unwrap();
+    std::fs::write(base_path.join("flac_audio_1.flac"), b"unique").unwrap();
+    std::fs::write(base_path.join("mp3_audio_1.mp3"), b"different").unwrap();
+
+    let database = Database::create(base_path.clone(), Arc::new(TestEmitter::new())).unwrap();
+
+    let entry_id_of = |path: &str| {
+        database
+            .data
+            .read()
+            .unwrap()
+            .get_entry_id(Path::new(path))
+            .unwrap()
+    };
+
+    let mut expected = vec![
+        vec![
+            entry_id_of("wave_audio_1.wav"),
+            entry_id_of("folder2/ogg_audio_2.ogg"),
+        ],
+        // empty files
+        vec![
+            entry_id_of("ogg_audio_1.ogg"),
+            entry_id_of("folder1/wave_audio_2.wav"),
+            entry_id_of("folder1/folder1-1/flac_audio_2.flac"),
+            entry_id_of("folder1/folder1-2/mp3_audio_2.mp3"),
+        ],
+    ];
+    for group in &mut expected {
+        group.sort_unstable();
+    }
+    expected.sort_unstable();
+
+    assert_eq!(
+        database.find_duplicates(HashMode::Content).unwrap(),
+        expected
+    );
+
+    // hashes are only computed for entries sharing the file size with another entry
+    {
+        let data = database.data.read().unwrap();
+        let flac_audio_1 = data.get_entry(entry_id_of("flac_audio_1.flac")).unwrap();
+        assert!(flac_audio_1.content_hash.is_none());
+        let wave_audio_1 = data.get_entry(entry_id_of("wave_audio_1.wav")).unwrap();
+        assert!(wave_audio_1.content_hash.is_some());
+    }
+    drop(database);
+
+    // Reopen the database to verify the hashes persist
+    let database = Database::open(base_path, Arc::new(TestEmitter::new())).unwrap();
+    let data = database.data.read().unwrap();
+    let entry_id = data.get_entry_id(Path::new("wave_audio_1.wav")).unwrap();
+    assert!(data.get_entry(entry_id).unwrap().content_hash.is_some());
+}
+
+#[test]
+fn test_import_paths_invalid() {
+    let (base_path, database, _emitter) = setup_database(testdir!().as_path());
+    let options = ImportOptions {
+        mode: ImportMode::Copy,
+        collision_policy: CollisionPolicy::Rename,
+        check_duplicates: false,
+    };
+
+    assert_err!(
+        database.import_paths(&[base_path.join("wave_audio_1.wav")], 1234, options, |_| {}),
+        Err(Error::FolderNotFound(1234))
+    );
+
+    // the library would be imported into itself
+    let outer_dir = base_path.parent().unwrap().to_owned();
+    let inner_dir = base_path.join("folder1");
+    let results = database
+        .import_paths(
+            &[outer_dir.clone(), base_path.clone(), inner_dir.clone()],
+            ROOT_FOLDER_ID,
+            options,
+            |_| {},
+        )
+        .unwrap();
+    assert_eq!(results.len(), 3);
+    for (result, path) in results.iter().zip([&outer_dir, &base_path, &inner_dir]) {
+        assert_eq!(&result.src_path, path);
+        assert!(matches!(result.status, ImportStatus::Failed(_)));
+    }
+    assert!(!base_path.join("folder1/folder1").exists());
+    assert_eq!(database.data.read().unwrap().get_entries().len(), 8);
+}
+
+#[test]
+fn test_import_paths_check_duplicates() {
+    let base_path = setup_files(testdir!().as_path());
+    std::fs::write(base_path.join("folder1/wave_audio_2.wav"), b"duplicated").unwrap();
+    std::fs::write(base_path.join("folder2/ogg_audio_2.ogg"), b"duplicates").unwrap();
+    let database = Database::create(base_path.clone(), Arc::new(TestEmitter::new())).unwrap();
+    // a candidate of the same size that cannot be hashed is skipped
+    remove_file(base_path.join("folder2/ogg_audio_2.ogg")).unwrap();
+
+    let new_file = testdir!().join("new_wave_audio.wav");
+    std::fs::write(&new_file, b"duplicated").unwrap();
+
+    let results = database
+        .import_paths(
+            &[new_file],
+            ROOT_FOLDER_ID,
+            ImportOptions {
+                mode: ImportMode::Copy,
+                collision_policy: CollisionPolicy::Skip,
+                check_duplicates: true,
+            },
+            |_| {},
+        )
+        .unwrap();
+
+    let data = database.data.read().unwrap();
+    assert_eq!(results[0].status, ImportStatus::Imported);
+    assert_eq!(
+        results[0].duplicates,
+        vec![data
+            .get_entry_id(Path::new("folder1/wave_audio_2.wav"))
+            .unwrap()]
+    );
+}
+
+#[test]
+fn test_open_database_with_renamed_file() {
+    let (base_path, database, _emitter) = setup_database(testdir!().as_path());
+
+    let (entry_id, tag_id) = {
+        let mut data = database.data.write().unwrap();
+        let db = database.db.lock().unwrap();
+        let entry_id = data.get_entry_id(Path::new("wave_audio_1.wav")).unwrap();
+        let tag_id = data.new_tag("tag".to_string(), &db).unwrap();
+        data.add_tag_for_entry(entry_id, tag_id, &db).unwrap();
+        (entry_id, tag_id)
+    };
+    drop(database);
+
+    // Rename and move the file while the database is closed
+    rename(
+        base_path.join("wave_audio_1.wav"),
+        base_path.join("folder2/renamed_wave_audio.wav"),
+    )
+    .unwrap();
+
+    let database = Database::open(base_path, Arc::new(TestEmitter::new())).unwrap();
+    let data = database.data.read().unwrap();
+    assert!(data.get_entry_id(Path::new("wave_audio_1.wav")).is_none());
+    assert_eq!(
+        data.get_entry_id(Path::new("folder2/renamed_wave_audio.wav")),
+        Some(entry_id)
+    );
+    assert!(data.get_entry(entry_id).unwrap().tag_ids.contains(&tag_id));
+    assert_eq!(data.get_entries().len(), 8);
+}
+
+#[test]
+fn test_open_database_with_copied_file() {
+    let base_path = setup_files(testdir!().as_path());
+    // files sharing their size with another file are hashed when finding duplicates
+    std::fs::write(base_path.join("wave_audio_1.wav"), b"deleted").unwrap();
+    std::fs::write(base_path.join("flac_audio_1.flac"), b"deleted").unwrap();
+    std::fs::write(base_path.join("mp3_audio_1.mp3"), b"moved").unwrap();
+    std::fs::write(base_path.join("folder2/ogg_audio_2.ogg"), b"moved").unwrap();
+
+    let database = Database::create(base_path.clone(), Arc::new(TestEmitter::new())).unwrap();
+    database.find_duplicates(HashMode::Content).unwrap();
+
+    let (deleted_id, moved_id) = {
+        let data = database.data.read().unwrap();
+        (
+            data.get_entry_id(Path::new("wave_audio_1.wav")).unwrap(),
+            data.get_entry_id(Path::new("mp3_audio_1.mp3")).unwrap(),
+        )
+    };
+
+    // Delete an entry while the database is open
+    remove_file(base_path.join("wave_audio_1.wav")).unwrap();
+    database.refresh().unwrap();
+    assert!(database
+        .data
+        .read()
+        .unwrap()
+        .get_entry(deleted_id)
+        .is_none());
+    drop(database);
+
+    // Copy a file to a new path and restore the deleted one while the database is closed
+    std::fs::copy(
+        base_path.join("mp3_audio_1.mp3"),
+        base_path.join("folder1/copied_mp3_audio.mp3"),
+    )
+    .unwrap();
+    remove_file(base_path.join("mp3_audio_1.mp3")).unwrap();
+    std::fs::write(base_path.join("restored_wave_audio.wav"), b"deleted").unwrap();
+
+    let database = Database::open(base_path, Arc::new(TestEmitter::new())).unwrap();
+    let data = database.data.read().unwrap();
+    // the copied file is matched by its content hash
+    assert_eq!(
+        data.get_entry_id(Path::new("folder1/copied_mp3_audio.mp3")),
+        Some(moved_id)
+    );
+    // the entry deleted before is not restored
+    let restored_id = data
+        .get_entry_id(Path::new("restored_wave_audio.wav"))
+        .unwrap();
+    assert_ne!(restored_id, deleted_id);
+    assert!(data.get_entry(deleted_id).is_none());
+    assert_eq!(data.get_entries().len(), 8);
+}
+
+#[test]
+fn test_export_entries() {
+    let base_path = setup_files(testdir!().as_path());
+    write_sine_wav(
+        &base_path.join("folder1/wave_audio_2.wav"),
+        44100,
+        4410,
+        SampleFormat::Float32,
+    );
+    let database = Database::create(base_path, Arc::new(TestEmitter::new())).unwrap();
+
+    let (wave_audio_2, mp3_audio_1) = {
+        let mut data = database.data.write().unwrap();
+        let db = database.db.lock().unwrap();
+        let wave_audio_2 = data
+            .get_entry_id(Path::new("folder1/wave_audio_2.wav"))
+            .unwrap();
+        let mp3_audio_1 = data.get_entry_id(Path::new("mp3_audio_1.mp3")).unwrap();
+        let tag_id = data.new_tag("tag, with comma".to_string(), &db).unwrap();
+        data.add_tag_for_entry(mp3_audio_1, tag_id, &db).unwrap();
+        (wave_audio_2, mp3_audio_1)
+    };
+
+    let target_dir = testdir!().join("export");
+    let options = ExportOptions {
+        preserve_structure: true,
+        convert: None,
+        rename_pattern: Some("{index}_{name}".to_string()),
+        manifest: Some(ManifestFormat::Csv),
+        collision_policy: CollisionPolicy::Skip,
+    };
+    let progress = Mutex::new(Vec::new());
+    let results = database
+        .export_entries(&[mp3_audio_1, wave_audio_2], &target_dir, &options, |p| {
+            progress.lock().unwrap().push(p.processed);
+        })
+        .unwrap();
+
+    assert_eq!(progress.into_inner().unwrap(), vec![1, 2]);
+    assert!(results
+        .iter()
+        .all(|result| result.status == ExportStatus::Exported));
+    assert!(target_dir.join("1_mp3_audio_1.mp3").is_file());
+    assert!(target_dir.join("folder1/2_wave_audio_2.wav").is_file());
+
+    let manifest = std::fs::read_to_string(target_dir.join("manifest.csv")).unwrap();
+    let mut lines = manifest.lines();
+    assert_eq!(
+        lines.next(),
+        Some("file,source,title,artist,album,duration,tags")
+    );
+    assert!(lines
+        .next()
+        .unwrap()
+        .starts_with("1_mp3_audio_1.mp3,mp3_audio_1.mp3,"));
+    assert!(manifest.contains("\"tag, with comma\""));
+
+    // exporting again skips the existing files
+    let results = database
+        .export_entries(&[mp3_audio_1], &target_dir, &options, |_| {})
+        .unwrap();
+    assert_eq!(
+        results[0].status,
+        ExportStatus::Skipped("file already exists".into())
+    );
+}
+
+#[test]
+fn test_export_entries_same_name() {
+    let base_path = setup_files(testdir!().as_path());
+    let database = Database::create(base_path, Arc::new(TestEmitter::new())).unwrap();
+    let entry_ids = {
+        let data = database.data.read().unwrap();
+        [
+            data.get_entry_id(Path::new("wave_audio_1.wav")).unwrap(),
+            data.get_entry_id(Path::new("folder1/wave_audio_2.wav"))
+                .unwrap(),
+        ]
+    };
+
+    let target_dir = testdir!().join("export");
+    let results = database
+        .export_entries(
+            &entry_ids,
+            &target_dir,
+            &ExportOptions {
+                preserve_structure: false,
+                convert: None,
+                rename_pattern: Some("sample".to_string()),
+                manifest: None,
+                collision_policy: CollisionPolicy::Overwrite,
+            },
+            |_| {},
+        )
+        .unwrap();
+
+    // the second entry does not overwrite the first one exported with the same name
+    assert_eq!(results[0].status, ExportStatus::Exported);
+    assert_eq!(results[1].status, ExportStatus::Renamed);
+    assert_eq!(
+        results[0].dst_path.as_deref(),
+        Some(target_dir.join("sample.wav").as_path())
+    );
+    assert_eq!(
+        results[1].dst_path.as_deref(),
+        Some(target_dir.join("sample (1).wav").as_path())
+    );
+}
+
+#[test]
+fn test_export_entries_convert() {
+    let base_path = setup_files(testdir!().as_path());
+    write_sine_wav(
+        &base_path.join("wave_audio_1.wav"),
+        44100,
+        44100,
+        SampleFormat::Float32,
+    );
+    let database = Database::create(base_path, Arc::new(TestEmitter::new())).unwrap();
+    let entry_id = database
+        .data
+        .read()
+        .unwrap()
+        .get_entry_id(Path::new("wave_audio_1.wav"))
+        .unwrap();
+
+    let target_dir = testdir!().join("export");
+    let results = database
+        .export_entries(
+            &[entry_id],
+            &target_dir,
+            &ExportOptions {
+                preserve_structure: false,
+                convert: Some(ConvertOptions {
+                    sample_format: SampleFormat::Int16,
+                    sample_rate: Some(22050),
+                }),
+                rename_pattern: None,
+                manifest: Some(ManifestFormat::Json),
+                collision_policy: CollisionPolicy::Skip,
+            },
+            |_| {},
+        )
+        .unwrap();
+    assert_eq!(results[0].status, ExportStatus::Exported);
+
+    let mut decoder = SampleDecoder::open(&target_dir.join("wave_audio_1.wav")).unwrap();
+    assert_eq!(decoder.spec().sample_rate, 22050);
+    assert_eq!(decoder.spec().n_channels, 2);
+    let mut n_samples = 0;
+    while let Some(samples) = decoder.next_samples().unwrap() {
+        n_samples += samples.len();
+    }
+    assert_eq!(n_samples, 22050 * 2);
+
+    let manifest: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(target_dir.join("manifest.json")).unwrap())
+            .unwrap();
+    assert_eq!(manifest[0]["file"], "wave_audio_1.wav");
+    assert_eq!(manifest[0]["source"], "wave_audio_1.wav");
+}
+
+#[test]
+fn test_export_entries_invalid_sample_rate() {
+    let base_path = setup_files(testdir!().as_path());
+    let database = Database::create(base_path, Arc::new(TestEmitter::new())).unwrap();
+    let entry_id = database
+        .data
+        .read()
+        .unwrap()
+        .get_entry_id(Path::new("wave_audio_1.wav"))
+        .unwrap();
+
+    let target_dir = testdir!().join("export");
+    for sample_rate in [0, 1000, 10_000_000] {
+        assert_err!(
+            database.export_entries(
+                &[entry_id],
+                &target_dir,
+                &ExportOptions {
+                    preserve_structure: false,
+                    convert: Some(ConvertOptions {
+                        sample_format: SampleFormat::Int16,
+                        sample_rate: Some(sample_rate),
+                    }),
+                    rename_pattern: None,
+                    manifest: None,
+                    collision_policy: CollisionPolicy::Skip,
+                },
+                |_| {},
+            ),
+            Err(Error::InvalidSampleRate(_))
+        );
+    }
+    assert!(!target_dir.exists());
+}
+
+#[test]
+fn test_render_region() {
+    let base_path = setup_files(testdir!().as_path());
+    write_sine_wav(
+        &base_path.join("wave_audio_1.wav"),
+        44100,
+        44100,
+        SampleFormat::Int16,
+    );
+    let database = Database::create(base_path, Arc::new(TestEmitter::new())).unwrap();
+    let entry_id = database
+        .data
+        .read()
+        .unwrap()
+        .get_entry_id(Path::new("wave_audio_1.wav"))
+        .unwrap();
+
+    let save_path = testdir!().join("spot");
+    create_dir(&save_path).unwrap();
+    let region = Region {
+        start: 0.25,
+        end: 0.75,
+        fade_in: 0.1,
+        fade_out: 0.1,
+        suffix: Some("_region".to_string()),
+    };
+    let path = database
+        .render_region(entry_id, &region, Some(&save_path), false)
+        .unwrap();
+    assert_eq!(path, save_path.join("wave_audio_1_region.wav"));
+
+    let mut decoder = SampleDecoder::open(&path).unwrap();
+    let mut samples = Vec::new();
+    while let Some(packet) = decoder.next_samples().unwrap() {
+        samples.extend_from_slice(packet);
+    }
+    assert_eq!(samples.len(), 22050 * 2);
+    // silent at both ends because of the fades
+    assert_eq!(samples[0], 0.);
+    assert_eq!(samples[samples.len() - 1], 0.);
+    assert!(samples.iter().any(|sample| sample.abs() > 0.4));
+
+    // the rendered file already exists
+    assert_err!(
+        database.render_region(entry_id, &region, Some(&save_path), false),
+        Err(Error::FileAlreadyExists(_))
+    );
+    // invalid region
+    assert_err!(
+        database.render_region(
+            entry_id,
+            &Region {
+                start: 0.5,
+                end: 0.25,
+                fade_in: 0.,
+                fade_out: 0.,
+                suffix: None,
+            },
+            None,
+            false
+        ),
+        Err(Error::InvalidRegion(..))
+    );
+    // the region starts after the end of the file
+    assert_err!(
+        database.render_region(
+            entry_id,
+            &Region {
+                start: 2.,
+                end: 3.,
+                fade_in: 0.,
+                fade_out: 0.,
+                suffix: Some("_after".to_string()),
+            },
+            Some(&save_path),
+            false
+        ),
+        Err(Error::InvalidRegion(..))
+    );
+    assert!(!save_path.join("wave_audio_1_after.wav").exists());
+    // invalid suffix
+    assert_err!(
+        database.render_region(
+            entry_id,
+            &Region {
+                start: 0.,
+                end: 0.5,
+                fade_in: 0.,
+                fade_out: 0.,
+                suffix: Some("/../region".to_string()),
+            },
+            Some(&save_path),
+            false
+        ),
+        Err(Error::InvalidName(_))
+    );
+}
+
+#[test]
+fn test_render_region_past_end() {
+    let base_path = setup_files(testdir!().as_path());
+    write_sine_wav(
+        &base_path.join("wave_audio_1.wav"),
+        44100,
+        44100,
+        SampleFormat::Int16,
+    );
+    let database = Database::create(base_path, Arc::new(TestEmitter::new())).unwrap();
+    let entry_id = database
+        .data
+        .read()
+        .unwrap()
+        .get_entry_id(Path::new("wave_audio_1.wav"))
+        .unwrap();
+
+    // the region is cut at the end of the file, which is faded out
+    let path = database
+        .render_region(
+            entry_id,
+            &Region {
+                start: 0.5,
+                end: 2.,
+                fade_in: 0.,
+                fade_out: 0.1,
+                suffix: None,
+            },
+            Some(&testdir!()),
+            false,
+        )
+        .unwrap();
+
+    let mut decoder = SampleDecoder::open(&path).unwrap();
+    let mut samples = Vec::new();
+    while let Some(packet) = decoder.next_samples().unwrap() {
+        samples.extend_from_slice(packet);
+    }
+    assert_eq!(samples.len(), 22050 * 2);
+    assert_eq!(samples[samples.len() - 1], 0.);
+    assert!(samples[..4410 * 2].iter().any(|sample| sample.abs() > 0.4));
+}
+
+#[test]
+fn test_get_loudness() {
+    let base_path = setup_files(testdir!().as_path());
+    // a stereo sine wave at -6 dBFS, i.e. around -6 LUFS
+    write_sine_wav(
+        &base_path.join("wave_audio_1.wav"),
+        48000,
+        48000 * 3,
+        SampleFormat::Float32,
+    );
+    let database = Database::create(base_path.clone(), Arc::new(TestEmitter::new())).unwrap();
+    let entry_id = database
+        .data
+        .read()
+        .unwrap()
+        .get_entry_id(Path::new("wave_audio_1.wav"))
+        .unwrap();
+
+    let loudness = database.get_loudness(entry_id).unwrap().unwrap();
+    assert!((loudness.integrated + 6.).abs() < 0.5, "{loudness:?}");
+    assert!((loudness.true_peak + 6.).abs() < 0.5, "{loudness:?}");
+    drop(database);
+
+    // Reopen the database to verify the loudness persists
+    let database = Database::open(base_path, Arc::new(TestEmitter::new())).unwrap();
+    let data = database.data.read().unwrap();
+    assert_eq!(data.get_entry(entry_id).unwrap().loudness, Some(loudness));
+}
+
+#[test]
+fn test_analysis() {
+    let base_path = setup_files(testdir!().as_path());
+    write_sine_wav(
+        &base_path.join("wave_audio_1.wav"),
+        48000,
+        48000 * 3,
+        SampleFormat::Int16,
+    );
+    // a full scale square wave, which is clipped
+    let mut writer = WavWriter::create(
+        &base_path.join("folder1/wave_audio_2.wav"),
+        WavSpec {
+            n_channels: 1,
+            sample_rate: 48000,
+            sample_format: SampleFormat::Int16,
+        },
+    )
+    .unwrap();
+    let samples = (0..48000 * 3)
+        .map(|i| if i / 50 % 2 == 0 { 1. } else { -1. })
+        .collect::<Vec<_>>();
+    writer.write_samples(&samples).unwrap();
+    writer.finalize().unwrap();
+
+    let emitter = Arc::new(TestEmitter::new());
+    let database = Database::create(base_path.clone(), emitter.clone()).unwrap();
+    database.start_analysis();
+
+    let entry_ids = ["wave_audio_1.wav", "folder1/wave_audio_2.wav"].map(|path| {
+        database
+            .data
+            .read()
+            .unwrap()
+            .get_entry_id(Path::new(path))
+            .unwrap()
+    });
+    for _ in 0..10 {
+        let data = database.data.read().unwrap();
+        if entry_ids
+            .iter()
+            .all(|&entry_id| data.get_entry(entry_id).unwrap().analysis.is_some())
+        {
+            break;
+        }
+        drop(data);
+        emitter.wait_for_files_updated(EMITTER_TIMEOUT);
+    }
+
+    let data = database.data.read().unwrap();
+    let sine = data.get_entry(entry_ids[0]).unwrap();
+    let sine_analysis = sine.analysis.unwrap();
+    assert!((sine_analysis.peak + 6.).abs() < 0.1, "{sine_analysis:?}");
+    assert!((sine_analysis.rms + 9.).abs() < 0.1, "{sine_analysis:?}");
+    assert!(
+        sine_analysis.loudness_range.abs() < 0.5,
+        "{sine_analysis:?}"
+    );
+    assert!(sine_analysis.dc_offset.abs() < 0.001, "{sine_analysis:?}");
+    assert_eq!(sine_analysis.clipped_samples, 0);
+    assert!((sine.loudness.unwrap().integrated + 6.).abs() < 0.5);
+
+    let square = data.get_entry(entry_ids[1]).unwrap();
+    let analysis = square.analysis.unwrap();
+    assert!(analysis.peak.abs() < 0.01, "{analysis:?}");
+    assert_eq!(analysis.clipped_samples, 48000 * 3);
+
+    let filter = Filter {
+        search: String::new(),
+        tag_ids: Vec::new(),
+        include_child_tags: false,
+        no_tags: false,
+        folder_id: None,
+        include_subfolders: false,
+        loudness: None,
+        peak: None,
+        rms: None,
+        loudness_range: None,
+        dc_offset: None,
+        clipped: true,
+    };
+    assert_eq!(data.filter(&filter), Some(vec![entry_ids[1]]));
+
+    // the square wave at full scale has an RMS level of 0 dBFS
+    let filter = Filter {
+        clipped: false,
+        rms: Some(ValueRange {
+            min: None,
+            max: Some(-6.),
+        }),
+        ..filter
+    };
+    assert_eq!(data.filter(&filter), Some(vec![entry_ids[0]]));
+
+    let filter = Filter {
+        rms: None,
+        loudness_range: Some(ValueRange {
+            min: None,
+            max: Some(1.),
+        }),
+        dc_offset: Some(ValueRange {
+            min: None,
+            max: Some(0.001),
+        }),
+        ..filter
+    };
+    let mut filtered = data.filter(&filter).unwrap();
+    filtered.sort_unstable();
+    let mut expected = entry_ids.to_vec();
+    expected.sort_unstable();
+    assert_eq!(filtered, expected);
+
+    let filter = Filter {
+        loudness_range: None,
+        dc_offset: Some(ValueRange {
+            min: Some(0.1),
+            max: None,
+        }),
+        ..filter
+    };
+    assert_eq!(data.filter(&filter), Some(vec![]));
+
+    let filter = Filter {
+        dc_offset: None,
+        loudness: Some(ValueRange {
+            min: None,
+            max: Some(-3.),
+        }),
+        ..filter
+    };
+    assert_eq!(data.filter(&filter), Some(vec![entry_ids[0]]));
+    drop(data);
+    drop(database);
+
+    // Reopen the database to verify the analysis persists
+    let database = Database::open(base_path, Arc::new(TestEmitter::new())).unwrap();
+    let data = database.data.read().unwrap();
+    assert_eq!(
+        data.get_entry(entry_ids[0]).unwrap().analysis,
+        Some(sine_analysis)
+    );
+}
+
+#[test]
+#[allow(clippy::cast_precision_loss)]
+fn test_get_silence() {
+    let base_path = setup_files(testdir!().as_path());
+    // 0.5 s of noise floor at -80 dBFS, 1 s of sine wave, then 0.5 s of noise floor again
+    let mut writer = WavWriter::create(
+        &base_path.join("wave_audio_1.wav"),
+        WavSpec {
+            n_channels: 1,
+            sample_rate: 48000,
+            sample_format: SampleFormat::Float32,
+        },
+    )
+    .unwrap();
+    let samples = (0..48000 * 2)
+        .map(|i| {
+            if (24000..72000).contains(&i) {
+                (i as f32 * 440. * std::f32::consts::TAU / 48000.).sin() * 0.5
+            } else if i % 2 == 0 {
+                0.0001
+            } else {
+                -0.0001
+            }
+        })
+        .collect::<Vec<_>>();
+    writer.write_samples(&samples).unwrap();
+    writer.finalize().unwrap();
+
+    let database = Database::create(base_path.clone(), Arc::new(TestEmitter::new())).unwrap();
+    let entry_id = database
+        .data
+        .read()
+        .unwrap()
+        .get_entry_id(Path::new("wave_audio_1.wav"))
+        .unwrap();
+
+    let options = SilenceOptions {
+        threshold: -60.,
+        min_duration: 0.,
+    };
+    let silence = database.get_silence(entry_id, options).unwrap().unwrap();
+    assert!((silence.head - 0.5).abs() < 0.001, "{silence:?}");
+    assert!((silence.tail - 1.5).abs() < 0.001, "{silence:?}");
+    assert_eq!(
+        database
+            .data
+            .read()
+            .unwrap()
+            .get_entry(entry_id)
+            .unwrap()
+            .silence,
+        Some(silence)
+    );
+
+    // the noise floor is above the threshold
+    let options = SilenceOptions {
+        threshold: -100.,
+        min_duration: 0.,
+    };
+    let silence = database.get_silence(entry_id, options).unwrap().unwrap();
+    assert!(silence.head.abs() < 0.001, "{silence:?}");
+    assert!((silence.tail - 2.).abs() < 0.001, "{silence:?}");
+
+    // the silence is shorter than the minimum duration
+    let options = SilenceOptions {
+        threshold: -60.,
+        min_duration: 0.6,
+    };
+    let silence = database.get_silence(entry_id, options).unwrap().unwrap();
+    assert!(silence.head.abs() < 0.001, "{silence:?}");
+    assert!((silence.tail - 2.).abs() < 0.001, "{silence:?}");
+}
+
 #[test]
 fn test_file_watcher_create_single_file() {
     let (base_path, database, emitter) = setup_database(testdir!().as_path());
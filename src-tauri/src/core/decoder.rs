@@ -0,0 +1,165 @@
+use super::player::get_format_reader;
+
+use log::warn;
+use std::path::Path;
+
+use symphonia::core::codecs::audio::{AudioDecoder, AudioDecoderOptions};
+use symphonia::core::codecs::CodecParameters;
+use symphonia::core::formats::{FormatReader, SeekMode, SeekTo, TrackType};
+use symphonia::core::units::{Time, TimeBase};
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error("tracks not found for source: {0}")]
+    TracksNotFound(String),
+    #[error("codec parameters missing")]
+    CodecParamsMissing,
+    #[error("symphonia error: {0}")]
+    Symphonia(#[from] symphonia::core::errors::Error),
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct AudioSpec {
+    pub n_channels: usize,
+    pub sample_rate: u32,
+    /// The number of frames of the track, if known.
+    pub n_frames: Option<u64>,
+}
+
+/// Decodes the default audio track of a file into interleaved `f32` samples, packet by packet.
+pub struct SampleDecoder {
+    reader: Box<dyn FormatReader>,
+    decoder: Box<dyn AudioDecoder>,
+    track_id: u32,
+    /// The time base of the timestamps of the track, if known.
+    time_base: Option<TimeBase>,
+    spec: AudioSpec,
+    samples: Vec<f32>,
+    /// The number of frames to skip from the next packet, to reach the frame seeked to.
+    n_skip: u64,
+}
+
+impl SampleDecoder {
+    pub fn open(path: &Path) -> Result<Self, Error> {
+        let reader = get_format_reader(path)?;
+
+        let track = reader
+            .default_track(TrackType::Audio)
+            .ok_or_else(|| Error::TracksNotFound(path.to_string_lossy().to_string()))?;
+        let track_id = track.id;
+        let time_base = track.time_base;
+        let n_frames = track.num_frames;
+
+        let params = track
+            .codec_params
+            .as_ref()
+            .and_then(CodecParameters::audio)
+            .ok_or(Error::CodecParamsMissing)?;
+        let n_channels = params
+            .channels
+            .as_ref()
+            .ok_or(Error::CodecParamsMissing)?
+            .count();
+        let sample_rate = params.sample_rate.ok_or(Error::CodecParamsMissing)?;
+
+        let decoder = symphonia::default::get_codecs()
+            .make_audio_decoder(params, &AudioDecoderOptions::default())?;
+
+        Ok(Self {
+            reader,
+            decoder,
+            track_id,
+            time_base,
+            spec: AudioSpec {
+                n_channels,
+                sample_rate,
+                n_frames,
+            },
+            samples: Vec::new(),
+            n_skip: 0,
+        })
+    }
+
+    pub fn spec(&self) -> &AudioSpec {
+        &self.spec
+    }
+
+    /// Seek to the frame, so that the next samples start from it.
+    #[allow(
+        clippy::cast_possible_truncation,
+        clippy::cast_precision_loss,
+        clippy::cast_sign_loss
+    )]
+    pub fn seek(&mut self, frame: u64) -> Result<(), Error> {
+        let sample_rate = u64::from(self.spec.sample_rate);
+        let seeked_to = self.reader.seek(
+            SeekMode::Accurate,
+            SeekTo::Time {
+                time: Time {
+                    seconds: frame / sample_rate,
+                    frac: (frame % sample_rate) as f64 / sample_rate as f64,
+                },
+                track_id: Some(self.track_id),
+            },
+        )?;
+        self.decoder.reset();
+
+        // the timestamps are in the time base of the track, which may differ from frames
+        let n_skip = seeked_to.required_ts.saturating_sub(seeked_to.actual_ts);
+        self.n_skip = self.time_base.map_or(n_skip, |time_base| {
+            let time = time_base.calc_time(n_skip);
+            time.seconds * sample_rate + (time.frac * sample_rate as f64).round() as u64
+        });
+        Ok(())
+    }
+
+    /// Decode the next packet of the track.
+    ///
+    /// Packets that fail to decode because of IO errors or invalid data are skipped.
+    ///
+    /// Returns the interleaved samples of the packet, or `None` at the end of the stream.
+    pub fn next_samples(&mut self) -> Result<Option<&[f32]>, Error> {
+        loop {
+            let Some(packet) = self.reader.next_packet()? else {
+                return Ok(None); // end of stream
+            };
+
+            // If the packet does not belong to the selected track, skip over it.
+            if packet.track_id() != self.track_id {
+                continue;
+            }
+
+            match self.decoder.decode(&packet) {
+                Ok(audio_buf) => {
+                    self.samples.resize(audio_buf.samples_interleaved(), 0.);
+                    audio_buf.copy_to_slice_interleaved(&mut self.samples);
+
+                    // drop the frames before the frame seeked to
+                    let n_frames = (self.samples.len() / self.spec.n_channels) as u64;
+                    let n_skip = self.n_skip.min(n_frames);
+                    self.n_skip -= n_skip;
+                    if n_skip == n_frames {
+                        continue;
+                    }
+                    #[allow(clippy::cast_possible_truncation)]
+                    return Ok(Some(
+                        &self.samples[n_skip as usize * self.spec.n_channels..],
+                    ));
+                }
+
+                Err(symphonia::core::errors::Error::IoError(err)) => {
+                    // The packet failed to decode due to an IO error, skip the packet.
+                    warn!("Decoder: skipped packet, because of IO error: {err}");
+                }
+                Err(symphonia::core::errors::Error::DecodeError(err)) => {
+                    // The packet failed to decode due to invalid data, skip the packet.
+                    warn!("Decoder: skipped packet, because of decode error: {err}");
+                }
+
+                // An unrecoverable error occurred, halt decoding.
+                Err(err) => return Err(err.into()),
+            }
+        }
+    }
+}
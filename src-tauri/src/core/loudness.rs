@@ -0,0 +1,299 @@
+use std::f64::consts::PI;
+
+/// Duration of a sub-block in seconds. Gating blocks overlap by 75%, i.e. they consist of 4 sub-blocks.
+const SUB_BLOCK_DURATION: f64 = 0.1;
+const SUB_BLOCKS_PER_BLOCK: usize = 4;
+/// The number of sub-blocks of the short-term loudness window (3 s) for loudness range measurement.
+const SUB_BLOCKS_PER_SHORT_TERM_BLOCK: usize = 30;
+const ABSOLUTE_GATE: f64 = -70.;
+const RELATIVE_GATE: f64 = -10.;
+const LOUDNESS_RANGE_RELATIVE_GATE: f64 = -20.;
+
+/// The number of taps of each phase of the interpolation filter for true peak measurement.
+const TAPS_PER_PHASE: usize = 12;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Loudness {
+    /// Integrated loudness in LUFS, negative infinity for silence.
+    pub integrated: f32,
+    /// True peak in dBTP, negative infinity for silence.
+    pub true_peak: f32,
+}
+
+/// Measures the loudness of interleaved samples, processed in chunks of any size.
+pub struct LoudnessMeter {
+    n_channels: usize,
+    channel_weights: Vec<f64>,
+    filters: Vec<KWeightingFilter>,
+    /// The number of frames per sub-block.
+    sub_block_frames: usize,
+    /// The weighted sum of squares of the current sub-block.
+    sub_block_sum: f64,
+    sub_block_pos: usize,
+    /// The mean squares of all completed sub-blocks.
+    sub_blocks: Vec<f64>,
+    true_peak_meters: Vec<TruePeakMeter>,
+}
+
+impl LoudnessMeter {
+    #[allow(clippy::cast_possible_truncation)]
+    #[allow(clippy::cast_sign_loss)]
+    pub fn new(n_channels: usize, sample_rate: u32) -> Self {
+        // LFE is excluded and surround channels are weighted by +1.5 dB, assuming the 5.1 layout
+        let channel_weights = if n_channels == 6 {
+            vec![1., 1., 1., 0., 1.41, 1.41]
+        } else {
+            vec![1.; n_channels]
+        };
+
+        Self {
+            n_channels,
+            channel_weights,
+            filters: (0..n_channels)
+                .map(|_| KWeightingFilter::new(sample_rate))
+                .collect(),
+            sub_block_frames: (f64::from(sample_rate) * SUB_BLOCK_DURATION).round() as usize,
+            sub_block_sum: 0.,
+            sub_block_pos: 0,
+            sub_blocks: Vec::new(),
+            true_peak_meters: (0..n_channels)
+                .map(|_| TruePeakMeter::new(sample_rate))
+                .collect(),
+        }
+    }
+
+    /// Process interleaved samples.
+    #[allow(clippy::cast_precision_loss)]
+    pub fn process(&mut self, samples: &[f32]) {
+        for frame in samples.chunks_exact(self.n_channels) {
+            for (channel, &sample) in frame.iter().enumerate() {
+                let filtered = self.filters[channel].process(f64::from(sample));
+                self.sub_block_sum += self.channel_weights[channel] * filtered * filtered;
+                self.true_peak_meters[channel].process(sample);
+            }
+
+            self.sub_block_pos += 1;
+            if self.sub_block_pos == self.sub_block_frames {
+                self.sub_blocks
+                    .push(self.sub_block_sum / self.sub_block_frames as f64);
+                self.sub_block_sum = 0.;
+                self.sub_block_pos = 0;
+            }
+        }
+    }
+
+    /// The mean squares of the gating blocks of `sub_blocks_per_block` sub-blocks,
+    /// overlapping by all but one sub-block.
+    #[allow(clippy::cast_precision_loss)]
+    fn blocks(&self, sub_blocks_per_block: usize) -> impl Iterator<Item = f64> + '_ {
+        self.sub_blocks
+            .windows(sub_blocks_per_block)
+            .map(move |window| window.iter().sum::<f64>() / sub_blocks_per_block as f64)
+    }
+
+    /// The true peak of all channels, as a linear value.
+    fn true_peak(&self) -> f32 {
+        self.true_peak_meters
+            .iter()
+            .map(|meter| meter.peak)
+            .fold(0., f32::max)
+    }
+
+    #[allow(clippy::cast_possible_truncation)]
+    pub fn finalize(&self) -> Loudness {
+        let blocks = self.blocks(SUB_BLOCKS_PER_BLOCK).collect::<Vec<_>>();
+        let blocks = gate(&blocks, RELATIVE_GATE);
+        Loudness {
+            integrated: to_lufs(mean(&blocks)) as f32,
+            true_peak: 20. * self.true_peak().log10(),
+        }
+    }
+
+    /// The loudness range in LU following EBU Tech 3342,
+    /// i.e. the difference between the 10th and the 95th percentiles of the short-term loudness.
+    #[allow(clippy::cast_possible_truncation)]
+    #[allow(clippy::cast_precision_loss)]
+    #[allow(clippy::cast_sign_loss)]
+    pub fn loudness_range(&self) -> f32 {
+        let blocks = self
+            .blocks(SUB_BLOCKS_PER_SHORT_TERM_BLOCK)
+            .collect::<Vec<_>>();
+        let mut loudness = gate(&blocks, LOUDNESS_RANGE_RELATIVE_GATE)
+            .into_iter()
+            .map(to_lufs)
+            .collect::<Vec<_>>();
+        if loudness.is_empty() {
+            return 0.;
+        }
+        loudness.sort_unstable_by(f64::total_cmp);
+
+        let percentile = |p: f64| loudness[((loudness.len() - 1) as f64 * p).round() as usize];
+        (percentile(0.95) - percentile(0.1)) as f32
+    }
+}
+
+/// Convert the mean square of a block to loudness in LUFS.
+fn to_lufs(mean_square: f64) -> f64 {
+    -0.691 + 10. * mean_square.log10()
+}
+
+/// The mean of the mean squares of blocks, zero if there are no blocks.
+#[allow(clippy::cast_precision_loss)]
+fn mean(blocks: &[f64]) -> f64 {
+    if blocks.is_empty() {
+        return 0.;
+    }
+    blocks.iter().sum::<f64>() / blocks.len() as f64
+}
+
+/// The blocks above the absolute gate and the gate relative to the loudness of these blocks.
+fn gate(blocks: &[f64], relative_gate: f64) -> Vec<f64> {
+    let blocks = blocks
+        .iter()
+        .copied()
+        .filter(|&block| to_lufs(block) > ABSOLUTE_GATE)
+        .collect::<Vec<_>>();
+
+    let threshold = to_lufs(mean(&blocks)) + relative_gate;
+    blocks
+        .into_iter()
+        .filter(|&block| to_lufs(block) > threshold)
+        .collect()
+}
+
+/// A biquad filter in direct form I.
+struct Biquad {
+    b: [f64; 3],
+    a: [f64; 2],
+    x: [f64; 2],
+    y: [f64; 2],
+}
+
+impl Biquad {
+    fn process(&mut self, x: f64) -> f64 {
+        let y = self.b[0] * x + self.b[1] * self.x[0] + self.b[2] * self.x[1]
+            - self.a[0] * self.y[0]
+            - self.a[1] * self.y[1];
+        self.x = [x, self.x[0]];
+        self.y = [y, self.y[0]];
+        y
+    }
+}
+
+/// The K-weighting filter, i.e. a high shelf followed by a high pass, for any sample rate.
+struct KWeightingFilter {
+    shelf: Biquad,
+    high_pass: Biquad,
+}
+
+impl KWeightingFilter {
+    fn new(sample_rate: u32) -> Self {
+        let sample_rate = f64::from(sample_rate);
+
+        let f0 = 1_681.974_450_955_533;
+        let gain = 3.999_843_853_973_347;
+        let q = 0.707_175_236_955_419_6;
+        let k = (PI * f0 / sample_rate).tan();
+        let vh = 10f64.powf(gain / 20.);
+        let vb = vh.powf(0.499_666_774_154_541_6);
+        let a0 = 1. + k / q + k * k;
+        let shelf = Biquad {
+            b: [
+                (vh + vb * k / q + k * k) / a0,
+                2. * (k * k - vh) / a0,
+                (vh - vb * k / q + k * k) / a0,
+            ],
+            a: [2. * (k * k - 1.) / a0, (1. - k / q + k * k) / a0],
+            x: [0.; 2],
+            y: [0.; 2],
+        };
+
+        let f0 = 38.135_470_876_024_44;
+        let q = 0.500_327_037_323_877_3;
+        let k = (PI * f0 / sample_rate).tan();
+        let a0 = 1. + k / q + k * k;
+        let high_pass = Biquad {
+            b: [1., -2., 1.],
+            a: [2. * (k * k - 1.) / a0, (1. - k / q + k * k) / a0],
+            x: [0.; 2],
+            y: [0.; 2],
+        };
+
+        Self { shelf, high_pass }
+    }
+
+    fn process(&mut self, x: f64) -> f64 {
+        self.high_pass.process(self.shelf.process(x))
+    }
+}
+
+/// Measures the true peak of a channel by oversampling with a windowed sinc interpolation filter.
+struct TruePeakMeter {
+    /// The taps of each phase of the interpolation filter.
+    phases: Vec<[f32; TAPS_PER_PHASE]>,
+    /// The last input samples, the latest first.
+    history: [f32; TAPS_PER_PHASE],
+    peak: f32,
+}
+
+impl TruePeakMeter {
+    #[allow(clippy::cast_precision_loss)]
+    #[allow(clippy::cast_possible_truncation)]
+    fn new(sample_rate: u32) -> Self {
+        // oversample to at least 192 kHz
+        let factor = match sample_rate {
+            0..96_000 => 4,
+            96_000..192_000 => 2,
+            _ => 1,
+        };
+
+        let n_taps = TAPS_PER_PHASE * factor;
+        let center = (n_taps - 1) as f64 / 2.;
+        let prototype = (0..n_taps)
+            .map(|n| {
+                let t = (n as f64 - center) / factor as f64;
+                let sinc = if t == 0. {
+                    1.
+                } else {
+                    (PI * t).sin() / (PI * t)
+                };
+                // Hann window
+                let window = 0.5 - 0.5 * (2. * PI * (n as f64 + 0.5) / n_taps as f64).cos();
+                sinc * window
+            })
+            .collect::<Vec<_>>();
+
+        let phases = (0..factor)
+            .map(|phase| {
+                let mut taps = [0.; TAPS_PER_PHASE];
+                for (k, tap) in taps.iter_mut().enumerate() {
+                    *tap = prototype[phase + factor * k];
+                }
+                // normalize the gain of each phase
+                let sum = taps.iter().sum::<f64>();
+                taps.map(|tap| (tap / sum) as f32)
+            })
+            .collect();
+
+        Self {
+            phases,
+            history: [0.; TAPS_PER_PHASE],
+            peak: 0.,
+        }
+    }
+
+    fn process(&mut self, sample: f32) {
+        self.history.copy_within(..TAPS_PER_PHASE - 1, 1);
+        self.history[0] = sample;
+
+        self.peak = self.peak.max(sample.abs());
+        for taps in &self.phases {
+            let interpolated = taps
+                .iter()
+                .zip(&self.history)
+                .map(|(tap, x)| tap * x)
+                .sum::<f32>();
+            self.peak = self.peak.max(interpolated.abs());
+        }
+    }
+}
@@ -1,21 +1,53 @@
+mod channel_source;
+mod fade_source;
+mod loop_source;
+mod meter_source;
+mod output;
+mod preload;
+mod queue;
+mod rate_source;
+mod spectrum_source;
+#[cfg(test)]
+mod tests;
+
 use core::time::Duration;
-use std::fs::File;
-use std::io::BufReader;
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::sync_channel;
 use std::sync::{Arc, Mutex, RwLock};
 use std::thread::{sleep, spawn};
+use std::time::Instant;
 
 use log::{debug, warn};
-use rodio::{Decoder, OutputStream, Sink};
+use rodio::Sink;
 use serde::Serialize;
-use symphonia::core::codecs::audio::AudioDecoderOptions;
-use symphonia::core::codecs::CodecParameters;
 use symphonia::core::formats::probe::Hint;
-use symphonia::core::formats::{FormatOptions, FormatReader, TrackType};
+use symphonia::core::formats::{FormatOptions, FormatReader};
 use symphonia::core::io::{MediaSourceStream, MediaSourceStreamOptions};
 use symphonia::core::meta::MetadataOptions;
 use thiserror::Error;
 
+use super::database::EntryId;
+use super::loudness::Loudness;
+use super::silence::{Silence, SilenceOptions};
+pub use channel_source::ChannelOptions;
+use channel_source::{ChannelControl, ChannelSource};
+pub use fade_source::FadeOptions;
+use fade_source::{FadeControl, FadeSource};
+pub use loop_source::LoopRegion;
+use loop_source::{LoopControl, LoopSource};
+pub use meter_source::MeterLevels;
+use meter_source::{Meter, MeterControl, MeterSource};
+use output::Output;
+pub use output::{get_output_devices, OutputDevice, OutputTarget};
+use preload::{FileSource, Preloader};
+use queue::Queue;
+pub use queue::{QueueOptions, QueueState, RepeatMode};
+pub use rate_source::{PlaybackRate, PlaybackRateRange, PLAYBACK_RATE_RANGE};
+use rate_source::{RateControl, RateSource};
+use spectrum_source::{Analyzer, SpectrumControl, SpectrumSource};
+pub use spectrum_source::{Spectrum, SpectrumOptions, Window};
+
 #[derive(Error, Debug)]
 pub enum Error {
     #[error("decoder error: {0}")]
@@ -34,25 +66,96 @@ pub enum Error {
     PlayerNotStarted,
     #[error("source not set")]
     SourceNotSet,
-    #[error("tracks not found for source: {0}")]
-    TracksNotFound(String),
-    #[error("codec parameters missing")]
-    CodecParamsMissing,
+    #[error("invalid loop region: {0}s - {1}s")]
+    InvalidLoopRegion(f32, f32),
+    #[error("playback rate out of range: {0:?}")]
+    InvalidPlaybackRate(PlaybackRate),
+    #[error("invalid position update rate: {0}")]
+    InvalidPositionUpdateRate(f32),
+    #[error("devices error: {0}")]
+    Devices(#[from] rodio::DevicesError),
+    #[error("no output device available")]
+    NoOutputDevice,
+    #[error("output device not found: {0}")]
+    OutputDeviceNotFound(String),
+    #[error("queue index out of range: {0}")]
+    InvalidQueueIndex(usize),
+    #[error("invalid gap between queue entries: {0}s")]
+    InvalidQueueGap(f32),
+    #[error("fade duration out of range: {0:?}")]
+    InvalidFadeOptions(FadeOptions),
+    #[error("invalid FFT size: {0}")]
+    InvalidFftSize(usize),
+    #[error("invalid silence options: {0:?}")]
+    InvalidSilenceOptions(SilenceOptions),
 }
 
 pub struct Player {
     sink: Arc<RwLock<Option<Sink>>>,
-    source: Mutex<Option<SourceInfo>>,
+    source: Arc<Mutex<Option<SourceInfo>>>,
     emitter: Arc<dyn PlayerEmitter + Send + Sync>,
+    volume: f32,
+    /// The target loudness in LUFS to normalize the playback to, if enabled.
+    target_loudness: Option<f32>,
+    silence_options: SilenceOptions,
+    /// The position to stop playing at, i.e. the start of the trailing silence when skipping silence.
+    end_pos: Arc<Mutex<Option<Duration>>>,
+    controls: SourceControls,
+    /// The interval to push the position while playing, `None` to disable.
+    position_update_interval: Arc<Mutex<Option<Duration>>>,
+    output_target: Arc<Mutex<OutputTarget>>,
+    queue: Arc<Mutex<Queue>>,
+    preloader: Arc<Preloader>,
+    /// Whether silence was skipped when last played, to play the next entry of the queue alike.
+    skip_silence: AtomicBool,
+}
+
+/// The controls shared with the wrappers of the source.
+#[derive(Clone, Default)]
+struct SourceControls {
+    loop_control: Arc<LoopControl>,
+    channel_control: Arc<ChannelControl>,
+    rate_control: Arc<RateControl>,
+    fade_control: Arc<FadeControl>,
+    meter_control: Arc<MeterControl>,
+    spectrum_control: Arc<SpectrumControl>,
+}
+
+impl SourceControls {
+    /// The position of the source being played in frames, which is behind the position
+    /// read by [`LoopSource`] by the frames buffered by [`RateSource`].
+    fn get_frames(&self) -> u64 {
+        self.loop_control
+            .get_frames_before(self.rate_control.get_latency())
+    }
+
+    /// The position of the source being played.
+    #[allow(clippy::cast_precision_loss)]
+    fn get_pos(&self) -> Duration {
+        let sample_rate = self.loop_control.get_sample_rate();
+        if sample_rate == 0 {
+            return Duration::ZERO;
+        }
+        Duration::from_secs_f64(self.get_frames() as f64 / f64::from(sample_rate))
+    }
 }
 
 struct SourceInfo {
     path: PathBuf,
-    first_transit_pos: Duration,
+    loudness: Option<Loudness>,
+    /// The silence detected with the current silence options.
+    silence: Option<Silence>,
 }
 
 pub trait PlayerEmitter {
     fn on_player_state_updated(&self, state: PlayerState);
+    fn on_player_position_updated(&self, position: PlayerPosition);
+    /// Report an error of the player thread, e.g. failing to open the output device.
+    fn on_player_error(&self, error: &Error);
+    fn on_player_queue_updated(&self, state: QueueState);
+    /// Called when the queue has advanced automatically after an entry ended,
+    /// to set the entry as the source and play it.
+    fn on_queue_advanced(&self, entry_id: EntryId);
 }
 
 #[derive(Clone, Debug, Serialize)]
@@ -60,8 +163,43 @@ pub trait PlayerEmitter {
 pub struct PlayerState {
     pub playing: bool,
     pub pos: f32,
+    /// The region being looped, if looping is enabled and a region is set.
+    pub loop_region: Option<LoopRegion>,
+}
+
+/// The position of the source being played, pushed periodically while playing.
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PlayerPosition {
+    /// The position in seconds.
+    pub pos: f32,
+    /// The position in frames of the source.
+    pub frames: u64,
+    pub sample_rate: u32,
 }
 
+impl PlayerPosition {
+    fn from_controls(controls: &SourceControls) -> Self {
+        Self {
+            pos: controls.get_pos().as_secs_f32(),
+            frames: controls.get_frames(),
+            sample_rate: controls.loop_control.get_sample_rate(),
+        }
+    }
+}
+
+/// The interval to check the state of the sink, e.g. whether the source has ended.
+const STATE_CHECK_INTERVAL: Duration = Duration::from_millis(100);
+/// The default interval to push the position while playing.
+const DEFAULT_POSITION_UPDATE_INTERVAL: Duration = Duration::from_millis(50);
+/// The interval to check whether the output device should be switched,
+/// e.g. the selected device has been unplugged or the default device has changed.
+const DEVICE_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+/// The interval to push the levels to the meter.
+const METER_INTERVAL: Duration = Duration::from_millis(33);
+/// The interval to push the spectrum.
+const SPECTRUM_INTERVAL: Duration = Duration::from_millis(33);
+
 impl Player {
     pub fn new<T>(emitter: Arc<T>) -> Self
     where
@@ -69,105 +207,322 @@ impl Player {
     {
         Self {
             sink: Arc::new(None.into()),
-            source: None.into(),
+            source: Arc::new(None.into()),
             emitter,
+            volume: 1.,
+            target_loudness: None,
+            silence_options: SilenceOptions::default(),
+            end_pos: Arc::new(None.into()),
+            controls: SourceControls::default(),
+            position_update_interval: Arc::new(Some(DEFAULT_POSITION_UPDATE_INTERVAL).into()),
+            output_target: Arc::new(OutputTarget::default().into()),
+            queue: Arc::new(Queue::default().into()),
+            preloader: Arc::new(Preloader::default()),
+            skip_silence: AtomicBool::new(false),
         }
     }
 
+    /// Start the player thread, and wait until the output is opened.
+    ///
+    /// If the output cannot be opened, the error is reported to the emitter
+    /// and the null output is used instead.
     pub fn run(&mut self) {
         let sink = self.sink.clone();
+        let source = self.source.clone();
         let emitter = self.emitter.clone();
+        let end_pos = self.end_pos.clone();
+        let controls = self.controls.clone();
+        let position_update_interval = self.position_update_interval.clone();
+        let output_target = self.output_target.clone();
+        let queue = self.queue.clone();
+        let (ready_tx, ready_rx) = sync_channel(1);
 
         spawn(move || {
             debug!("start player thread");
 
-            // stream must be created in the player thread
+            // output must be created in the player thread
             // because it needs to live as long as the sink
-            let (_stream, handle) =
-                OutputStream::try_default().expect("failed to create output stream");
-            sink.write()
-                .unwrap()
-                .replace(Sink::try_new(&handle).expect("failed to create sink"));
+            let mut target = output_target.lock().unwrap().clone();
+            let mut device_name = target.resolve();
+            let (mut _output, new_sink) = open_output(&target, emitter.as_ref());
+            sink.write().unwrap().replace(new_sink);
+            ready_tx.send(()).unwrap();
 
             let mut had_source = false;
+            let mut last_position_update = Instant::now();
+            let mut last_device_check = Instant::now();
+            // when to advance the queue after the source ended
+            let mut advance_at = None;
             loop {
+                let position_update_interval = *position_update_interval.lock().unwrap();
+
                 // sleep should before break, to make sure it always sleep each loop
-                sleep(Duration::from_millis(100));
+                sleep(
+                    position_update_interval.map_or(STATE_CHECK_INTERVAL, |interval| {
+                        interval.min(STATE_CHECK_INTERVAL)
+                    }),
+                );
+
+                // switch the output if the target or the device it resolves to has changed
+                let new_target = output_target.lock().unwrap().clone();
+                if new_target != target || last_device_check.elapsed() >= DEVICE_CHECK_INTERVAL {
+                    last_device_check = Instant::now();
+                    let new_device_name = new_target.resolve();
+                    if new_target != target || new_device_name != device_name {
+                        debug!("switch output from {device_name:?} to {new_device_name:?}");
+                        target = new_target;
+                        device_name = new_device_name;
+
+                        let (new_output, new_sink) = open_output(&target, emitter.as_ref());
+                        let mut sink = sink.write().unwrap();
+                        let Some(old_sink) = sink.as_ref() else {
+                            break; // <== break HERE
+                        };
+                        let source = source.lock().unwrap();
+                        let path = source.as_ref().map(|source| source.path.as_path());
+                        if let Err(err) = transfer_playback(old_sink, &new_sink, path, &controls) {
+                            warn!("Failed to resume playback on the new output: {err}");
+                            emitter.on_player_error(&err);
+                        }
+                        sink.replace(new_sink);
+                        // the old output is dropped after its sink
+                        _output = new_output;
+                    }
+                }
 
-                let empty = match sink.read().unwrap().as_ref() {
-                    Some(sink) => sink.empty(),
+                let (empty, playing) = match sink.read().unwrap().as_ref() {
+                    Some(sink) => {
+                        let mut end_pos = end_pos.lock().unwrap();
+                        if end_pos
+                            .is_some_and(|end_pos| !sink.empty() && controls.get_pos() >= end_pos)
+                        {
+                            // end as if the source ended
+                            debug!("trailing silence reached");
+                            controls.loop_control.set_ended();
+                            sink.skip_one();
+                            *end_pos = None;
+                        }
+                        (sink.empty(), !sink.empty() && !sink.is_paused())
+                    }
                     None => break, // <== break HERE
                 };
 
+                if playing
+                    && position_update_interval
+                        .is_some_and(|interval| last_position_update.elapsed() >= interval)
+                {
+                    emitter.on_player_position_updated(PlayerPosition::from_controls(&controls));
+                    last_position_update = Instant::now();
+                }
+
                 if had_source && empty {
                     // had source -> empty
                     debug!("source ended");
                     emitter.on_player_state_updated(PlayerState {
                         playing: false,
                         pos: 0.,
+                        loop_region: controls.loop_control.active_region(),
                     });
                     had_source = false;
+
+                    let options = queue.lock().unwrap().options();
+                    if controls.loop_control.take_ended() && options.auto_advance {
+                        advance_at = Some(Instant::now() + Duration::from_secs_f32(options.gap));
+                    }
                 } else if !empty {
                     had_source = true;
+                    // played something else during the gap
+                    advance_at = None;
+                }
+
+                if advance_at.is_some_and(|advance_at| Instant::now() >= advance_at) {
+                    advance_at = None;
+                    let mut queue = queue.lock().unwrap();
+                    if let Some(entry_id) = queue.advance() {
+                        debug!("advance queue to entry {entry_id}");
+                        let state = queue.state();
+                        drop(queue);
+                        emitter.on_player_queue_updated(state);
+                        emitter.on_queue_advanced(entry_id);
+                    }
                 }
             }
 
             debug!("stop player thread");
         });
+
+        ready_rx.recv().unwrap();
     }
 
     pub fn terminate(&self) {
         self.sink.write().unwrap().take();
     }
 
-    pub fn set_source(&mut self, path: PathBuf) -> Result<(), Error> {
-        let sink = self.sink.read().unwrap();
-        let sink = sink.as_ref().ok_or(Error::PlayerNotStarted)?;
-
+    /// Set the file to play.
+    ///
+    /// # Arguments
+    ///
+    /// * `loudness` - The loudness of the file for normalization, if it has been measured.
+    ///   It can be set later with [`Player::set_loudness`].
+    /// * `silence` - The silence of the file, if it has been detected.
+    ///   It can be set later with [`Player::set_silence`].
+    pub fn set_source(
+        &mut self,
+        path: PathBuf,
+        loudness: Option<Loudness>,
+        silence: Option<Silence>,
+    ) -> Result<(), Error> {
         debug!("set source: {}", path.display());
 
+        // faded out before locking the sink, which the output waits for to switch devices
+        if self.is_playing() {
+            self.controls.fade_control.fade_out_and_wait();
+        }
+
+        let sink = self.sink.read().unwrap();
+        let sink = sink.as_ref().ok_or(Error::PlayerNotStarted)?;
         sink.clear();
-        self.emitter.on_player_state_updated(PlayerState {
-            playing: !sink.is_paused(),
-            pos: 0.,
-        });
+        self.end_pos.lock().unwrap().take();
+        // the loop region is specific to a source
+        self.controls.loop_control.set_region(None);
+        self.controls.loop_control.reset_pos();
+        self.emit_state(!sink.is_paused(), 0.);
 
-        let first_transit_pos = get_first_transit_pos(&path)?;
-        debug!("first transit pos: {first_transit_pos:?}");
         self.source.lock().unwrap().replace(SourceInfo {
             path,
-            first_transit_pos,
+            loudness,
+            silence: silence.filter(|silence| silence.options == self.silence_options),
         });
+        self.update_volume(sink);
+
+        Ok(())
+    }
+
+    /// Set the loudness of the file at `path`, if it is still the current source.
+    pub fn set_loudness(&self, path: &Path, loudness: Loudness) {
+        {
+            let mut source_info = self.source.lock().unwrap();
+            match source_info.as_mut() {
+                Some(source_info) if source_info.path == path => {
+                    source_info.loudness = Some(loudness);
+                }
+                _ => return,
+            }
+        }
+
+        if let Some(sink) = self.sink.read().unwrap().as_ref() {
+            self.update_volume(sink);
+        }
+    }
+
+    /// Set the silence of the file at `path`, if it is still the current source
+    /// and the silence was detected with the current options.
+    ///
+    /// If the source is being played skipping silence, e.g. the detection has finished
+    /// after the playback started, the silence is skipped from the current position.
+    pub fn set_silence(&self, path: &Path, silence: Silence) {
+        if silence.options != self.silence_options {
+            return;
+        }
+        {
+            let mut source_info = self.source.lock().unwrap();
+            match source_info.as_mut() {
+                Some(source_info) if source_info.path == path => {
+                    debug!("silence: {silence:?}");
+                    source_info.silence = Some(silence);
+                }
+                _ => return,
+            }
+        }
+
+        if !self.is_playing() || !self.skip_silence.load(Ordering::Acquire) {
+            return;
+        }
+
+        debug!("skip silence while playing");
+        match self.skip_silence(silence, true) {
+            Ok(()) => self.emit_state(true, self.controls.get_pos().as_secs_f32()),
+            Err(err) => warn!("Failed to skip the leading silence: {err}"),
+        }
+    }
+
+    pub fn get_silence_options(&self) -> SilenceOptions {
+        self.silence_options
+    }
+
+    /// Set the options to detect silence with.
+    ///
+    /// The silence of the current source is discarded if it was detected with other options,
+    /// so it should be set again with [`Player::set_silence`].
+    pub fn set_silence_options(&mut self, options: SilenceOptions) -> Result<(), Error> {
+        if !options.is_valid() {
+            return Err(Error::InvalidSilenceOptions(options));
+        }
 
+        debug!("set silence options to {options:?}");
+        self.silence_options = options;
+
+        if let Some(source_info) = self.source.lock().unwrap().as_mut() {
+            source_info.silence = source_info
+                .silence
+                .filter(|silence| silence.options == options);
+        }
         Ok(())
     }
 
+    /// Set the target loudness in LUFS to normalize the playback to, or `None` to disable normalization.
+    ///
+    /// The normalization is applied as a gain on playback and never modifies the file.
+    pub fn set_loudness_normalization(&mut self, target_loudness: Option<f32>) {
+        debug!("set loudness normalization to {target_loudness:?}");
+        self.target_loudness = target_loudness;
+
+        if let Some(sink) = self.sink.read().unwrap().as_ref() {
+            self.update_volume(sink);
+        }
+    }
+
     pub fn seek(&self, pos: Duration) -> Result<(), Error> {
+        let path = {
+            let source_info = self.source.lock().unwrap();
+            source_info
+                .as_ref()
+                .ok_or(Error::SourceNotSet)?
+                .path
+                .clone()
+        };
+
+        if self.is_playing() {
+            self.controls.fade_control.fade_out_and_wait();
+        }
+
         let sink = self.sink.read().unwrap();
         let sink = sink.as_ref().ok_or(Error::PlayerNotStarted)?;
-
-        let source_info = self.source.lock().unwrap();
-        let source_info = source_info.as_ref().ok_or(Error::SourceNotSet)?;
-
         if sink.empty() {
-            let source_path = source_info.path.as_path();
-            let file = BufReader::new(File::open(source_path)?);
-            let source = Decoder::new(file)?;
-            sink.append(source);
+            self.append_source(sink, &path)?;
         }
 
-        sink.try_seek(pos)?;
+        let result = sink.try_seek(pos);
+        if !sink.is_paused() {
+            self.controls.fade_control.fade_in();
+        }
+        result?;
 
-        self.emitter.on_player_state_updated(PlayerState {
-            playing: !sink.is_paused(),
-            pos: pos.as_secs_f32(),
-        });
+        let mut end_pos = self.end_pos.lock().unwrap();
+        if end_pos.is_some_and(|end_pos| pos >= end_pos) {
+            // seeked into the trailing silence, play it
+            *end_pos = None;
+        }
+        drop(end_pos);
+
+        self.emit_state(!sink.is_paused(), pos.as_secs_f32());
 
         Ok(())
     }
 
     pub fn play(&self, skip_silence: bool) -> Result<(), Error> {
-        {
+        self.skip_silence.store(skip_silence, Ordering::Release);
+        let silence = {
             let sink = self.sink.read().unwrap();
             let sink = sink.as_ref().ok_or(Error::PlayerNotStarted)?;
 
@@ -175,77 +530,398 @@ impl Player {
             let source_info = source_info.as_ref().ok_or(Error::SourceNotSet)?;
 
             if sink.empty() {
-                let source_path = source_info.path.as_path();
-                let file = BufReader::new(File::open(source_path)?);
-                let source = Decoder::new(file)?;
-                sink.append(source);
+                self.append_source(sink, &source_info.path)?;
             }
 
             debug!("continue playing");
 
-            if skip_silence && sink.get_pos() < source_info.first_transit_pos {
-                sink.try_seek(source_info.first_transit_pos)?;
-            }
-
+            self.end_pos.lock().unwrap().take();
+            source_info.silence.filter(|_| skip_silence)
+        };
+        if let Some(silence) = silence {
+            self.skip_silence(silence, false)?;
+        }
+        {
+            let sink = self.sink.read().unwrap();
+            let sink = sink.as_ref().ok_or(Error::PlayerNotStarted)?;
+            self.controls.fade_control.fade_in();
             sink.play();
         }
 
-        self.emitter.on_player_state_updated(PlayerState {
-            playing: true,
-            pos: self.get_pos(),
-        });
+        self.emit_state(true, self.get_pos());
 
         Ok(())
     }
 
     pub fn pause(&self) {
-        {
-            let sink = self.sink.read().unwrap();
-            if let Some(sink) = sink.as_ref() {
-                debug!("pause");
-                sink.pause();
-            }
+        debug!("pause");
+        if self.is_playing() {
+            self.controls.fade_control.fade_out_and_wait();
+        }
+        if let Some(sink) = self.sink.read().unwrap().as_ref() {
+            sink.pause();
         }
 
-        self.emitter.on_player_state_updated(PlayerState {
-            playing: false,
-            pos: self.get_pos(),
-        });
+        self.emit_state(false, self.get_pos());
     }
 
     pub fn stop(&self) {
-        {
-            let sink = self.sink.read().unwrap();
-            if let Some(sink) = sink.as_ref() {
-                debug!("stop");
-                sink.clear();
+        debug!("stop");
+        if self.is_playing() {
+            self.controls.fade_control.fade_out_and_wait();
+        }
+        if let Some(sink) = self.sink.read().unwrap().as_ref() {
+            sink.clear();
+            self.end_pos.lock().unwrap().take();
+        }
+        self.controls.loop_control.reset_pos();
+
+        self.emit_state(false, 0.);
+    }
+
+    /// Set the region to loop, or `None` to clear it.
+    ///
+    /// The region is cleared when the source changes.
+    pub fn set_loop_region(&self, region: Option<LoopRegion>) -> Result<(), Error> {
+        if let Some(region) = region {
+            if !(region.start >= 0. && region.start < region.end) {
+                return Err(Error::InvalidLoopRegion(region.start, region.end));
             }
         }
 
-        self.emitter.on_player_state_updated(PlayerState {
-            playing: false,
-            pos: 0.,
+        debug!("set loop region to {region:?}");
+        self.controls.loop_control.set_region(region);
+        self.emit_state(self.is_playing(), self.get_pos());
+        Ok(())
+    }
+
+    /// Enable or disable looping over the loop region.
+    pub fn set_looping(&self, enabled: bool) {
+        debug!("set looping to {enabled}");
+        self.controls.loop_control.set_enabled(enabled);
+        self.emit_state(self.is_playing(), self.get_pos());
+    }
+
+    /// Set the speed and the pitch of the playback.
+    ///
+    /// The position of the player stays in the time of the source.
+    pub fn set_playback_rate(&self, rate: PlaybackRate) -> Result<(), Error> {
+        if !rate.is_valid() {
+            return Err(Error::InvalidPlaybackRate(rate));
+        }
+
+        debug!("set playback rate to {rate:?}");
+        self.controls.rate_control.set(rate);
+        Ok(())
+    }
+
+    pub fn get_playback_rate(&self) -> PlaybackRate {
+        self.controls.rate_control.get()
+    }
+
+    /// Open and decode the beginning of the files likely to be played next in background,
+    /// e.g. the neighbouring entries, so that switching to them is near-instant.
+    pub fn preload(&self, paths: Vec<PathBuf>) {
+        self.preloader.preload(paths);
+    }
+
+    /// The entry the queue would advance to after the current entry, to preload it.
+    pub fn get_upcoming_in_queue(&self) -> Option<EntryId> {
+        self.queue.lock().unwrap().upcoming()
+    }
+
+    /// Whether silence was skipped when last played.
+    pub fn is_skipping_silence(&self) -> bool {
+        self.skip_silence.load(Ordering::Acquire)
+    }
+
+    pub fn get_queue(&self) -> QueueState {
+        self.queue.lock().unwrap().state()
+    }
+
+    /// Replace the queue with `entries`, e.g. a filtered result set.
+    ///
+    /// Returns the entry at `start` to play, if any.
+    pub fn set_queue(&self, entries: Vec<EntryId>, start: Option<usize>) -> Option<EntryId> {
+        self.update_queue(|queue| queue.set(entries, start))
+    }
+
+    pub fn enqueue(&self, entries: &[EntryId]) {
+        self.update_queue(|queue| queue.enqueue(entries));
+    }
+
+    pub fn remove_from_queue(&self, index: usize) -> Result<(), Error> {
+        if self.update_queue(|queue| queue.remove(index)) {
+            Ok(())
+        } else {
+            Err(Error::InvalidQueueIndex(index))
+        }
+    }
+
+    pub fn clear_queue(&self) {
+        self.update_queue(Queue::clear);
+    }
+
+    /// Make the entry at `index` of the queue the current entry, and return it to play.
+    pub fn jump_in_queue(&self, index: usize) -> Result<EntryId, Error> {
+        self.update_queue(|queue| queue.jump(index))
+            .ok_or(Error::InvalidQueueIndex(index))
+    }
+
+    /// Move to the next entry of the queue, and return it to play.
+    pub fn next_in_queue(&self) -> Option<EntryId> {
+        self.update_queue(Queue::next)
+    }
+
+    /// Move to the previous entry of the queue, and return it to play.
+    pub fn previous_in_queue(&self) -> Option<EntryId> {
+        self.update_queue(Queue::previous)
+    }
+
+    pub fn set_queue_options(&self, options: QueueOptions) -> Result<(), Error> {
+        if !(options.gap >= 0. && options.gap.is_finite()) {
+            return Err(Error::InvalidQueueGap(options.gap));
+        }
+
+        debug!("set queue options to {options:?}");
+        self.update_queue(|queue| queue.set_options(options));
+        Ok(())
+    }
+
+    /// Set the channels to audition, e.g. to solo a channel or sum to mono.
+    pub fn set_channel_options(&self, options: ChannelOptions) {
+        debug!("set channel options to {options:?}");
+        self.controls.channel_control.set(options);
+    }
+
+    pub fn get_channel_options(&self) -> ChannelOptions {
+        self.controls.channel_control.get()
+    }
+
+    pub fn get_fade_options(&self) -> FadeOptions {
+        self.controls.fade_control.get_options()
+    }
+
+    /// Set the fades applied around play, pause, stop, seek and source changes.
+    pub fn set_fade_options(&self, options: FadeOptions) -> Result<(), Error> {
+        if !options.is_valid() {
+            return Err(Error::InvalidFadeOptions(options));
+        }
+
+        debug!("set fade options to {options:?}");
+        self.controls.fade_control.set_options(options);
+        Ok(())
+    }
+
+    /// Push the levels of the playback to `callback` about 30 times per second,
+    /// until `callback` returns `false` or the meter is subscribed again or unsubscribed.
+    ///
+    /// Nothing is pushed while nothing is played, after the levels have dropped to silence.
+    pub fn subscribe_meter(&self, mut callback: impl FnMut(MeterLevels) -> bool + Send + 'static) {
+        let control = self.controls.meter_control.clone();
+        let generation = control.subscribe();
+        debug!("subscribe meter: {generation}");
+
+        spawn(move || {
+            let mut meter = Meter::new(control.clone());
+            while control.is_subscribed(generation) {
+                sleep(METER_INTERVAL);
+                if let Some(levels) = meter.update() {
+                    if !callback(levels) {
+                        break;
+                    }
+                }
+            }
+            debug!("meter {generation} stopped");
         });
     }
 
-    pub fn set_volume(&self, volume: f32) -> Result<(), Error> {
+    pub fn unsubscribe_meter(&self) {
+        debug!("unsubscribe meter");
+        self.controls.meter_control.unsubscribe();
+    }
+
+    /// Push the spectrum of the playback to `callback` about 30 times per second,
+    /// until `callback` returns `false` or the spectrum is subscribed again or unsubscribed.
+    ///
+    /// Nothing is pushed while nothing is played, after the spectrum has dropped to silence.
+    pub fn subscribe_spectrum(&self, mut callback: impl FnMut(Spectrum) -> bool + Send + 'static) {
+        let control = self.controls.spectrum_control.clone();
+        let generation = control.subscribe();
+        debug!("subscribe spectrum: {generation}");
+
+        spawn(move || {
+            let mut analyzer = Analyzer::new(control.clone());
+            while control.is_subscribed(generation) {
+                sleep(SPECTRUM_INTERVAL);
+                if let Some(spectrum) = analyzer.update() {
+                    if !callback(spectrum) {
+                        break;
+                    }
+                }
+            }
+            debug!("spectrum {generation} stopped");
+        });
+    }
+
+    pub fn unsubscribe_spectrum(&self) {
+        debug!("unsubscribe spectrum");
+        self.controls.spectrum_control.unsubscribe();
+    }
+
+    pub fn get_spectrum_options(&self) -> SpectrumOptions {
+        self.controls.spectrum_control.get_options()
+    }
+
+    /// Set the FFT size and the window of the spectrum, applied from the next update.
+    pub fn set_spectrum_options(&self, options: SpectrumOptions) -> Result<(), Error> {
+        if !options.is_valid() {
+            return Err(Error::InvalidFftSize(options.fft_size));
+        }
+
+        debug!("set spectrum options to {options:?}");
+        self.controls.spectrum_control.set_options(options);
+        Ok(())
+    }
+
+    pub fn get_output_target(&self) -> OutputTarget {
+        self.output_target.lock().unwrap().clone()
+    }
+
+    /// Set the output to play on.
+    ///
+    /// The output is switched by the player thread, and the playback continues on the new output.
+    pub fn set_output_target(&self, target: OutputTarget) {
+        debug!("set output target to {target:?}");
+        *self.output_target.lock().unwrap() = target;
+    }
+
+    /// Set the rate in Hz to push the position while playing, or `None` to disable it.
+    pub fn set_position_update_rate(&self, rate: Option<f32>) -> Result<(), Error> {
+        let interval = match rate {
+            Some(rate) if rate > 0. && rate.is_finite() => Some(Duration::from_secs_f32(1. / rate)),
+            Some(rate) => return Err(Error::InvalidPositionUpdateRate(rate)),
+            None => None,
+        };
+
+        debug!("set position update interval to {interval:?}");
+        *self.position_update_interval.lock().unwrap() = interval;
+        Ok(())
+    }
+
+    pub fn set_volume(&mut self, volume: f32) -> Result<(), Error> {
         let sink = self.sink.read().unwrap();
         let sink = sink.as_ref().ok_or(Error::PlayerNotStarted)?;
 
         debug!("set volume to {volume}");
-        sink.set_volume(volume);
+        self.volume = volume;
+        self.update_volume(sink);
         Ok(())
     }
 
+    /// Apply the volume to the sink, and the normalization gain to the source,
+    /// which ramps to it, e.g. when the loudness is measured after the playback has started.
+    fn update_volume(&self, sink: &Sink) {
+        let gain = self.get_normalization_gain();
+        debug!("normalization gain: {gain}");
+        sink.set_volume(self.volume);
+        self.controls.fade_control.set_gain(gain);
+    }
+
+    /// Get the gain to normalize the current source to the target loudness,
+    /// limited to keep the true peak below 0 dBTP.
+    fn get_normalization_gain(&self) -> f32 {
+        let Some(target_loudness) = self.target_loudness else {
+            return 1.;
+        };
+        let source_info = self.source.lock().unwrap();
+        let Some(loudness) = source_info.as_ref().and_then(|source| source.loudness) else {
+            return 1.;
+        };
+        if !loudness.integrated.is_finite() {
+            // silence
+            return 1.;
+        }
+
+        let gain_db = (target_loudness - loudness.integrated).min(-loudness.true_peak);
+        10f32.powf(gain_db / 20.)
+    }
+
     /// Get the current position of the player in seconds.
     pub fn get_pos(&self) -> f32 {
         let sink = self.sink.read().unwrap();
 
         match sink.as_ref() {
-            Some(sink) => sink.get_pos().as_secs_f32(),
-            None => 0.,
+            Some(sink) if !sink.empty() => self.controls.get_pos().as_secs_f32(),
+            _ => 0.,
         }
     }
+
+    fn is_playing(&self) -> bool {
+        self.sink
+            .read()
+            .unwrap()
+            .as_ref()
+            .is_some_and(|sink| !sink.empty() && !sink.is_paused())
+    }
+
+    /// Seek to the end of the leading silence if before it,
+    /// and stop at the start of the trailing silence if before it.
+    ///
+    /// The seek is faded if `playing`. The sink is locked, so it must not be locked by the caller.
+    fn skip_silence(&self, silence: Silence, playing: bool) -> Result<(), Error> {
+        let head = Duration::from_secs_f32(silence.head);
+        if self.controls.get_pos() < head {
+            if playing {
+                self.controls.fade_control.fade_out_and_wait();
+            }
+            let result = match self.sink.read().unwrap().as_ref() {
+                Some(sink) => sink.try_seek(head).map_err(Error::from),
+                None => Err(Error::PlayerNotStarted),
+            };
+            if playing {
+                self.controls.fade_control.fade_in();
+            }
+            result?;
+        }
+
+        let tail = Duration::from_secs_f32(silence.tail);
+        if self.controls.get_pos() < tail {
+            *self.end_pos.lock().unwrap() = Some(tail);
+        }
+        Ok(())
+    }
+
+    /// Append the file at `path` to the sink, preloaded if it has been.
+    fn append_source(&self, sink: &Sink, path: &Path) -> Result<(), Error> {
+        let source = match self.preloader.take(path) {
+            Some(source) => {
+                debug!("play preloaded source");
+                source
+            }
+            None => FileSource::open(path)?,
+        };
+        append_source(sink, source, path, &self.controls)
+    }
+
+    /// Apply `f` to the queue and emit the state of the queue.
+    fn update_queue<T>(&self, f: impl FnOnce(&mut Queue) -> T) -> T {
+        let (result, state) = {
+            let mut queue = self.queue.lock().unwrap();
+            let result = f(&mut queue);
+            (result, queue.state())
+        };
+        self.emitter.on_player_queue_updated(state);
+        result
+    }
+
+    fn emit_state(&self, playing: bool, pos: f32) {
+        self.emitter.on_player_state_updated(PlayerState {
+            playing,
+            pos,
+            loop_region: self.controls.loop_control.active_region(),
+        });
+    }
 }
 
 impl Drop for Player {
@@ -254,6 +930,79 @@ impl Drop for Player {
     }
 }
 
+/// Append the source of the file at `path` to the sink.
+fn append_source(
+    sink: &Sink,
+    source: FileSource,
+    path: &Path,
+    controls: &SourceControls,
+) -> Result<(), Error> {
+    // `.amb` files are ambisonics in FuMa
+    let fuma = path
+        .extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("amb"));
+    sink.append(SpectrumSource::new(
+        MeterSource::new(
+            FadeSource::new(
+                RateSource::new(
+                    ChannelSource::new(
+                        LoopSource::new(source, controls.loop_control.clone()),
+                        controls.channel_control.clone(),
+                        fuma,
+                    ),
+                    controls.rate_control.clone(),
+                ),
+                controls.fade_control.clone(),
+            ),
+            controls.meter_control.clone(),
+        ),
+        controls.spectrum_control.clone(),
+    ));
+    Ok(())
+}
+
+/// Open the output of `target`, falling back to the default device and then the null output.
+///
+/// The errors are reported to `emitter`.
+fn open_output(target: &OutputTarget, emitter: &dyn PlayerEmitter) -> (Output, Sink) {
+    let result = Output::open(target).or_else(|err| {
+        if *target == OutputTarget::Default {
+            return Err(err);
+        }
+        warn!("Failed to open output {target:?}, fall back to the default device: {err}");
+        emitter.on_player_error(&err);
+        Output::open(&OutputTarget::Default)
+    });
+
+    result.unwrap_or_else(|err| {
+        warn!("Failed to open the default output device, fall back to the null output: {err}");
+        emitter.on_player_error(&err);
+        Output::open_null()
+    })
+}
+
+/// Continue the playback of `old_sink` on `new_sink` from the same position,
+/// with the same volume and paused state.
+fn transfer_playback(
+    old_sink: &Sink,
+    new_sink: &Sink,
+    path: Option<&Path>,
+    controls: &SourceControls,
+) -> Result<(), Error> {
+    new_sink.set_volume(old_sink.volume());
+    if old_sink.is_paused() {
+        new_sink.pause();
+    }
+
+    if let Some(path) = path.filter(|_| !old_sink.empty()) {
+        let pos = controls.get_pos();
+        old_sink.clear();
+        append_source(new_sink, FileSource::open(path)?, path, controls)?;
+        new_sink.try_seek(pos)?;
+    }
+    Ok(())
+}
+
 pub fn get_format_reader(
     path: &Path,
 ) -> Result<Box<dyn FormatReader>, symphonia::core::errors::Error> {
@@ -271,79 +1020,3 @@ pub fn get_format_reader(
     )?;
     Ok(format)
 }
-
-#[allow(clippy::cast_precision_loss)]
-pub fn get_first_transit_pos(path: &Path) -> Result<Duration, Error> {
-    let mut reader = get_format_reader(path)?;
-    let track = reader
-        .default_track(TrackType::Audio)
-        .ok_or(Error::TracksNotFound(path.to_string_lossy().to_string()))?;
-    let track_id = track.id;
-
-    let params = &track
-        .codec_params
-        .as_ref()
-        .and_then(CodecParameters::audio)
-        .ok_or_else(|| Error::CodecParamsMissing)?;
-    let n_channels = params
-        .channels
-        .as_ref()
-        .ok_or_else(|| Error::CodecParamsMissing)?
-        .count();
-    let sample_rate = params.sample_rate.unwrap();
-
-    let mut decoder = symphonia::default::get_codecs()
-        .make_audio_decoder(params, &AudioDecoderOptions::default())?;
-    let mut sample_buf = Vec::<i16>::new();
-
-    let mut current_pos: usize = 0;
-
-    let pos_frame = loop {
-        match reader.next_packet() {
-            Err(err) => break Err(err),
-
-            Ok(Some(packet)) => {
-                if packet.track_id() != track_id {
-                    continue;
-                }
-
-                match decoder.decode(&packet) {
-                    Ok(audio_buf) => {
-                        sample_buf.resize(audio_buf.samples_interleaved(), 0);
-                        audio_buf.copy_to_slice_interleaved(&mut sample_buf);
-
-                        if let Some(pos) = sample_buf.iter().position(|&x| x != 0) {
-                            // first transit found
-                            let pos_frame = (current_pos + pos) / n_channels;
-                            break Ok(Some(pos_frame));
-                        }
-
-                        // first transit not found, continue
-                        let packet_length = sample_buf.len();
-                        current_pos += packet_length;
-                    }
-
-                    // The packet failed to decode due to an IO error or invalid data, skip the packet.
-                    Err(symphonia::core::errors::Error::IoError(err)) => {
-                        // The packet failed to decode due to an IO error, skip the packet.
-                        warn!("IO error: {err}");
-                    }
-                    Err(symphonia::core::errors::Error::DecodeError(err)) => {
-                        // The packet failed to decode due to invalid data, skip the packet.
-                        warn!("decode error: {err}");
-                    }
-
-                    // An unrecoverable error occurred, halt decoding.
-                    Err(err) => break Err(err),
-                }
-            }
-
-            Ok(None) => break Ok(None), // end of stream
-        }
-    }?; // loop
-
-    // Default to 0
-    Ok(Duration::from_secs_f32(
-        pos_frame.unwrap_or(0) as f32 / sample_rate as f32,
-    ))
-}
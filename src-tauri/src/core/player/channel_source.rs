@@ -0,0 +1,293 @@
+use std::f32::consts::{FRAC_1_SQRT_2, SQRT_2};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use rodio::source::SeekError;
+use rodio::{Sample, Source};
+use serde::{Deserialize, Serialize};
+
+/// The options of the channels to audition, applied after downmixing to stereo.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChannelOptions {
+    /// The index of the channel of the source to play alone on all outputs,
+    /// ignored if the source does not have the channel.
+    pub solo: Option<u16>,
+    /// Sum the channels to mono.
+    pub mono: bool,
+    /// Swap the left and the right channels.
+    pub swap: bool,
+    /// Invert the polarity.
+    pub invert: bool,
+    /// Treat sources of 4 or more channels as first order ambisonics in AmbiX (ACN/SN3D),
+    /// which cannot be told apart from other layouts.
+    ///
+    /// `.amb` files are always treated as ambisonics in FuMa.
+    pub ambisonic: bool,
+}
+
+/// The channel options shared between the player and [`ChannelSource`].
+#[derive(Default)]
+pub struct ChannelControl {
+    options: Mutex<ChannelOptions>,
+    /// Incremented whenever the options change, so that the source picks up the change.
+    version: AtomicU64,
+}
+
+impl ChannelControl {
+    pub fn get(&self) -> ChannelOptions {
+        *self.options.lock().unwrap()
+    }
+
+    pub fn set(&self, options: ChannelOptions) {
+        *self.options.lock().unwrap() = options;
+        self.version.fetch_add(1, Ordering::Release);
+    }
+}
+
+/// The layout of the channels of a source, by the order of the channels in the file.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Layout {
+    Mono,
+    Stereo,
+    /// L, R, C, LFE, Ls, Rs.
+    Surround51,
+    /// L, R, C, LFE, Lb, Rb, Ls, Rs.
+    Surround71,
+    /// W, Y, Z, X with SN3D normalization, and higher orders ignored.
+    AmbiX,
+    /// W, X, Y, Z with W attenuated by 3 dB, and higher orders ignored.
+    FuMa,
+    /// Unknown, of which the first two channels are played as left and right.
+    Other,
+}
+
+impl Layout {
+    fn detect(n_channels: usize, fuma: bool, ambisonic: bool) -> Self {
+        match n_channels {
+            1 => Self::Mono,
+            2 => Self::Stereo,
+            3.. if fuma => Self::FuMa,
+            4.. if ambisonic => Self::AmbiX,
+            6 => Self::Surround51,
+            8 => Self::Surround71,
+            _ => Self::Other,
+        }
+    }
+
+    /// The gains of the input channels to the left and the right outputs.
+    fn stereo_gains(self, n_channels: usize) -> [Vec<f32>; 2] {
+        let mut left = vec![0.; n_channels];
+        let mut right = vec![0.; n_channels];
+        match self {
+            Self::Mono => {
+                left[0] = 1.;
+                right[0] = 1.;
+            }
+            Self::Stereo | Self::Other => {
+                left[0] = 1.;
+                right[1] = 1.;
+            }
+            Self::Surround51 | Self::Surround71 => {
+                // ITU-R BS.775 without LFE
+                left[0] = 1.;
+                right[1] = 1.;
+                left[2] = FRAC_1_SQRT_2;
+                right[2] = FRAC_1_SQRT_2;
+                for (i, channel) in (4..n_channels).enumerate() {
+                    if i % 2 == 0 {
+                        left[channel] = FRAC_1_SQRT_2;
+                    } else {
+                        right[channel] = FRAC_1_SQRT_2;
+                    }
+                }
+            }
+            Self::AmbiX | Self::FuMa => {
+                // virtual cardioids pointing to the left and the right
+                let (w_gain, y) = if self == Self::AmbiX {
+                    (1., 1)
+                } else {
+                    (SQRT_2, 2)
+                };
+                left[0] = 0.5 * w_gain;
+                right[0] = 0.5 * w_gain;
+                left[y] = 0.5;
+                right[y] = -0.5;
+            }
+        }
+        [left, right]
+    }
+}
+
+/// Wraps a source to apply the [`ChannelOptions`] of [`ChannelControl`],
+/// with the channels downmixed to stereo if there are more than two.
+pub struct ChannelSource<S> {
+    input: S,
+    control: Arc<ChannelControl>,
+    version: u64,
+    /// Whether the source is ambisonics in FuMa, i.e. an `.amb` file.
+    fuma: bool,
+    n_input_channels: usize,
+    n_output_channels: usize,
+    /// The gains of the input channels to each output channel, by output channel.
+    matrix: Vec<f32>,
+    input_frame: Vec<f32>,
+    output_frame: Vec<f32>,
+    /// The position of the next sample in `output_frame`.
+    output_pos: usize,
+}
+
+impl<S> ChannelSource<S>
+where
+    S: Source,
+    S::Item: Sample,
+{
+    pub fn new(input: S, control: Arc<ChannelControl>, fuma: bool) -> Self {
+        let n_input_channels = usize::from(input.channels());
+        let n_output_channels = n_input_channels.min(2);
+
+        let mut source = Self {
+            input,
+            control,
+            version: 0,
+            fuma,
+            n_input_channels,
+            n_output_channels,
+            matrix: Vec::new(),
+            input_frame: Vec::with_capacity(n_input_channels),
+            output_frame: vec![0.; n_output_channels],
+            output_pos: n_output_channels,
+        };
+        source.update_options();
+        source
+    }
+
+    /// Read the options from the control and build the matrix.
+    fn update_options(&mut self) {
+        self.version = self.control.version.load(Ordering::Acquire);
+        let options = self.control.get();
+        let n_inputs = self.n_input_channels;
+        let layout = Layout::detect(n_inputs, self.fuma, options.ambisonic);
+
+        let mut rows = match options.solo.map(usize::from) {
+            Some(solo) if solo < n_inputs => {
+                let mut row = vec![0.; n_inputs];
+                row[solo] = 1.;
+                vec![row; self.n_output_channels]
+            }
+            _ if self.n_output_channels == 1 => vec![vec![1.]],
+            _ => layout.stereo_gains(n_inputs).to_vec(),
+        };
+
+        // keep the peak below full scale
+        for row in &mut rows {
+            let sum = row.iter().map(|gain: &f32| gain.abs()).sum::<f32>();
+            if sum > 1. {
+                row.iter_mut().for_each(|gain| *gain /= sum);
+            }
+        }
+
+        if rows.len() == 2 {
+            if options.mono {
+                let mono = rows[0]
+                    .iter()
+                    .zip(&rows[1])
+                    .map(|(left, right)| (left + right) / 2.)
+                    .collect::<Vec<_>>();
+                rows = vec![mono.clone(), mono];
+            }
+            if options.swap {
+                rows.swap(0, 1);
+            }
+        }
+        let polarity = if options.invert { -1. } else { 1. };
+
+        self.matrix = rows
+            .into_iter()
+            .flatten()
+            .map(|gain| gain * polarity)
+            .collect();
+    }
+}
+
+impl<S> Iterator for ChannelSource<S>
+where
+    S: Source,
+    S::Item: Sample,
+{
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        if self.output_pos == self.n_output_channels {
+            if self.control.version.load(Ordering::Acquire) != self.version {
+                self.update_options();
+            }
+
+            self.input_frame.clear();
+            self.input_frame.extend(
+                self.input
+                    .by_ref()
+                    .take(self.n_input_channels)
+                    .map(Sample::to_f32),
+            );
+            if self.input_frame.len() < self.n_input_channels {
+                return None;
+            }
+
+            for (output, gains) in self
+                .output_frame
+                .iter_mut()
+                .zip(self.matrix.chunks_exact(self.n_input_channels))
+            {
+                *output = gains
+                    .iter()
+                    .zip(&self.input_frame)
+                    .map(|(g, x)| g * x)
+                    .sum();
+            }
+            self.output_pos = 0;
+        }
+
+        let sample = self.output_frame[self.output_pos];
+        self.output_pos += 1;
+        Some(sample)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let (lower, upper) = self.input.size_hint();
+        let convert = |n: usize| n / self.n_input_channels * self.n_output_channels;
+        (convert(lower), upper.map(convert))
+    }
+}
+
+impl<S> Source for ChannelSource<S>
+where
+    S: Source,
+    S::Item: Sample,
+{
+    fn current_frame_len(&self) -> Option<usize> {
+        self.input
+            .current_frame_len()
+            .map(|len| len / self.n_input_channels * self.n_output_channels)
+    }
+
+    #[allow(clippy::cast_possible_truncation)]
+    fn channels(&self) -> u16 {
+        self.n_output_channels as u16
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.input.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.input.total_duration()
+    }
+
+    fn try_seek(&mut self, pos: Duration) -> Result<(), SeekError> {
+        self.input.try_seek(pos)?;
+        self.output_pos = self.n_output_channels;
+        Ok(())
+    }
+}
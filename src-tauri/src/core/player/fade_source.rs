@@ -0,0 +1,251 @@
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::Duration;
+
+use rodio::source::SeekError;
+use rodio::{Sample, Source};
+use serde::{Deserialize, Serialize};
+
+/// The longest fade allowed, in seconds.
+const MAX_FADE_DURATION: f32 = 1.;
+/// The duration of the ramp to a new gain, in seconds.
+const GAIN_RAMP_DURATION: f32 = 0.05;
+
+/// The fades applied around transport actions to avoid clicks.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FadeOptions {
+    /// The duration of the fade-in on play, after seeking and at the start of a source, in seconds.
+    pub fade_in: f32,
+    /// The duration of the fade-out on pause, stop, seek and source change, in seconds.
+    pub fade_out: f32,
+}
+
+impl Default for FadeOptions {
+    fn default() -> Self {
+        Self {
+            fade_in: 0.01,
+            fade_out: 0.01,
+        }
+    }
+}
+
+impl FadeOptions {
+    pub fn is_valid(self) -> bool {
+        (0.0..=MAX_FADE_DURATION).contains(&self.fade_in)
+            && (0.0..=MAX_FADE_DURATION).contains(&self.fade_out)
+    }
+}
+
+/// The fade state shared between the player and [`FadeSource`].
+pub struct FadeControl {
+    options: Mutex<FadeOptions>,
+    /// The gain to ramp to, e.g. to normalize the loudness.
+    gain: Mutex<f32>,
+    /// Incremented whenever the options or the gain change, so that the source picks up the change.
+    version: AtomicU64,
+    /// Whether to fade out, or to fade in otherwise.
+    fading_out: AtomicBool,
+    /// Whether the source has faded out completely.
+    silent: AtomicBool,
+    /// Locked to set `silent` and notify `silenced`, so that a waiter never misses the notification.
+    silent_lock: Mutex<()>,
+    silenced: Condvar,
+}
+
+impl Default for FadeControl {
+    fn default() -> Self {
+        Self {
+            options: Mutex::default(),
+            gain: Mutex::new(1.),
+            version: AtomicU64::default(),
+            fading_out: AtomicBool::default(),
+            silent: AtomicBool::default(),
+            silent_lock: Mutex::default(),
+            silenced: Condvar::new(),
+        }
+    }
+}
+
+impl FadeControl {
+    pub fn get_options(&self) -> FadeOptions {
+        *self.options.lock().unwrap()
+    }
+
+    pub fn set_options(&self, options: FadeOptions) {
+        *self.options.lock().unwrap() = options;
+        self.version.fetch_add(1, Ordering::Release);
+    }
+
+    /// Set the gain applied to the source, which ramps to it instead of jumping while playing.
+    pub fn set_gain(&self, gain: f32) {
+        *self.gain.lock().unwrap() = gain;
+        self.version.fetch_add(1, Ordering::Release);
+    }
+
+    /// Fade in from the current gain.
+    pub fn fade_in(&self) {
+        self.fading_out.store(false, Ordering::Release);
+        self.silent.store(false, Ordering::Release);
+    }
+
+    /// Fade out to silence, which the source keeps until [`FadeControl::fade_in`].
+    pub fn fade_out(&self) {
+        self.silent.store(false, Ordering::Release);
+        self.fading_out.store(true, Ordering::Release);
+    }
+
+    /// Fade out and wait until the source is silent.
+    ///
+    /// Gives up after twice the fade duration, e.g. when the output does not pull the source,
+    /// so it should not be called while holding a lock the output needs.
+    pub fn fade_out_and_wait(&self) {
+        self.fade_out();
+        let timeout = Duration::from_secs_f32(self.get_options().fade_out * 2.);
+        let guard = self.silent_lock.lock().unwrap();
+        let (_guard, _) = self
+            .silenced
+            .wait_timeout_while(guard, timeout, |()| !self.silent.load(Ordering::Acquire))
+            .unwrap();
+    }
+
+    /// Mark the source as silent, waking up the waiters of [`FadeControl::fade_out_and_wait`].
+    fn set_silent(&self) {
+        let _guard = self.silent_lock.lock().unwrap();
+        self.silent.store(true, Ordering::Release);
+        self.silenced.notify_all();
+    }
+}
+
+/// Wraps a source to fade it in and out as requested by [`FadeControl`],
+/// and to apply its gain.
+///
+/// The source starts silent and fades in, unless it is requested to fade out.
+pub struct FadeSource<S> {
+    input: S,
+    control: Arc<FadeControl>,
+    version: u64,
+    options: FadeOptions,
+    channels: u16,
+    sample_rate: u32,
+    /// The gain of the fade, from 0 to 1.
+    gain: f32,
+    /// The gain of the control, and the gain ramping to it.
+    target_level: f32,
+    level: f32,
+    /// The change of `level` per frame.
+    level_step: f32,
+    /// The position of the next sample in the frame, as the gain changes once per frame.
+    frame_pos: u16,
+}
+
+impl<S> FadeSource<S>
+where
+    S: Source,
+    S::Item: Sample,
+{
+    pub fn new(input: S, control: Arc<FadeControl>) -> Self {
+        let level = *control.gain.lock().unwrap();
+        Self {
+            channels: input.channels().max(1),
+            sample_rate: input.sample_rate(),
+            input,
+            version: control.version.load(Ordering::Acquire),
+            options: control.get_options(),
+            control,
+            gain: 0.,
+            target_level: level,
+            level,
+            level_step: 0.,
+            frame_pos: 0,
+        }
+    }
+
+    /// Move the gains one frame towards the target of the fade and the gain of the control.
+    #[allow(clippy::cast_precision_loss)]
+    fn update_gain(&mut self) {
+        let version = self.control.version.load(Ordering::Acquire);
+        if version != self.version {
+            self.version = version;
+            self.options = self.control.get_options();
+            self.target_level = *self.control.gain.lock().unwrap();
+            self.level_step = (self.target_level - self.level).abs()
+                / (GAIN_RAMP_DURATION * self.sample_rate as f32);
+        }
+
+        self.level = if self.level < self.target_level {
+            (self.level + self.level_step).min(self.target_level)
+        } else {
+            (self.level - self.level_step).max(self.target_level)
+        };
+
+        let options = self.options;
+        let fading_out = self.control.fading_out.load(Ordering::Acquire);
+        let (target, duration) = if fading_out {
+            (0., options.fade_out)
+        } else {
+            (1., options.fade_in)
+        };
+
+        let step = 1. / (duration * self.sample_rate as f32);
+        self.gain = if self.gain < target {
+            (self.gain + step).min(target)
+        } else {
+            (self.gain - step).max(target)
+        };
+
+        // only locked once faded out, not on every frame while silent
+        if fading_out && self.gain <= 0. && !self.control.silent.load(Ordering::Acquire) {
+            self.control.set_silent();
+        }
+    }
+}
+
+impl<S> Iterator for FadeSource<S>
+where
+    S: Source,
+    S::Item: Sample,
+{
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        if self.frame_pos == 0 {
+            self.update_gain();
+        }
+        self.frame_pos = (self.frame_pos + 1) % self.channels;
+
+        self.input
+            .next()
+            .map(|sample| sample.to_f32() * self.gain * self.level)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.input.size_hint()
+    }
+}
+
+impl<S> Source for FadeSource<S>
+where
+    S: Source,
+    S::Item: Sample,
+{
+    fn current_frame_len(&self) -> Option<usize> {
+        self.input.current_frame_len()
+    }
+
+    fn channels(&self) -> u16 {
+        self.input.channels()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.input.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.input.total_duration()
+    }
+
+    fn try_seek(&mut self, pos: Duration) -> Result<(), SeekError> {
+        self.input.try_seek(pos)
+    }
+}
@@ -0,0 +1,257 @@
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use log::warn;
+use rodio::source::SeekError;
+use rodio::{Sample, Source};
+use serde::{Deserialize, Serialize};
+
+/// A region of the source in seconds.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct LoopRegion {
+    pub start: f32,
+    pub end: f32,
+}
+
+/// The loop settings shared between the player and [`LoopSource`],
+/// and the position of the source reported back to the player.
+#[derive(Default)]
+pub struct LoopControl {
+    region: Mutex<Option<LoopRegion>>,
+    enabled: AtomicBool,
+    /// Incremented whenever the region or `enabled` changes, so that the source picks up the change.
+    version: AtomicU64,
+    /// The position of the source in frames, which wraps around with the loop.
+    pos: AtomicU64,
+    sample_rate: AtomicU32,
+    /// Whether the source has played to its end, rather than being cleared from the sink.
+    ended: AtomicBool,
+    /// Whether the source has wrapped around the region since the last seek or region change.
+    wrapped: AtomicBool,
+}
+
+impl LoopControl {
+    pub fn set_region(&self, region: Option<LoopRegion>) {
+        *self.region.lock().unwrap() = region;
+        self.version.fetch_add(1, Ordering::Release);
+    }
+
+    pub fn set_enabled(&self, enabled: bool) {
+        self.enabled.store(enabled, Ordering::Release);
+        self.version.fetch_add(1, Ordering::Release);
+    }
+
+    /// The region being looped, i.e. `None` if looping is disabled.
+    pub fn active_region(&self) -> Option<LoopRegion> {
+        if self.enabled.load(Ordering::Acquire) {
+            *self.region.lock().unwrap()
+        } else {
+            None
+        }
+    }
+
+    /// The position of the source in frames, of the last frame read from it.
+    pub fn get_frames(&self) -> u64 {
+        self.pos.load(Ordering::Acquire)
+    }
+
+    /// The position of the source `latency` frames before the current position,
+    /// e.g. of the frames being played while the frames read are buffered downstream,
+    /// wrapped around the region if the source has looped.
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    pub fn get_frames_before(&self, latency: u64) -> u64 {
+        let frames = self.get_frames();
+        let sample_rate = f64::from(self.get_sample_rate());
+        let bounds = self
+            .active_region()
+            .filter(|_| self.wrapped.load(Ordering::Acquire))
+            .map(|region| {
+                let to_frames = |secs: f32| (f64::from(secs.max(0.)) * sample_rate).round() as u64;
+                (to_frames(region.start), to_frames(region.end))
+            })
+            .filter(|&(start, end)| (start..end).contains(&frames));
+
+        match bounds {
+            Some((start, end)) => {
+                let len = end - start;
+                start + (frames - start + len - latency % len) % len
+            }
+            None => frames.saturating_sub(latency),
+        }
+    }
+
+    /// The sample rate of the source, zero if no source has been played.
+    pub fn get_sample_rate(&self) -> u32 {
+        self.sample_rate.load(Ordering::Acquire)
+    }
+
+    pub fn reset_pos(&self) {
+        self.pos.store(0, Ordering::Release);
+    }
+
+    /// Mark the source as ended, e.g. when it is stopped at the trailing silence.
+    pub fn set_ended(&self) {
+        self.ended.store(true, Ordering::Release);
+    }
+
+    /// Whether the source has ended since the last call.
+    pub fn take_ended(&self) -> bool {
+        self.ended.swap(false, Ordering::AcqRel)
+    }
+}
+
+/// Wraps a source to loop over the active region of [`LoopControl`] without gaps,
+/// by seeking back to the start of the region when its end is reached.
+///
+/// The loop only wraps around when playing into the end of the region,
+/// so a region before the current position is not jumped back to.
+pub struct LoopSource<S> {
+    input: S,
+    control: Arc<LoopControl>,
+    channels: u64,
+    sample_rate: u32,
+    /// The position in samples.
+    pos: u64,
+    /// The start and end of the active region in samples.
+    bounds: Option<(u64, u64)>,
+    version: u64,
+}
+
+impl<S> LoopSource<S>
+where
+    S: Source,
+    S::Item: Sample,
+{
+    pub fn new(input: S, control: Arc<LoopControl>) -> Self {
+        let channels = u64::from(input.channels());
+        let sample_rate = input.sample_rate();
+        control.sample_rate.store(sample_rate, Ordering::Release);
+        control.reset_pos();
+        control.ended.store(false, Ordering::Release);
+        control.wrapped.store(false, Ordering::Release);
+
+        let mut source = Self {
+            input,
+            control,
+            channels,
+            sample_rate,
+            pos: 0,
+            bounds: None,
+            version: 0,
+        };
+        source.update_bounds();
+        source
+    }
+
+    /// Read the active region from the control.
+    #[allow(clippy::cast_possible_truncation)]
+    #[allow(clippy::cast_sign_loss)]
+    fn update_bounds(&mut self) {
+        self.version = self.control.version.load(Ordering::Acquire);
+        let to_samples = |secs: f32| {
+            (f64::from(secs.max(0.)) * f64::from(self.sample_rate)).round() as u64 * self.channels
+        };
+        self.bounds = self
+            .control
+            .active_region()
+            .map(|region| (to_samples(region.start), to_samples(region.end)))
+            .filter(|(start, end)| start < end);
+        self.control.wrapped.store(false, Ordering::Release);
+    }
+
+    #[allow(clippy::cast_precision_loss)]
+    fn to_duration(&self, samples: u64) -> Duration {
+        Duration::from_secs_f64((samples / self.channels) as f64 / f64::from(self.sample_rate))
+    }
+}
+
+impl<S> Iterator for LoopSource<S>
+where
+    S: Source,
+    S::Item: Sample,
+{
+    type Item = S::Item;
+
+    fn next(&mut self) -> Option<S::Item> {
+        if self.control.version.load(Ordering::Acquire) != self.version {
+            self.update_bounds();
+        }
+
+        if let Some((start, end)) = self.bounds {
+            if self.pos == end {
+                match self.input.try_seek(self.to_duration(start)) {
+                    Ok(()) => {
+                        self.pos = start;
+                        self.control.wrapped.store(true, Ordering::Release);
+                    }
+                    Err(err) => {
+                        warn!("Failed to loop, stop looping: {err}");
+                        self.bounds = None;
+                    }
+                }
+            }
+        }
+
+        let Some(sample) = self.input.next() else {
+            self.control.set_ended();
+            return None;
+        };
+        self.pos += 1;
+        if self.pos.is_multiple_of(self.channels) {
+            self.control
+                .pos
+                .store(self.pos / self.channels, Ordering::Release);
+        }
+        Some(sample)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        // unbounded while looping
+        (self.input.size_hint().0, None)
+    }
+}
+
+impl<S> Source for LoopSource<S>
+where
+    S: Source,
+    S::Item: Sample,
+{
+    fn current_frame_len(&self) -> Option<usize> {
+        let len = self.input.current_frame_len()?;
+        // the span ends early at the end of the region
+        match self.bounds {
+            Some((_, end)) if self.pos < end => {
+                Some(usize::try_from(end - self.pos).map_or(len, |remaining| len.min(remaining)))
+            }
+            _ => Some(len),
+        }
+    }
+
+    fn channels(&self) -> u16 {
+        self.input.channels()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.input.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        if self.bounds.is_some() {
+            None
+        } else {
+            self.input.total_duration()
+        }
+    }
+
+    #[allow(clippy::cast_possible_truncation)]
+    #[allow(clippy::cast_sign_loss)]
+    fn try_seek(&mut self, pos: Duration) -> Result<(), SeekError> {
+        self.input.try_seek(pos)?;
+        let frames = (pos.as_secs_f64() * f64::from(self.sample_rate)).round() as u64;
+        self.pos = frames * self.channels;
+        self.control.pos.store(frames, Ordering::Release);
+        self.control.wrapped.store(false, Ordering::Release);
+        Ok(())
+    }
+}
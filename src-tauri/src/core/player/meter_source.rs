@@ -0,0 +1,267 @@
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use rodio::source::SeekError;
+use rodio::{Sample, Source};
+use serde::Serialize;
+
+/// The number of frames measured before the levels are handed to [`MeterControl`].
+const BLOCK_FRAMES: usize = 256;
+/// How long the peak-hold value is held before it falls back to the peak.
+const PEAK_HOLD_DURATION: Duration = Duration::from_millis(1500);
+
+/// The levels of each channel since the last update, as linear amplitudes.
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MeterLevels {
+    pub peak: Vec<f32>,
+    pub rms: Vec<f32>,
+    /// The highest peak of the last [`PEAK_HOLD_DURATION`].
+    pub peak_hold: Vec<f32>,
+}
+
+/// The levels measured by [`MeterSource`] and not yet taken by the meter.
+#[derive(Default)]
+struct Block {
+    peak: Vec<f32>,
+    sum_squares: Vec<f32>,
+    n_frames: usize,
+}
+
+impl Block {
+    fn reset(&mut self, n_channels: usize) {
+        self.peak.clear();
+        self.peak.resize(n_channels, 0.);
+        self.sum_squares.clear();
+        self.sum_squares.resize(n_channels, 0.);
+        self.n_frames = 0;
+    }
+
+    fn merge(&mut self, other: &Self) {
+        if self.peak.len() != other.peak.len() {
+            // the channels of the source changed
+            self.reset(other.peak.len());
+        }
+        for (peak, other) in self.peak.iter_mut().zip(&other.peak) {
+            *peak = peak.max(*other);
+        }
+        for (sum, other) in self.sum_squares.iter_mut().zip(&other.sum_squares) {
+            *sum += other;
+        }
+        self.n_frames += other.n_frames;
+    }
+}
+
+/// The levels shared between [`MeterSource`] and the meter of the player.
+#[derive(Default)]
+pub struct MeterControl {
+    block: Mutex<Block>,
+    /// Whether the levels are measured, i.e. anyone is subscribed.
+    active: AtomicBool,
+    /// Incremented whenever the meter is subscribed or unsubscribed,
+    /// so that a replaced subscription stops.
+    generation: AtomicU64,
+}
+
+impl MeterControl {
+    /// Start a new subscription, stopping the previous one, and return its generation.
+    pub fn subscribe(&self) -> u64 {
+        self.block.lock().unwrap().reset(0);
+        self.active.store(true, Ordering::Release);
+        self.generation.fetch_add(1, Ordering::AcqRel) + 1
+    }
+
+    pub fn unsubscribe(&self) {
+        self.active.store(false, Ordering::Release);
+        self.generation.fetch_add(1, Ordering::AcqRel);
+    }
+
+    pub fn is_subscribed(&self, generation: u64) -> bool {
+        self.generation.load(Ordering::Acquire) == generation
+    }
+
+    fn is_active(&self) -> bool {
+        self.active.load(Ordering::Acquire)
+    }
+
+    /// Take the levels measured since the last call, or `None` if nothing has been played.
+    #[allow(clippy::cast_precision_loss)]
+    fn take(&self) -> Option<(Vec<f32>, Vec<f32>)> {
+        let mut block = self.block.lock().unwrap();
+        if block.n_frames == 0 {
+            return None;
+        }
+        let n_frames = block.n_frames as f32;
+        let rms = block
+            .sum_squares
+            .iter()
+            .map(|sum| (sum / n_frames).sqrt())
+            .collect();
+        let peak = block.peak.clone();
+        let n_channels = block.peak.len();
+        block.reset(n_channels);
+        Some((peak, rms))
+    }
+}
+
+/// Computes [`MeterLevels`] with peak-hold from the levels of [`MeterControl`].
+pub struct Meter {
+    control: Arc<MeterControl>,
+    peak_hold: Vec<f32>,
+    held_at: Vec<Instant>,
+    /// Whether the last levels sent were silent, so that silence is only sent once.
+    silent: bool,
+}
+
+impl Meter {
+    pub fn new(control: Arc<MeterControl>) -> Self {
+        Self {
+            control,
+            peak_hold: Vec::new(),
+            held_at: Vec::new(),
+            silent: true,
+        }
+    }
+
+    /// Get the levels since the last call, or `None` if they have not changed,
+    /// i.e. still nothing is played.
+    pub fn update(&mut self) -> Option<MeterLevels> {
+        let now = Instant::now();
+        let (peak, rms) = match self.control.take() {
+            Some(levels) => levels,
+            None if self.silent => return None,
+            None => {
+                let n_channels = self.peak_hold.len();
+                (vec![0.; n_channels], vec![0.; n_channels])
+            }
+        };
+        self.silent = peak.iter().all(|&peak| peak <= 0.);
+
+        if self.peak_hold.len() != peak.len() {
+            self.peak_hold = vec![0.; peak.len()];
+            self.held_at = vec![now; peak.len()];
+        }
+        for ((hold, held_at), &peak) in self.peak_hold.iter_mut().zip(&mut self.held_at).zip(&peak)
+        {
+            if peak >= *hold || now.duration_since(*held_at) >= PEAK_HOLD_DURATION {
+                *hold = peak;
+                *held_at = now;
+            }
+        }
+
+        Some(MeterLevels {
+            peak,
+            rms,
+            peak_hold: self.peak_hold.clone(),
+        })
+    }
+}
+
+/// Wraps a source to measure its levels for [`MeterControl`] while the meter is subscribed.
+///
+/// The levels are handed over once per block and never block the playback.
+pub struct MeterSource<S> {
+    input: S,
+    control: Arc<MeterControl>,
+    n_channels: usize,
+    block: Block,
+    /// The channel of the next sample.
+    channel: usize,
+    active: bool,
+}
+
+impl<S> MeterSource<S>
+where
+    S: Source,
+    S::Item: Sample,
+{
+    pub fn new(input: S, control: Arc<MeterControl>) -> Self {
+        let n_channels = usize::from(input.channels().max(1));
+        let mut block = Block::default();
+        block.reset(n_channels);
+        Self {
+            active: control.is_active(),
+            input,
+            control,
+            n_channels,
+            block,
+            channel: 0,
+        }
+    }
+
+    fn flush(&mut self) {
+        // retry on the next block rather than waiting for the meter
+        if let Ok(mut block) = self.control.block.try_lock() {
+            block.merge(&self.block);
+            self.block.reset(self.n_channels);
+        }
+    }
+}
+
+impl<S> Iterator for MeterSource<S>
+where
+    S: Source,
+    S::Item: Sample,
+{
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        let sample = self.input.next()?.to_f32();
+        if !self.active {
+            // start measuring from a whole frame
+            if self.channel == 0 && self.control.is_active() {
+                self.active = true;
+                self.block.reset(self.n_channels);
+            } else {
+                self.channel = (self.channel + 1) % self.n_channels;
+                return Some(sample);
+            }
+        }
+
+        let peak = &mut self.block.peak[self.channel];
+        *peak = peak.max(sample.abs());
+        self.block.sum_squares[self.channel] += sample * sample;
+
+        self.channel += 1;
+        if self.channel == self.n_channels {
+            self.channel = 0;
+            self.block.n_frames += 1;
+            if self.block.n_frames >= BLOCK_FRAMES {
+                self.flush();
+                self.active = self.control.is_active();
+            }
+        }
+        Some(sample)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.input.size_hint()
+    }
+}
+
+impl<S> Source for MeterSource<S>
+where
+    S: Source,
+    S::Item: Sample,
+{
+    fn current_frame_len(&self) -> Option<usize> {
+        self.input.current_frame_len()
+    }
+
+    fn channels(&self) -> u16 {
+        self.input.channels()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.input.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.input.total_duration()
+    }
+
+    fn try_seek(&mut self, pos: Duration) -> Result<(), SeekError> {
+        self.input.try_seek(pos)
+    }
+}
@@ -0,0 +1,138 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::{sleep, spawn};
+use std::time::{Duration, Instant};
+
+use log::debug;
+use rodio::cpal::traits::HostTrait;
+use rodio::queue::SourcesQueueOutput;
+use rodio::{cpal, Device, DeviceTrait, OutputStream, Sink, Source};
+use serde::Serialize;
+
+use super::Error;
+
+/// The interval the null output consumes the samples at.
+const NULL_OUTPUT_INTERVAL: Duration = Duration::from_millis(10);
+
+/// An audio output device available to play on.
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OutputDevice {
+    pub name: String,
+    pub is_default: bool,
+}
+
+/// What the player outputs the audio to.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum OutputTarget {
+    /// The default output device of the system, which follows the changes of the default.
+    #[default]
+    Default,
+    /// The output device with the name, falling back to the default device if it is not available.
+    Device(String),
+    /// Discard the audio in real time, e.g. when no device is available or in tests.
+    Null,
+}
+
+impl OutputTarget {
+    /// The name of the device the output should be on, or `None` for the null output.
+    pub fn resolve(&self) -> Option<String> {
+        match self {
+            Self::Device(name) if find_device(name).is_some() => Some(name.clone()),
+            Self::Null => None,
+            _ => cpal::default_host()
+                .default_output_device()
+                .and_then(|device| device.name().ok()),
+        }
+    }
+}
+
+/// The output the sink of the player plays on.
+///
+/// It must live as long as the sink, in the thread it is created in.
+pub enum Output {
+    Device(OutputStream),
+    /// Stopped when dropped.
+    Null(Arc<AtomicBool>),
+}
+
+impl Output {
+    /// Open the output of `target` and create a sink playing on it.
+    pub fn open(target: &OutputTarget) -> Result<(Self, Sink), Error> {
+        let device = match target {
+            OutputTarget::Default => cpal::default_host()
+                .default_output_device()
+                .ok_or(Error::NoOutputDevice)?,
+            OutputTarget::Device(name) => {
+                find_device(name).ok_or_else(|| Error::OutputDeviceNotFound(name.clone()))?
+            }
+            OutputTarget::Null => return Ok(Self::open_null()),
+        };
+        debug!("open output device: {:?}", device.name());
+
+        let (stream, handle) = OutputStream::try_from_device(&device)?;
+        let sink = Sink::try_new(&handle)?;
+        Ok((Self::Device(stream), sink))
+    }
+
+    /// Open the null output, which consumes the samples in real time without playing them.
+    pub fn open_null() -> (Self, Sink) {
+        debug!("open null output");
+
+        let (sink, queue) = Sink::new_idle();
+        let stopped = Arc::new(AtomicBool::new(false));
+        let stopped_clone = stopped.clone();
+        spawn(move || run_null_output(queue, &stopped_clone));
+        (Self::Null(stopped), sink)
+    }
+}
+
+impl Drop for Output {
+    fn drop(&mut self) {
+        if let Self::Null(stopped) = self {
+            stopped.store(true, Ordering::Release);
+        }
+    }
+}
+
+/// List the output devices of the system.
+pub fn get_output_devices() -> Result<Vec<OutputDevice>, Error> {
+    let host = cpal::default_host();
+    let default_name = host
+        .default_output_device()
+        .and_then(|device| device.name().ok());
+
+    Ok(host
+        .output_devices()?
+        .filter_map(|device| device.name().ok())
+        .map(|name| OutputDevice {
+            is_default: default_name.as_ref() == Some(&name),
+            name,
+        })
+        .collect())
+}
+
+fn find_device(name: &str) -> Option<Device> {
+    cpal::default_host()
+        .output_devices()
+        .ok()?
+        .find(|device| device.name().is_ok_and(|device_name| device_name == name))
+}
+
+#[allow(clippy::cast_possible_truncation)]
+#[allow(clippy::cast_sign_loss)]
+fn run_null_output(mut queue: SourcesQueueOutput<f32>, stopped: &AtomicBool) {
+    let mut last_time = Instant::now();
+    while !stopped.load(Ordering::Acquire) {
+        sleep(NULL_OUTPUT_INTERVAL);
+
+        let now = Instant::now();
+        let n_frames = (now.duration_since(last_time).as_secs_f64()
+            * f64::from(queue.sample_rate()))
+        .round() as usize;
+        last_time = now;
+
+        let n_samples = n_frames * usize::from(queue.channels());
+        queue.by_ref().take(n_samples).for_each(drop);
+    }
+}
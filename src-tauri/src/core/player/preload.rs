@@ -0,0 +1,160 @@
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::thread::spawn;
+use std::time::Duration;
+
+use log::{debug, warn};
+use rodio::source::SeekError;
+use rodio::{Decoder, Source};
+
+use super::Error;
+
+/// The number of sources kept preloaded.
+const PRELOAD_LIMIT: usize = 4;
+/// The duration decoded ahead from the start of each preloaded source.
+const PRELOAD_DURATION: Duration = Duration::from_millis(500);
+
+/// A file to play, of which the beginning may have been decoded ahead.
+pub struct FileSource {
+    decoder: Decoder<BufReader<File>>,
+    /// The samples decoded ahead, played before the rest of the decoder.
+    head: Vec<i16>,
+    /// The position of the next sample in `head`.
+    head_pos: usize,
+    channels: u16,
+    sample_rate: u32,
+}
+
+impl FileSource {
+    pub fn open(path: &Path) -> Result<Self, Error> {
+        let file = BufReader::new(File::open(path)?);
+        let decoder = Decoder::new(file)?;
+        Ok(Self {
+            channels: decoder.channels(),
+            sample_rate: decoder.sample_rate(),
+            decoder,
+            head: Vec::new(),
+            head_pos: 0,
+        })
+    }
+
+    /// Decode the first `duration` of the file ahead.
+    #[allow(clippy::cast_possible_truncation)]
+    #[allow(clippy::cast_sign_loss)]
+    fn decode_ahead(&mut self, duration: Duration) {
+        let n_frames = (duration.as_secs_f64() * f64::from(self.sample_rate)).round() as usize;
+        let n_samples = n_frames * usize::from(self.channels);
+        self.head.extend(self.decoder.by_ref().take(n_samples));
+    }
+
+    fn head_remaining(&self) -> usize {
+        self.head.len() - self.head_pos
+    }
+}
+
+impl Iterator for FileSource {
+    type Item = i16;
+
+    fn next(&mut self) -> Option<i16> {
+        if let Some(&sample) = self.head.get(self.head_pos) {
+            self.head_pos += 1;
+            return Some(sample);
+        }
+        self.decoder.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let (lower, upper) = self.decoder.size_hint();
+        let remaining = self.head_remaining();
+        (lower + remaining, upper.map(|upper| upper + remaining))
+    }
+}
+
+impl Source for FileSource {
+    fn current_frame_len(&self) -> Option<usize> {
+        match self.head_remaining() {
+            0 => self.decoder.current_frame_len(),
+            remaining => Some(remaining),
+        }
+    }
+
+    fn channels(&self) -> u16 {
+        match self.head_remaining() {
+            0 => self.decoder.channels(),
+            _ => self.channels,
+        }
+    }
+
+    fn sample_rate(&self) -> u32 {
+        match self.head_remaining() {
+            0 => self.decoder.sample_rate(),
+            _ => self.sample_rate,
+        }
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.decoder.total_duration()
+    }
+
+    fn try_seek(&mut self, pos: Duration) -> Result<(), SeekError> {
+        self.decoder.try_seek(pos)?;
+        self.head.clear();
+        self.head_pos = 0;
+        Ok(())
+    }
+}
+
+/// Opens the sources likely to be played next in background,
+/// so that switching to them does not wait for the file to be opened and decoded.
+#[derive(Default)]
+pub struct Preloader {
+    /// The preloaded sources, the least recently preloaded first.
+    sources: Mutex<VecDeque<(PathBuf, FileSource)>>,
+}
+
+impl Preloader {
+    /// Preload the files in background, the most likely to be played first,
+    /// replacing the least recently preloaded sources.
+    pub fn preload(self: &Arc<Self>, paths: Vec<PathBuf>) {
+        let preloader = self.clone();
+        spawn(move || {
+            for path in paths.into_iter().take(PRELOAD_LIMIT) {
+                {
+                    let mut sources = preloader.sources.lock().unwrap();
+                    if let Some(index) = sources.iter().position(|(p, _)| *p == path) {
+                        // keep it preloaded for longer
+                        let source = sources.remove(index).unwrap();
+                        sources.push_back(source);
+                        continue;
+                    }
+                }
+
+                debug!("preload: {}", path.display());
+                let mut source = match FileSource::open(&path) {
+                    Ok(source) => source,
+                    Err(err) => {
+                        warn!("Failed to preload {}: {err}", path.display());
+                        continue;
+                    }
+                };
+                source.decode_ahead(PRELOAD_DURATION);
+
+                let mut sources = preloader.sources.lock().unwrap();
+                sources.push_back((path, source));
+                if sources.len() > PRELOAD_LIMIT {
+                    sources.pop_front();
+                }
+            }
+        });
+    }
+
+    /// Take the preloaded source of the file at `path`, if any.
+    pub fn take(&self, path: &Path) -> Option<FileSource> {
+        let mut sources = self.sources.lock().unwrap();
+        let index = sources.iter().position(|(p, _)| p == path)?;
+        sources.remove(index).map(|(_, source)| source)
+    }
+}
@@ -0,0 +1,237 @@
+use std::hash::{BuildHasher, RandomState};
+
+use serde::{Deserialize, Serialize};
+
+use crate::core::EntryId;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum RepeatMode {
+    #[default]
+    Off,
+    /// Repeat the current entry when advancing automatically.
+    One,
+    /// Start over from the first entry after the last one.
+    All,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QueueOptions {
+    pub shuffle: bool,
+    pub repeat: RepeatMode,
+    /// Play the next entry when the current entry ends.
+    pub auto_advance: bool,
+    /// The silence between the entries when advancing automatically, in seconds.
+    pub gap: f32,
+}
+
+impl Default for QueueOptions {
+    fn default() -> Self {
+        Self {
+            shuffle: false,
+            repeat: RepeatMode::Off,
+            auto_advance: true,
+            gap: 0.,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QueueState {
+    /// The entries in the order they were queued.
+    pub entries: Vec<EntryId>,
+    /// The index of the current entry in `entries`.
+    pub current: Option<usize>,
+    pub options: QueueOptions,
+}
+
+/// The entries to audition one after another.
+#[derive(Default)]
+pub struct Queue {
+    entries: Vec<EntryId>,
+    /// The order to play the entries in, as indices of `entries`.
+    order: Vec<usize>,
+    /// The position of the current entry in `order`.
+    pos: Option<usize>,
+    options: QueueOptions,
+}
+
+impl Queue {
+    /// Replace the entries of the queue, with the entry at `start` as the current entry.
+    ///
+    /// Returns the current entry.
+    pub fn set(&mut self, entries: Vec<EntryId>, start: Option<usize>) -> Option<EntryId> {
+        let start = start.filter(|&start| start < entries.len());
+        self.entries = entries;
+        self.reorder(start);
+        self.current()
+    }
+
+    /// Append the entries to the queue, shuffled among themselves if shuffling.
+    pub fn enqueue(&mut self, entries: &[EntryId]) {
+        let mut indices =
+            (self.entries.len()..self.entries.len() + entries.len()).collect::<Vec<_>>();
+        if self.options.shuffle {
+            shuffle(&mut indices);
+        }
+        self.entries.extend_from_slice(entries);
+        self.order.extend(indices);
+    }
+
+    /// Remove the entry at `index` of the entries.
+    ///
+    /// Returns `false` if the index is out of range.
+    pub fn remove(&mut self, index: usize) -> bool {
+        if index >= self.entries.len() {
+            return false;
+        }
+
+        self.entries.remove(index);
+        let removed_pos = self.order.iter().position(|&i| i == index).unwrap();
+        self.order.remove(removed_pos);
+        for i in &mut self.order {
+            if *i > index {
+                *i -= 1;
+            }
+        }
+        self.pos = match self.pos {
+            Some(pos) if pos == removed_pos => None,
+            Some(pos) if pos > removed_pos => Some(pos - 1),
+            pos => pos,
+        };
+        true
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.order.clear();
+        self.pos = None;
+    }
+
+    /// Make the entry at `index` of the entries the current entry.
+    pub fn jump(&mut self, index: usize) -> Option<EntryId> {
+        self.pos = Some(self.order.iter().position(|&i| i == index)?);
+        self.current()
+    }
+
+    /// Move to the next entry, wrapping around if repeating all.
+    ///
+    /// Returns `None` at the end of the queue.
+    pub fn next(&mut self) -> Option<EntryId> {
+        let next_pos = self.pos.map_or(0, |pos| pos + 1);
+        if next_pos < self.order.len() {
+            self.pos = Some(next_pos);
+        } else if self.options.repeat == RepeatMode::All && !self.order.is_empty() {
+            // a new order for each round
+            self.reorder(None);
+            self.pos = Some(0);
+        } else {
+            return None;
+        }
+        self.current()
+    }
+
+    /// Move to the previous entry, wrapping around if repeating all.
+    ///
+    /// Returns `None` at the start of the queue.
+    pub fn previous(&mut self) -> Option<EntryId> {
+        match self.pos {
+            Some(pos) if pos > 0 => self.pos = Some(pos - 1),
+            _ if self.options.repeat == RepeatMode::All && !self.order.is_empty() => {
+                self.pos = Some(self.order.len() - 1);
+            }
+            _ => return None,
+        }
+        self.current()
+    }
+
+    /// Move to the entry to play after the current entry ends,
+    /// or return `None` if the queue does not advance.
+    pub fn advance(&mut self) -> Option<EntryId> {
+        if !self.options.auto_advance {
+            return None;
+        }
+        match self.current() {
+            Some(entry_id) if self.options.repeat == RepeatMode::One => Some(entry_id),
+            _ => self.next(),
+        }
+    }
+
+    /// The entry [`Queue::advance`] would move to, without moving.
+    pub fn upcoming(&self) -> Option<EntryId> {
+        if !self.options.auto_advance {
+            return None;
+        }
+        match self.pos {
+            Some(_) if self.options.repeat == RepeatMode::One => self.current(),
+            Some(pos) if pos + 1 < self.order.len() => Some(self.entries[self.order[pos + 1]]),
+            None if !self.order.is_empty() => Some(self.entries[self.order[0]]),
+            // the order is reshuffled when repeating all
+            Some(_) if self.options.repeat == RepeatMode::All && !self.options.shuffle => {
+                Some(self.entries[self.order[0]])
+            }
+            _ => None,
+        }
+    }
+
+    pub fn options(&self) -> QueueOptions {
+        self.options
+    }
+
+    /// Set the options, keeping the current entry when the shuffle is toggled.
+    pub fn set_options(&mut self, options: QueueOptions) {
+        let reshuffle = options.shuffle != self.options.shuffle;
+        self.options = options;
+        if reshuffle {
+            self.reorder(self.current_index());
+        }
+    }
+
+    pub fn current(&self) -> Option<EntryId> {
+        self.current_index().map(|index| self.entries[index])
+    }
+
+    pub fn state(&self) -> QueueState {
+        QueueState {
+            entries: self.entries.clone(),
+            current: self.current_index(),
+            options: self.options,
+        }
+    }
+
+    fn current_index(&self) -> Option<usize> {
+        self.pos.map(|pos| self.order[pos])
+    }
+
+    /// Build the order of all entries, with the entry at `current` of the entries
+    /// as the current entry, and the first to play if shuffling.
+    fn reorder(&mut self, current: Option<usize>) {
+        self.order = (0..self.entries.len()).collect();
+        if !self.options.shuffle {
+            self.pos = current;
+            return;
+        }
+
+        shuffle(&mut self.order);
+        if let Some(current) = current {
+            self.order.retain(|&i| i != current);
+            self.order.insert(0, current);
+        }
+        self.pos = current.map(|_| 0);
+    }
+}
+
+/// Shuffle by Fisher-Yates with xorshift, seeded randomly by the standard library.
+#[allow(clippy::cast_possible_truncation)]
+fn shuffle<T>(items: &mut [T]) {
+    let mut state = RandomState::new().hash_one(items.len()) | 1;
+    for i in (1..items.len()).rev() {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        let j = (state % (i as u64 + 1)) as usize;
+        items.swap(i, j);
+    }
+}
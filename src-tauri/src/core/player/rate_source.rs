@@ -0,0 +1,297 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use rodio::source::SeekError;
+use rodio::{Sample, Source};
+use serde::{Deserialize, Serialize};
+
+use crate::core::resampler::Resampler;
+use crate::core::time_stretch::TimeStretcher;
+
+/// The number of frames read from the input at a time.
+const CHUNK_FRAMES: usize = 512;
+
+pub const PLAYBACK_RATE_RANGE: PlaybackRateRange = PlaybackRateRange {
+    min_speed: 0.25,
+    max_speed: 4.,
+    min_semitones: -24.,
+    max_semitones: 24.,
+    semitone_step: 1.,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "mode", rename_all = "camelCase")]
+pub enum PlaybackRate {
+    /// Speed and pitch changed together like a tape, i.e. by `12 * log2(speed)` semitones.
+    Varispeed { speed: f32 },
+    /// Speed and pitch changed independently, by time-stretching and resampling.
+    Independent { speed: f32, semitones: f32 },
+}
+
+impl Default for PlaybackRate {
+    fn default() -> Self {
+        Self::Varispeed { speed: 1. }
+    }
+}
+
+impl PlaybackRate {
+    pub fn is_valid(self) -> bool {
+        let range = PLAYBACK_RATE_RANGE;
+        let (speed, semitones) = match self {
+            Self::Varispeed { speed } => (speed, 0.),
+            Self::Independent { speed, semitones } => (speed, semitones),
+        };
+        (range.min_speed..=range.max_speed).contains(&speed)
+            && (range.min_semitones..=range.max_semitones).contains(&semitones)
+    }
+
+    /// The tempo of the time-stretching and the ratio of the resampling,
+    /// both as the number of input frames per output frame.
+    fn factors(self) -> (f64, f64) {
+        match self {
+            Self::Varispeed { speed } => (1., f64::from(speed)),
+            Self::Independent { speed, semitones } => {
+                let ratio = 2f64.powf(f64::from(semitones) / 12.);
+                (f64::from(speed) / ratio, ratio)
+            }
+        }
+    }
+}
+
+/// The ranges of [`PlaybackRate`] supported, for the frontend.
+#[derive(Clone, Copy, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PlaybackRateRange {
+    pub min_speed: f32,
+    pub max_speed: f32,
+    pub min_semitones: f32,
+    pub max_semitones: f32,
+    pub semitone_step: f32,
+}
+
+/// The playback rate shared between the player and [`RateSource`].
+#[derive(Default)]
+pub struct RateControl {
+    rate: Mutex<PlaybackRate>,
+    /// Incremented whenever the rate changes, so that the source picks up the change.
+    version: AtomicU64,
+    /// The number of frames read from the input but not played yet.
+    latency: AtomicU64,
+}
+
+impl RateControl {
+    pub fn get(&self) -> PlaybackRate {
+        *self.rate.lock().unwrap()
+    }
+
+    pub fn set(&self, rate: PlaybackRate) {
+        *self.rate.lock().unwrap() = rate;
+        self.version.fetch_add(1, Ordering::Release);
+    }
+
+    /// The number of frames of the source read but not played yet,
+    /// buffered by the time-stretching and the resampling.
+    pub fn get_latency(&self) -> u64 {
+        self.latency.load(Ordering::Acquire)
+    }
+}
+
+/// Wraps a source to play it at the rate of [`RateControl`].
+///
+/// The source is passed through untouched at the original rate.
+pub struct RateSource<S> {
+    input: S,
+    control: Arc<RateControl>,
+    version: u64,
+    n_channels: usize,
+    stretcher: TimeStretcher,
+    resampler: Resampler,
+    /// Whether the time-stretching is applied, i.e. the tempo is not 1.
+    stretch: bool,
+    /// Whether the source is played at the original rate.
+    bypass: bool,
+    /// The number of input frames per output frame.
+    step: f64,
+    /// The number of input frames read but not output yet.
+    buffered: f64,
+    input_buffer: Vec<f32>,
+    stretched: Vec<f32>,
+    output: Vec<f32>,
+    /// The position of the next sample in `output`.
+    output_pos: usize,
+}
+
+impl<S> RateSource<S>
+where
+    S: Source,
+    S::Item: Sample,
+{
+    pub fn new(input: S, control: Arc<RateControl>) -> Self {
+        let n_channels = usize::from(input.channels());
+        let sample_rate = input.sample_rate();
+
+        let mut source = Self {
+            input,
+            control,
+            version: 0,
+            n_channels,
+            stretcher: TimeStretcher::new(n_channels, sample_rate, 1.),
+            resampler: Resampler::new(n_channels, sample_rate, sample_rate),
+            stretch: false,
+            bypass: true,
+            step: 1.,
+            buffered: 0.,
+            input_buffer: Vec::new(),
+            stretched: Vec::new(),
+            output: Vec::new(),
+            output_pos: 0,
+        };
+        source.update_rate();
+        source
+    }
+
+    /// Read the rate from the control.
+    #[allow(clippy::float_cmp)]
+    fn update_rate(&mut self) {
+        self.version = self.control.version.load(Ordering::Acquire);
+        let (tempo, ratio) = self.control.get().factors();
+
+        let stretch = tempo != 1.;
+        if stretch != self.stretch {
+            self.stretcher.reset();
+        }
+        self.stretch = stretch;
+        self.stretcher.set_tempo(tempo);
+        self.resampler.set_step(ratio);
+        self.step = tempo * ratio;
+
+        let bypass = !stretch && ratio == 1.;
+        if bypass && !self.bypass {
+            // the buffered samples are skipped
+            self.stretcher.reset();
+            self.resampler.reset();
+            self.set_buffered(0.);
+        }
+        self.bypass = bypass;
+    }
+
+    /// Set the number of input frames buffered, reported as the latency.
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    fn set_buffered(&mut self, buffered: f64) {
+        self.buffered = buffered.max(0.);
+        self.control
+            .latency
+            .store(self.buffered.round() as u64, Ordering::Release);
+    }
+
+    /// Process the next chunk of the input into `output`.
+    ///
+    /// Returns `false` at the end of the input.
+    #[allow(clippy::cast_precision_loss)]
+    fn fill_output(&mut self) -> bool {
+        self.output.clear();
+        self.output_pos = 0;
+
+        while self.output.is_empty() {
+            self.input_buffer.clear();
+            self.input_buffer.extend(
+                self.input
+                    .by_ref()
+                    .take(CHUNK_FRAMES * self.n_channels)
+                    .map(Sample::to_f32),
+            );
+            let ended = self.input_buffer.len() < CHUNK_FRAMES * self.n_channels;
+            self.set_buffered(self.buffered + (self.input_buffer.len() / self.n_channels) as f64);
+
+            self.stretched.clear();
+            if self.stretch {
+                self.stretcher
+                    .process(&self.input_buffer, &mut self.stretched);
+                if ended {
+                    self.stretcher.flush(&mut self.stretched);
+                }
+            } else {
+                self.stretched.extend_from_slice(&self.input_buffer);
+            }
+
+            self.resampler.process(&self.stretched, &mut self.output);
+            if ended {
+                self.resampler.flush(&mut self.output);
+                return !self.output.is_empty();
+            }
+        }
+
+        true
+    }
+}
+
+impl<S> Iterator for RateSource<S>
+where
+    S: Source,
+    S::Item: Sample,
+{
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        if self.output_pos == self.output.len() {
+            if self.control.version.load(Ordering::Acquire) != self.version {
+                self.update_rate();
+            }
+
+            if self.bypass {
+                return self.input.next().map(Sample::to_f32);
+            }
+            if !self.fill_output() {
+                return None;
+            }
+        }
+
+        let sample = self.output[self.output_pos];
+        self.output_pos += 1;
+        if self.output_pos.is_multiple_of(self.n_channels) {
+            self.set_buffered(self.buffered - self.step);
+        }
+        Some(sample)
+    }
+}
+
+impl<S> Source for RateSource<S>
+where
+    S: Source,
+    S::Item: Sample,
+{
+    fn current_frame_len(&self) -> Option<usize> {
+        if self.bypass {
+            self.input.current_frame_len()
+        } else {
+            None
+        }
+    }
+
+    fn channels(&self) -> u16 {
+        self.input.channels()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.input.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        if self.bypass {
+            self.input.total_duration()
+        } else {
+            None
+        }
+    }
+
+    fn try_seek(&mut self, pos: Duration) -> Result<(), SeekError> {
+        self.input.try_seek(pos)?;
+        self.stretcher.reset();
+        self.resampler.reset();
+        self.output.clear();
+        self.output_pos = 0;
+        self.set_buffered(0.);
+        Ok(())
+    }
+}
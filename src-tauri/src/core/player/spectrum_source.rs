@@ -0,0 +1,347 @@
+use std::collections::VecDeque;
+use std::f32::consts::PI;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use rodio::source::SeekError;
+use rodio::{Sample, Source};
+use rustfft::num_complex::Complex;
+use rustfft::{Fft, FftPlanner};
+use serde::{Deserialize, Serialize};
+
+/// The number of frames downmixed before they are handed to [`SpectrumControl`].
+const BLOCK_FRAMES: usize = 256;
+pub const MIN_FFT_SIZE: usize = 256;
+pub const MAX_FFT_SIZE: usize = 16384;
+/// The level of the bins without any energy, in dBFS.
+pub const MIN_DB: f32 = -120.;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Window {
+    Rectangular,
+    #[default]
+    Hann,
+    Hamming,
+    Blackman,
+    BlackmanHarris,
+}
+
+impl Window {
+    /// The coefficients of the window of `size` samples.
+    #[allow(clippy::cast_precision_loss)]
+    pub fn coefficients(self, size: usize) -> Vec<f32> {
+        let cosine_sum = |a: &[f32]| {
+            (0..size)
+                .map(|i| {
+                    let phase = 2. * PI * i as f32 / size as f32;
+                    a.iter()
+                        .enumerate()
+                        .map(|(k, a)| {
+                            let sign = if k % 2 == 0 { 1. } else { -1. };
+                            sign * a * (phase * k as f32).cos()
+                        })
+                        .sum()
+                })
+                .collect()
+        };
+        match self {
+            Self::Rectangular => vec![1.; size],
+            Self::Hann => cosine_sum(&[0.5, 0.5]),
+            Self::Hamming => cosine_sum(&[0.54, 0.46]),
+            Self::Blackman => cosine_sum(&[0.42, 0.5, 0.08]),
+            Self::BlackmanHarris => cosine_sum(&[0.358_75, 0.488_29, 0.141_28, 0.011_68]),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SpectrumOptions {
+    /// The number of samples of each FFT, a power of two
+    /// between [`MIN_FFT_SIZE`] and [`MAX_FFT_SIZE`].
+    pub fft_size: usize,
+    pub window: Window,
+}
+
+impl Default for SpectrumOptions {
+    fn default() -> Self {
+        Self {
+            fft_size: 4096,
+            window: Window::default(),
+        }
+    }
+}
+
+impl SpectrumOptions {
+    pub fn is_valid(self) -> bool {
+        self.fft_size.is_power_of_two() && (MIN_FFT_SIZE..=MAX_FFT_SIZE).contains(&self.fft_size)
+    }
+}
+
+/// The spectrum of the latest samples played.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Spectrum {
+    pub sample_rate: u32,
+    /// The level of each bin from DC to Nyquist in dBFS, i.e. `fft_size / 2 + 1` bins,
+    /// where a full scale sine at the frequency of a bin is 0 dBFS.
+    pub bins: Vec<f32>,
+}
+
+/// The samples downmixed to mono by [`SpectrumSource`] and not yet analyzed.
+#[derive(Default)]
+struct History {
+    /// The latest samples, at most [`MAX_FFT_SIZE`].
+    samples: VecDeque<f32>,
+    sample_rate: u32,
+    /// The number of samples pushed since the last analysis.
+    n_new: usize,
+}
+
+/// The samples and the options shared between [`SpectrumSource`] and the analyzer of the player.
+#[derive(Default)]
+pub struct SpectrumControl {
+    history: Mutex<History>,
+    options: Mutex<SpectrumOptions>,
+    /// Whether the samples are collected, i.e. anyone is subscribed.
+    active: AtomicBool,
+    /// Incremented whenever the spectrum is subscribed or unsubscribed,
+    /// so that a replaced subscription stops.
+    generation: AtomicU64,
+}
+
+impl SpectrumControl {
+    /// Start a new subscription, stopping the previous one, and return its generation.
+    pub fn subscribe(&self) -> u64 {
+        *self.history.lock().unwrap() = History::default();
+        self.active.store(true, Ordering::Release);
+        self.generation.fetch_add(1, Ordering::AcqRel) + 1
+    }
+
+    pub fn unsubscribe(&self) {
+        self.active.store(false, Ordering::Release);
+        self.generation.fetch_add(1, Ordering::AcqRel);
+    }
+
+    pub fn is_subscribed(&self, generation: u64) -> bool {
+        self.generation.load(Ordering::Acquire) == generation
+    }
+
+    pub fn get_options(&self) -> SpectrumOptions {
+        *self.options.lock().unwrap()
+    }
+
+    pub fn set_options(&self, options: SpectrumOptions) {
+        *self.options.lock().unwrap() = options;
+    }
+
+    fn is_active(&self) -> bool {
+        self.active.load(Ordering::Acquire)
+    }
+}
+
+/// Computes the [`Spectrum`] of the samples of [`SpectrumControl`].
+pub struct Analyzer {
+    control: Arc<SpectrumControl>,
+    planner: FftPlanner<f32>,
+    options: Option<SpectrumOptions>,
+    fft: Option<Arc<dyn Fft<f32>>>,
+    /// The window coefficients, scaled so that a full scale sine is 0 dBFS.
+    window: Vec<f32>,
+    buffer: Vec<Complex<f32>>,
+    scratch: Vec<Complex<f32>>,
+    /// Whether the last spectrum was computed from silence, so that silence is only sent once.
+    silent: bool,
+}
+
+impl Analyzer {
+    pub fn new(control: Arc<SpectrumControl>) -> Self {
+        Self {
+            control,
+            planner: FftPlanner::new(),
+            options: None,
+            fft: None,
+            window: Vec::new(),
+            buffer: Vec::new(),
+            scratch: Vec::new(),
+            silent: true,
+        }
+    }
+
+    /// Plan the FFT and compute the window for the current options.
+    fn update_options(&mut self, options: SpectrumOptions) {
+        let size = options.fft_size;
+        let fft = self.planner.plan_fft_forward(size);
+        self.scratch = vec![Complex::default(); fft.get_inplace_scratch_len()];
+        self.fft = Some(fft);
+
+        self.window = options.window.coefficients(size);
+        let gain = 2. / self.window.iter().sum::<f32>();
+        self.window.iter_mut().for_each(|w| *w *= gain);
+        self.buffer = vec![Complex::default(); size];
+        self.options = Some(options);
+    }
+
+    /// Compute the spectrum of the latest samples, or return `None` if it has not changed,
+    /// i.e. still nothing is played.
+    pub fn update(&mut self) -> Option<Spectrum> {
+        let options = self.control.get_options();
+        if self.options != Some(options) {
+            self.update_options(options);
+        }
+        let size = options.fft_size;
+
+        let (sample_rate, silent) = {
+            let mut history = self.control.history.lock().unwrap();
+            if history.n_new == 0 && self.silent {
+                return None;
+            }
+            if history.n_new == 0 {
+                // nothing played since, drop to silence at once
+                history.samples.clear();
+            }
+            history.n_new = 0;
+
+            // zero padded at the start if not enough samples
+            let n_padding = size.saturating_sub(history.samples.len());
+            let skip = history.samples.len().saturating_sub(size);
+            for (i, value) in self.buffer.iter_mut().enumerate() {
+                *value = if i < n_padding {
+                    Complex::default()
+                } else {
+                    let sample = history.samples[skip + i - n_padding];
+                    Complex::new(sample * self.window[i], 0.)
+                };
+            }
+            (history.sample_rate, history.samples.is_empty())
+        };
+
+        self.silent = silent;
+        self.fft
+            .as_ref()
+            .unwrap()
+            .process_with_scratch(&mut self.buffer, &mut self.scratch);
+
+        let bins = self.buffer[..=size / 2]
+            .iter()
+            .map(|value| (20. * value.norm().log10()).max(MIN_DB))
+            .collect();
+        Some(Spectrum { sample_rate, bins })
+    }
+}
+
+/// Wraps a source to collect its samples downmixed to mono for [`SpectrumControl`],
+/// while the spectrum is subscribed.
+///
+/// The samples are handed over once per block and never block the playback.
+pub struct SpectrumSource<S> {
+    input: S,
+    control: Arc<SpectrumControl>,
+    n_channels: usize,
+    sample_rate: u32,
+    /// The downmixed samples not yet handed over.
+    block: Vec<f32>,
+    /// The sum of the samples of the current frame.
+    frame_sum: f32,
+    /// The channel of the next sample.
+    channel: usize,
+    active: bool,
+}
+
+impl<S> SpectrumSource<S>
+where
+    S: Source,
+    S::Item: Sample,
+{
+    pub fn new(input: S, control: Arc<SpectrumControl>) -> Self {
+        Self {
+            n_channels: usize::from(input.channels().max(1)),
+            sample_rate: input.sample_rate(),
+            active: control.is_active(),
+            input,
+            control,
+            block: Vec::with_capacity(BLOCK_FRAMES),
+            frame_sum: 0.,
+            channel: 0,
+        }
+    }
+
+    fn flush(&mut self) {
+        // retry on the next block rather than waiting for the analyzer
+        let Ok(mut history) = self.control.history.try_lock() else {
+            return;
+        };
+        history.sample_rate = self.sample_rate;
+        history.n_new += self.block.len();
+        history.samples.extend(self.block.drain(..));
+        let excess = history.samples.len().saturating_sub(MAX_FFT_SIZE);
+        history.samples.drain(..excess);
+    }
+}
+
+impl<S> Iterator for SpectrumSource<S>
+where
+    S: Source,
+    S::Item: Sample,
+{
+    type Item = f32;
+
+    #[allow(clippy::cast_precision_loss)]
+    fn next(&mut self) -> Option<f32> {
+        let sample = self.input.next()?.to_f32();
+        if !self.active {
+            // start collecting from a whole frame
+            if self.channel == 0 && self.control.is_active() {
+                self.active = true;
+                self.block.clear();
+            } else {
+                self.channel = (self.channel + 1) % self.n_channels;
+                return Some(sample);
+            }
+        }
+
+        self.frame_sum += sample;
+        self.channel += 1;
+        if self.channel == self.n_channels {
+            self.block.push(self.frame_sum / self.n_channels as f32);
+            self.frame_sum = 0.;
+            self.channel = 0;
+            if self.block.len() >= BLOCK_FRAMES {
+                self.flush();
+                self.active = self.control.is_active();
+            }
+        }
+        Some(sample)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.input.size_hint()
+    }
+}
+
+impl<S> Source for SpectrumSource<S>
+where
+    S: Source,
+    S::Item: Sample,
+{
+    fn current_frame_len(&self) -> Option<usize> {
+        self.input.current_frame_len()
+    }
+
+    fn channels(&self) -> u16 {
+        self.input.channels()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.input.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.input.total_duration()
+    }
+
+    fn try_seek(&mut self, pos: Duration) -> Result<(), SeekError> {
+        self.input.try_seek(pos)
+    }
+}
@@ -0,0 +1,1010 @@
+use super::channel_source::{ChannelControl, ChannelSource};
+use super::fade_source::{FadeControl, FadeSource};
+use super::loop_source::{LoopControl, LoopSource};
+use super::meter_source::{Meter, MeterControl, MeterSource};
+use super::preload::{FileSource, Preloader};
+use super::queue::Queue;
+use super::rate_source::{RateControl, RateSource};
+use super::spectrum_source::{Analyzer, SpectrumControl, SpectrumSource, MIN_DB};
+use super::{
+    ChannelOptions, Error, FadeOptions, LoopRegion, OutputTarget, PlaybackRate, Player,
+    PlayerEmitter, PlayerPosition, PlayerState, QueueOptions, QueueState, RepeatMode,
+};
+use crate::core::silence::{Silence, SilenceOptions};
+use crate::core::wav::{SampleFormat, WavSpec, WavWriter};
+use crate::core::EntryId;
+
+use std::f32::consts::PI;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+use rodio::buffer::SamplesBuffer;
+use rodio::Source;
+use test_log::test;
+use testdir::testdir;
+
+const EMITTER_TIMEOUT: Duration = Duration::from_secs(3);
+const SAMPLE_RATE: u32 = 48000;
+
+#[derive(Default)]
+struct TestEmitter {
+    condvar: Condvar,
+    events: Mutex<Events>,
+}
+
+#[derive(Default)]
+struct Events {
+    states: Vec<PlayerState>,
+    positions: Vec<PlayerPosition>,
+    errors: Vec<String>,
+    queues: Vec<QueueState>,
+    advanced: Vec<EntryId>,
+}
+
+impl TestEmitter {
+    /// Wait until the queue has advanced `n` times.
+    fn wait_for_advanced(&self, n: usize) -> Vec<EntryId> {
+        let events = self.events.lock().unwrap();
+        let (events, _) = self
+            .condvar
+            .wait_timeout_while(events, EMITTER_TIMEOUT, |events| events.advanced.len() < n)
+            .unwrap();
+        events.advanced.clone()
+    }
+
+    /// Wait until a state matching `predicate` is emitted.
+    fn wait_for_state(&self, predicate: impl Fn(&PlayerState) -> bool) -> bool {
+        let events = self.events.lock().unwrap();
+        let (events, _) = self
+            .condvar
+            .wait_timeout_while(events, EMITTER_TIMEOUT, |events| {
+                !events.states.iter().any(&predicate)
+            })
+            .unwrap();
+        events.states.iter().any(predicate)
+    }
+}
+
+impl PlayerEmitter for TestEmitter {
+    fn on_player_state_updated(&self, state: PlayerState) {
+        self.events.lock().unwrap().states.push(state);
+        self.condvar.notify_all();
+    }
+
+    fn on_player_position_updated(&self, position: PlayerPosition) {
+        self.events.lock().unwrap().positions.push(position);
+        self.condvar.notify_all();
+    }
+
+    fn on_player_error(&self, error: &Error) {
+        self.events.lock().unwrap().errors.push(error.to_string());
+        self.condvar.notify_all();
+    }
+
+    fn on_player_queue_updated(&self, state: QueueState) {
+        self.events.lock().unwrap().queues.push(state);
+        self.condvar.notify_all();
+    }
+
+    fn on_queue_advanced(&self, entry_id: EntryId) {
+        self.events.lock().unwrap().advanced.push(entry_id);
+        self.condvar.notify_all();
+    }
+}
+
+fn write_sine(path: &Path, duration: f32) -> PathBuf {
+    let mut writer = WavWriter::create(
+        path,
+        WavSpec {
+            n_channels: 1,
+            sample_rate: SAMPLE_RATE,
+            sample_format: SampleFormat::Int16,
+        },
+    )
+    .unwrap();
+    #[allow(clippy::cast_possible_truncation)]
+    #[allow(clippy::cast_sign_loss)]
+    let n_frames = (duration * SAMPLE_RATE as f32) as usize;
+    #[allow(clippy::cast_precision_loss)]
+    let samples = (0..n_frames)
+        .map(|i| 0.5 * (2. * PI * 440. * i as f32 / SAMPLE_RATE as f32).sin())
+        .collect::<Vec<_>>();
+    writer.write_samples(&samples).unwrap();
+    writer.finalize().unwrap();
+    path.to_path_buf()
+}
+
+#[test]
+fn test_null_output() {
+    let dir = testdir!();
+    let path = write_sine(&dir.join("sine.wav"), 0.5);
+
+    let emitter = Arc::new(TestEmitter::default());
+    let mut player = Player::new(emitter.clone());
+    player.set_output_target(OutputTarget::Null);
+    player.run();
+
+    player.set_source(path, None, None).unwrap();
+    player.play(false).unwrap();
+    assert!(emitter.wait_for_state(|state| state.playing));
+
+    // ends in real time, with the position pushed while playing
+    assert!(emitter.wait_for_state(|state| !state.playing));
+    let events = emitter.events.lock().unwrap();
+    assert!(events.errors.is_empty());
+    assert!(!events.positions.is_empty());
+    assert!(events
+        .positions
+        .iter()
+        .all(|position| position.sample_rate == SAMPLE_RATE));
+    assert!(events
+        .positions
+        .windows(2)
+        .all(|positions| positions[0].frames <= positions[1].frames));
+}
+
+#[test]
+fn test_set_silence_options() {
+    let mut player = Player::new(Arc::new(TestEmitter::default()));
+    let options = SilenceOptions {
+        threshold: -40.,
+        min_duration: 0.5,
+    };
+    player.set_silence_options(options).unwrap();
+    assert_eq!(player.get_silence_options(), options);
+
+    for (threshold, min_duration) in [
+        (6., 0.),
+        (f32::NAN, 0.),
+        (f32::NEG_INFINITY, 0.),
+        (-60., -1.),
+        (-60., f32::NAN),
+        (-60., f32::INFINITY),
+    ] {
+        let invalid = SilenceOptions {
+            threshold,
+            min_duration,
+        };
+        assert!(matches!(
+            player.set_silence_options(invalid),
+            Err(Error::InvalidSilenceOptions(_))
+        ));
+    }
+    assert_eq!(player.get_silence_options(), options);
+}
+
+#[test]
+fn test_skip_silence_detected_while_playing() {
+    let dir = testdir!();
+    let path = dir.join("silence.wav");
+    let mut writer = WavWriter::create(
+        &path,
+        WavSpec {
+            n_channels: 1,
+            sample_rate: SAMPLE_RATE,
+            sample_format: SampleFormat::Int16,
+        },
+    )
+    .unwrap();
+    // 0.5 s of silence, 1 s of sine, and 0.5 s of silence
+    let n_frames = SAMPLE_RATE as usize / 2;
+    #[allow(clippy::cast_precision_loss)]
+    let samples = (0..n_frames * 4)
+        .map(|i| {
+            if (n_frames..n_frames * 3).contains(&i) {
+                0.5 * (2. * PI * 440. * i as f32 / SAMPLE_RATE as f32).sin()
+            } else {
+                0.
+            }
+        })
+        .collect::<Vec<_>>();
+    writer.write_samples(&samples).unwrap();
+    writer.finalize().unwrap();
+
+    let emitter = Arc::new(TestEmitter::default());
+    let mut player = Player::new(emitter.clone());
+    player.set_output_target(OutputTarget::Null);
+    player.run();
+
+    // played before the silence has been detected
+    player.set_source(path.clone(), None, None).unwrap();
+    player.play(true).unwrap();
+    assert!(player.get_pos() < 0.5);
+
+    player.set_silence(
+        &path,
+        Silence {
+            head: 0.5,
+            tail: 1.5,
+            options: player.get_silence_options(),
+        },
+    );
+    assert!(player.get_pos() >= 0.5);
+    assert!(player.is_playing());
+
+    // stops at the trailing silence
+    sleep(Duration::from_millis(1250));
+    assert!(!player.is_playing());
+}
+
+#[test]
+fn test_output_device_not_found() {
+    let dir = testdir!();
+    let path = write_sine(&dir.join("sine.wav"), 0.5);
+
+    let emitter = Arc::new(TestEmitter::default());
+    let mut player = Player::new(emitter.clone());
+    player.set_output_target(OutputTarget::Device("not a device".to_string()));
+    player.run();
+
+    // falls back to the default device, or the null output if there is none
+    {
+        let events = emitter.events.lock().unwrap();
+        assert!(events.errors[0].contains("not a device"));
+    }
+    player.set_source(path, None, None).unwrap();
+    player.play(false).unwrap();
+    assert!(emitter.wait_for_state(|state| state.playing));
+}
+
+#[test]
+fn test_switch_output() {
+    let dir = testdir!();
+    let path = write_sine(&dir.join("sine.wav"), 2.);
+
+    let emitter = Arc::new(TestEmitter::default());
+    let mut player = Player::new(emitter.clone());
+    player.set_output_target(OutputTarget::Null);
+    player.run();
+
+    player.set_source(path, None, None).unwrap();
+    player.seek(Duration::from_secs(1)).unwrap();
+    player.pause();
+
+    // the playback continues on the new output from the same position
+    player.set_output_target(OutputTarget::Device("not a device".to_string()));
+    std::thread::sleep(Duration::from_millis(500));
+    assert!(player.get_pos() >= 1.);
+    assert!(!player.is_playing());
+    assert!(emitter.events.lock().unwrap().errors[0].contains("not a device"));
+}
+
+#[test]
+#[allow(clippy::cast_precision_loss)]
+fn test_fade_source() {
+    const FADE_FRAMES: usize = 48;
+
+    let control = Arc::new(FadeControl::default());
+    control.set_options(FadeOptions {
+        fade_in: FADE_FRAMES as f32 / SAMPLE_RATE as f32,
+        fade_out: FADE_FRAMES as f32 / SAMPLE_RATE as f32,
+    });
+    assert!(!FadeOptions {
+        fade_in: 2.,
+        fade_out: 0.
+    }
+    .is_valid());
+
+    let input = SamplesBuffer::new(2, SAMPLE_RATE, vec![1.; SAMPLE_RATE as usize * 2]);
+    let mut source = FadeSource::new(input, control.clone());
+
+    // fades in from silence, with the same gain for both channels of a frame
+    let head = source.by_ref().take(FADE_FRAMES * 4).collect::<Vec<_>>();
+    for frame in head.chunks_exact(2) {
+        assert!((frame[0] - frame[1]).abs() < 1e-6);
+    }
+    assert!(head[0] < 0.1);
+    assert!(head.windows(2).all(|pair| pair[1] >= pair[0]));
+    assert!((head[FADE_FRAMES * 2] - 1.).abs() < 1e-6);
+
+    // fades out and stays silent
+    control.fade_out();
+    let tail = source.by_ref().take(FADE_FRAMES * 4).collect::<Vec<_>>();
+    assert!(tail.windows(2).all(|pair| pair[1] <= pair[0]));
+    assert!(tail[FADE_FRAMES * 2..]
+        .iter()
+        .all(|sample| sample.abs() < f32::EPSILON));
+
+    control.fade_in();
+    let resumed = source.by_ref().take(FADE_FRAMES * 4).collect::<Vec<_>>();
+    assert!(resumed[0] < 0.1);
+    assert!((resumed[FADE_FRAMES * 4 - 1] - 1.).abs() < 1e-6);
+
+    // the options changed while playing apply to the next fade
+    control.set_options(FadeOptions {
+        fade_in: FADE_FRAMES as f32 / SAMPLE_RATE as f32,
+        fade_out: (FADE_FRAMES * 2) as f32 / SAMPLE_RATE as f32,
+    });
+    control.fade_out();
+    let tail = source.by_ref().take(FADE_FRAMES * 2).collect::<Vec<_>>();
+    assert!((tail[FADE_FRAMES * 2 - 1] - 0.5).abs() < 0.05, "{tail:?}");
+}
+
+#[test]
+fn test_fade_out_and_wait() {
+    let control = Arc::new(FadeControl::default());
+    control.set_options(FadeOptions {
+        fade_in: 0.,
+        fade_out: 0.5,
+    });
+
+    // woken up once the source pulled on another thread is silent, before the timeout
+    let input = SamplesBuffer::new(2, SAMPLE_RATE, vec![1.; SAMPLE_RATE as usize * 4]);
+    let source = FadeSource::new(input, control.clone());
+    let output = std::thread::spawn(move || {
+        sleep(Duration::from_millis(50));
+        source.collect::<Vec<_>>()
+    });
+    let start = Instant::now();
+    control.fade_out_and_wait();
+    assert!(start.elapsed() < Duration::from_millis(900));
+    let samples = output.join().unwrap();
+    assert!(samples[SAMPLE_RATE as usize * 2..]
+        .iter()
+        .all(|sample| sample.abs() < f32::EPSILON));
+
+    // gives up after twice the fade duration if the source is not pulled
+    control.set_options(FadeOptions {
+        fade_in: 0.,
+        fade_out: 0.1,
+    });
+    control.fade_in();
+    let start = Instant::now();
+    control.fade_out_and_wait();
+    assert!(start.elapsed() >= Duration::from_millis(200));
+}
+
+#[test]
+fn test_fade_source_gain() {
+    let control = Arc::new(FadeControl::default());
+    control.set_options(FadeOptions {
+        fade_in: 0.,
+        fade_out: 0.,
+    });
+    control.set_gain(0.5);
+
+    let input = SamplesBuffer::new(1, SAMPLE_RATE, vec![1.; SAMPLE_RATE as usize]);
+    let mut source = FadeSource::new(input, control.clone());
+    let head = source.by_ref().take(480).collect::<Vec<_>>();
+    assert!((head[1] - 0.5).abs() < 1e-6);
+
+    // ramps to the new gain instead of jumping
+    control.set_gain(1.);
+    let ramp = source.by_ref().take(4800).collect::<Vec<_>>();
+    assert!(ramp[0] < 0.51);
+    assert!(ramp.windows(2).all(|pair| pair[1] >= pair[0]));
+    assert!(ramp.windows(2).all(|pair| pair[1] - pair[0] < 0.001));
+    assert!((ramp[4799] - 1.).abs() < 1e-6);
+}
+
+#[test]
+fn test_rate_source_latency() {
+    let control = Arc::new(RateControl::default());
+    let input = SamplesBuffer::new(1, SAMPLE_RATE, vec![0.5; SAMPLE_RATE as usize]);
+    let mut source = RateSource::new(input, control.clone());
+
+    // nothing is buffered at the original rate
+    source.by_ref().take(100).for_each(drop);
+    assert_eq!(control.get_latency(), 0);
+
+    // the frames read ahead of the frames output, two input frames per output frame
+    control.set(PlaybackRate::Varispeed { speed: 2. });
+    source.by_ref().take(100).for_each(drop);
+    assert_eq!(control.get_latency(), 512 - 200);
+}
+
+/// The number of frames of an analysis frame of the time-stretching at the original speed,
+/// of which a few may pad the end of a source.
+const TIME_STRETCH_PADDING: usize = SAMPLE_RATE as usize / 25;
+
+/// A stereo sine of one second, the right channel of opposite phase.
+#[allow(clippy::cast_precision_loss)]
+fn sine_buffer(freq: f32) -> SamplesBuffer<f32> {
+    let samples = (0..SAMPLE_RATE)
+        .flat_map(|i| {
+            let x = 0.5 * (2. * PI * freq * i as f32 / SAMPLE_RATE as f32).sin();
+            [x, -x]
+        })
+        .collect::<Vec<_>>();
+    SamplesBuffer::new(2, SAMPLE_RATE, samples)
+}
+
+/// Estimate the frequency of the left channel from its zero crossings, away from the edges.
+#[allow(clippy::cast_precision_loss)]
+fn estimate_freq(samples: &[f32]) -> f32 {
+    let left = samples.iter().step_by(2).copied().collect::<Vec<_>>();
+    let middle = &left[left.len() / 4..left.len() * 3 / 4];
+    let n_crossings = middle
+        .windows(2)
+        .filter(|pair| (pair[0] < 0.) != (pair[1] < 0.))
+        .count();
+    n_crossings as f32 / 2. / (middle.len() as f32 / SAMPLE_RATE as f32)
+}
+
+#[test]
+fn test_playback_rate_is_valid() {
+    assert!(PlaybackRate::default().is_valid());
+    assert!(PlaybackRate::Varispeed { speed: 0.25 }.is_valid());
+    assert!(PlaybackRate::Independent {
+        speed: 4.,
+        semitones: -24.
+    }
+    .is_valid());
+
+    for rate in [
+        PlaybackRate::Varispeed { speed: 0. },
+        PlaybackRate::Varispeed { speed: 4.5 },
+        PlaybackRate::Varispeed { speed: f32::NAN },
+        PlaybackRate::Varispeed {
+            speed: f32::INFINITY,
+        },
+        PlaybackRate::Independent {
+            speed: 0.2,
+            semitones: 0.,
+        },
+        PlaybackRate::Independent {
+            speed: 1.,
+            semitones: 25.,
+        },
+        PlaybackRate::Independent {
+            speed: 1.,
+            semitones: f32::NAN,
+        },
+        PlaybackRate::Independent {
+            speed: f32::NAN,
+            semitones: 0.,
+        },
+    ] {
+        assert!(!rate.is_valid(), "{rate:?}");
+    }
+}
+
+#[test]
+#[allow(
+    clippy::cast_possible_truncation,
+    clippy::cast_precision_loss,
+    clippy::cast_sign_loss
+)]
+fn test_rate_source_speed() {
+    for rate in [
+        PlaybackRate::Varispeed { speed: 0.5 },
+        PlaybackRate::Varispeed { speed: 2. },
+        PlaybackRate::Independent {
+            speed: 0.5,
+            semitones: 0.,
+        },
+        PlaybackRate::Independent {
+            speed: 2.,
+            semitones: 0.,
+        },
+    ] {
+        let control = Arc::new(RateControl::default());
+        control.set(rate);
+        let output = RateSource::new(sine_buffer(1000.), control).collect::<Vec<_>>();
+
+        // the duration is divided by the speed
+        let speed = match rate {
+            PlaybackRate::Varispeed { speed } | PlaybackRate::Independent { speed, .. } => speed,
+        };
+        let n_frames = output.len() / 2;
+        let expected = (SAMPLE_RATE as f32 / speed) as usize;
+        assert!(
+            n_frames.abs_diff(expected) <= TIME_STRETCH_PADDING * 2,
+            "{rate:?}: {n_frames}"
+        );
+
+        // the pitch changes with the speed like a tape, or is kept
+        let expected = match rate {
+            PlaybackRate::Varispeed { speed } => 1000. * speed,
+            PlaybackRate::Independent { .. } => 1000.,
+        };
+        let freq = estimate_freq(&output);
+        assert!(
+            (freq - expected).abs() < expected * 0.02,
+            "{rate:?}: {freq}"
+        );
+    }
+}
+
+#[test]
+fn test_rate_source_pitch() {
+    for (semitones, expected) in [(12., 2000.), (-12., 500.)] {
+        let control = Arc::new(RateControl::default());
+        control.set(PlaybackRate::Independent {
+            speed: 1.,
+            semitones,
+        });
+        let output = RateSource::new(sine_buffer(1000.), control).collect::<Vec<_>>();
+
+        // shifted by an octave, keeping the duration
+        let n_frames = output.len() / 2;
+        assert!(
+            n_frames.abs_diff(SAMPLE_RATE as usize) <= TIME_STRETCH_PADDING * 2,
+            "{semitones}: {n_frames}"
+        );
+        let freq = estimate_freq(&output);
+        assert!(
+            (freq - expected).abs() < expected * 0.02,
+            "{semitones}: {freq}"
+        );
+    }
+}
+
+#[test]
+fn test_rate_source_bypass() {
+    let input = sine_buffer(1000.).collect::<Vec<_>>();
+
+    // passed through bit-exact at the original rate
+    for rate in [
+        PlaybackRate::Varispeed { speed: 1. },
+        PlaybackRate::Independent {
+            speed: 1.,
+            semitones: 0.,
+        },
+    ] {
+        let control = Arc::new(RateControl::default());
+        control.set(rate);
+        let output = RateSource::new(sine_buffer(1000.), control).collect::<Vec<_>>();
+        assert_eq!(output, input);
+    }
+
+    // and again once set back to the original rate, after the rest of the chunk read at speed 2
+    let control = Arc::new(RateControl::default());
+    let mut source = RateSource::new(sine_buffer(1000.), control.clone());
+    control.set(PlaybackRate::Varispeed { speed: 2. });
+    source.by_ref().take(100).for_each(drop);
+    control.set(PlaybackRate::default());
+    let output = source.collect::<Vec<_>>();
+    let passed = &input[512 * 2..];
+    assert!(output.ends_with(passed));
+    assert!(output.len() - passed.len() < 512);
+    assert_eq!(control.get_latency(), 0);
+}
+
+#[test]
+fn test_rate_source_seek() {
+    let control = Arc::new(RateControl::default());
+    // a positive half second followed by a negative half second
+    let samples = (0..SAMPLE_RATE)
+        .map(|i| if i < SAMPLE_RATE / 2 { 0.5 } else { -0.5 })
+        .collect::<Vec<_>>();
+    let input = SamplesBuffer::new(1, SAMPLE_RATE, samples);
+    let mut source = RateSource::new(input, control.clone());
+    control.set(PlaybackRate::Independent {
+        speed: 2.,
+        semitones: 3.,
+    });
+    source.by_ref().take(1000).for_each(drop);
+    assert!(control.get_latency() > 0);
+
+    // the samples buffered before the seek are discarded
+    source.try_seek(Duration::from_secs_f32(0.75)).unwrap();
+    assert_eq!(control.get_latency(), 0);
+    let output = source.by_ref().take(1000).collect::<Vec<_>>();
+    assert!(output.iter().all(|&x| x <= 0.), "{output:?}");
+}
+
+/// A stereo source of `n_frames` frames at 1 kHz, with each sample being the index of its frame,
+/// negated on the right channel.
+#[allow(clippy::cast_precision_loss)]
+fn frame_index_buffer(n_frames: u32) -> SamplesBuffer<f32> {
+    let samples = (0..n_frames)
+        .flat_map(|i| [i as f32, -(i as f32)])
+        .collect::<Vec<_>>();
+    SamplesBuffer::new(2, 1000, samples)
+}
+
+/// The frame indices of the samples read from [`frame_index_buffer`],
+/// checking that no frame is split.
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+fn frame_indices(samples: impl IntoIterator<Item = f32>) -> Vec<u32> {
+    let samples = samples.into_iter().collect::<Vec<_>>();
+    assert!(samples.len().is_multiple_of(2));
+    samples
+        .chunks_exact(2)
+        .map(|frame| {
+            assert!((frame[0] + frame[1]).abs() < f32::EPSILON, "{frame:?}");
+            frame[0] as u32
+        })
+        .collect()
+}
+
+#[test]
+fn test_loop_source() {
+    let control = Arc::new(LoopControl::default());
+    control.set_region(Some(LoopRegion {
+        start: 0.1,
+        end: 0.2,
+    }));
+    control.set_enabled(true);
+    let mut source = LoopSource::new(frame_index_buffer(1000), control.clone());
+
+    // wraps from the last frame of the region to its first frame, without gap or duplicate
+    let frames = frame_indices(source.by_ref().take(350 * 2));
+    let expected = (0..200).chain(100..200).chain(100..150).collect::<Vec<_>>();
+    assert_eq!(frames, expected);
+    assert_eq!(control.get_frames(), 150);
+
+    // plays on linearly once disabled
+    control.set_enabled(false);
+    let frames = frame_indices(source.by_ref());
+    assert_eq!(frames, (150..1000).collect::<Vec<_>>());
+    assert!(control.take_ended());
+}
+
+#[test]
+fn test_loop_source_region_before() {
+    let control = Arc::new(LoopControl::default());
+    let mut source = LoopSource::new(frame_index_buffer(1000), control.clone());
+    let frames = frame_indices(source.by_ref().take(300 * 2));
+    assert_eq!(frames, (0..300).collect::<Vec<_>>());
+
+    // not jumped back to
+    control.set_region(Some(LoopRegion {
+        start: 0.1,
+        end: 0.2,
+    }));
+    control.set_enabled(true);
+    let frames = frame_indices(source.by_ref());
+    assert_eq!(frames, (300..1000).collect::<Vec<_>>());
+}
+
+#[test]
+fn test_loop_source_empty_region() {
+    for (start, end) in [(0.2, 0.2), (0.3, 0.1)] {
+        let control = Arc::new(LoopControl::default());
+        control.set_region(Some(LoopRegion { start, end }));
+        control.set_enabled(true);
+        let source = LoopSource::new(frame_index_buffer(1000), control.clone());
+
+        // ignored, i.e. played linearly to the end
+        let frames = frame_indices(source);
+        assert_eq!(frames, (0..1000).collect::<Vec<_>>(), "{start} - {end}");
+    }
+}
+
+#[test]
+fn test_loop_source_latency() {
+    let control = Arc::new(LoopControl::default());
+    control.set_region(Some(LoopRegion {
+        start: 0.5,
+        end: 0.6,
+    }));
+    control.set_enabled(true);
+    let input = SamplesBuffer::new(1, SAMPLE_RATE, vec![0.5; SAMPLE_RATE as usize]);
+    let mut source = LoopSource::new(input, control.clone());
+
+    // before the region
+    source.by_ref().take(24_000 + 50).for_each(drop);
+    assert_eq!(control.get_frames_before(100), 23_950);
+
+    // the frames played before the frames read are at the end of the region once looped
+    source.by_ref().take(4800).for_each(drop);
+    assert_eq!(control.get_frames(), 24_050);
+    assert_eq!(control.get_frames_before(20), 24_030);
+    assert_eq!(control.get_frames_before(100), 28_750);
+}
+
+#[test]
+#[allow(clippy::cast_precision_loss)]
+fn test_meter_source() {
+    let control = Arc::new(MeterControl::default());
+    let mut meter = Meter::new(control.clone());
+    let samples = (0..SAMPLE_RATE)
+        .flat_map(|i| {
+            let x = (2. * PI * 1000. * i as f32 / SAMPLE_RATE as f32).sin();
+            [0.5 * x, 0.25 * x]
+        })
+        .collect::<Vec<_>>();
+
+    // nothing is measured until subscribed
+    MeterSource::new(
+        SamplesBuffer::new(2, SAMPLE_RATE, samples.clone()),
+        control.clone(),
+    )
+    .for_each(|_| {});
+    assert!(meter.update().is_none());
+
+    let generation = control.subscribe();
+    assert!(control.is_subscribed(generation));
+    MeterSource::new(SamplesBuffer::new(2, SAMPLE_RATE, samples), control.clone()).for_each(|_| {});
+    let levels = meter.update().unwrap();
+    for (channel, peak) in [0.5, 0.25].into_iter().enumerate() {
+        assert!((levels.peak[channel] - peak).abs() < 1e-3);
+        assert!((levels.rms[channel] - peak * std::f32::consts::FRAC_1_SQRT_2).abs() < 1e-3);
+        assert!((levels.peak_hold[channel] - peak).abs() < 1e-3);
+    }
+
+    // silence is pushed once, with the peak held
+    let levels = meter.update().unwrap();
+    assert_eq!(levels.peak, vec![0.; 2]);
+    assert!((levels.peak_hold[0] - 0.5).abs() < 1e-3);
+    assert!(meter.update().is_none());
+
+    control.unsubscribe();
+    assert!(!control.is_subscribed(generation));
+}
+
+#[test]
+#[allow(clippy::cast_precision_loss)]
+fn test_spectrum_source() {
+    const FFT_SIZE: usize = 1024;
+    const BIN: usize = 100;
+
+    let control = Arc::new(SpectrumControl::default());
+    control.set_options(SpectrumOptions {
+        fft_size: FFT_SIZE,
+        window: Window::Hann,
+    });
+    assert!(!SpectrumOptions {
+        fft_size: 1000,
+        window: Window::Hann,
+    }
+    .is_valid());
+    let mut analyzer = Analyzer::new(control.clone());
+    assert!(analyzer.update().is_none());
+
+    // a sine at the frequency of a bin, at -6 dBFS on both channels
+    let freq = SAMPLE_RATE as f32 * BIN as f32 / FFT_SIZE as f32;
+    let samples = (0..SAMPLE_RATE / 10)
+        .flat_map(|i| {
+            let x = 0.5 * (2. * PI * freq * i as f32 / SAMPLE_RATE as f32).sin();
+            [x, x]
+        })
+        .collect::<Vec<_>>();
+    control.subscribe();
+    SpectrumSource::new(SamplesBuffer::new(2, SAMPLE_RATE, samples), control.clone())
+        .for_each(|_| {});
+
+    let spectrum = analyzer.update().unwrap();
+    assert_eq!(spectrum.sample_rate, SAMPLE_RATE);
+    assert_eq!(spectrum.bins.len(), FFT_SIZE / 2 + 1);
+    let peak = spectrum
+        .bins
+        .iter()
+        .enumerate()
+        .max_by(|(_, a), (_, b)| a.total_cmp(b))
+        .unwrap()
+        .0;
+    assert_eq!(peak, BIN);
+    assert!(
+        (spectrum.bins[BIN] + 6.02).abs() < 0.1,
+        "{}",
+        spectrum.bins[BIN]
+    );
+    assert!(spectrum.bins[BIN * 2] < -60.);
+
+    // silence is pushed once
+    let spectrum = analyzer.update().unwrap();
+    assert!(spectrum.bins.iter().all(|&bin| bin <= MIN_DB));
+    assert!(analyzer.update().is_none());
+}
+
+#[test]
+fn test_channel_source() {
+    let control = Arc::new(ChannelControl::default());
+    let play = |n_channels: u16, frame: &[f32], fuma: bool| {
+        let input = SamplesBuffer::new(n_channels, SAMPLE_RATE, frame.to_vec());
+        let source = ChannelSource::new(input, control.clone(), fuma);
+        assert_eq!(source.channels(), n_channels.min(2));
+        source.collect::<Vec<_>>()
+    };
+    let assert_frame = |actual: &[f32], expected: &[f32]| {
+        assert_eq!(actual.len(), expected.len());
+        for (actual, expected) in actual.iter().zip(expected) {
+            assert!((actual - expected).abs() < 1e-6, "{actual} != {expected}");
+        }
+    };
+
+    // stereo passes through
+    assert_frame(&play(2, &[0.5, -0.25], false), &[0.5, -0.25]);
+
+    // 5.1 downmixed without LFE
+    let surround = [0.1, 0.2, 0.3, 0.4, 0.5, 0.6];
+    let gain = std::f32::consts::FRAC_1_SQRT_2;
+    let sum = 1. + 2. * gain;
+    assert_frame(
+        &play(6, &surround, false),
+        &[
+            (0.1 + 0.3 * gain + 0.5 * gain) / sum,
+            (0.2 + 0.3 * gain + 0.6 * gain) / sum,
+        ],
+    );
+
+    // FuMa, virtual cardioids from W and Y
+    let w = 0.5 * std::f32::consts::SQRT_2 * 0.5;
+    let sum = 0.5 * std::f32::consts::SQRT_2 + 0.5;
+    assert_frame(
+        &play(4, &[0.5, 0., 0.4, 0.], true),
+        &[(w + 0.2) / sum, (w - 0.2) / sum],
+    );
+
+    control.set(ChannelOptions {
+        solo: Some(3),
+        ..ChannelOptions::default()
+    });
+    assert_frame(&play(6, &surround, false), &[0.4, 0.4]);
+
+    control.set(ChannelOptions {
+        swap: true,
+        invert: true,
+        ..ChannelOptions::default()
+    });
+    assert_frame(&play(2, &[0.5, -0.25], false), &[0.25, -0.5]);
+
+    control.set(ChannelOptions {
+        mono: true,
+        ..ChannelOptions::default()
+    });
+    assert_frame(&play(2, &[0.5, -0.25], false), &[0.125, 0.125]);
+    assert_frame(&play(1, &[0.5], false), &[0.5]);
+
+    // AmbiX only when enabled
+    control.set(ChannelOptions {
+        ambisonic: true,
+        ..ChannelOptions::default()
+    });
+    assert_frame(&play(4, &[0.5, 0.4, 0., 0.], false), &[0.45, 0.05]);
+}
+
+#[test]
+fn test_queue() {
+    let mut queue = Queue::default();
+    assert_eq!(queue.next(), None);
+
+    assert_eq!(queue.set(vec![10, 20, 30], Some(1)), Some(20));
+    assert_eq!(queue.next(), Some(30));
+    assert_eq!(queue.next(), None);
+    assert_eq!(queue.previous(), Some(20));
+    assert_eq!(queue.previous(), Some(10));
+    assert_eq!(queue.previous(), None);
+
+    // repeat
+    let options = QueueOptions::default();
+    queue.set_options(QueueOptions {
+        repeat: RepeatMode::All,
+        ..options
+    });
+    assert_eq!(queue.previous(), Some(30));
+    assert_eq!(queue.next(), Some(10));
+    queue.set_options(QueueOptions {
+        repeat: RepeatMode::One,
+        ..options
+    });
+    assert_eq!(queue.advance(), Some(10));
+    assert_eq!(queue.next(), Some(20));
+    queue.set_options(QueueOptions {
+        auto_advance: false,
+        ..options
+    });
+    assert_eq!(queue.advance(), None);
+
+    // remove and enqueue
+    queue.set_options(options);
+    assert!(queue.remove(0));
+    assert!(!queue.remove(2));
+    assert_eq!(queue.state().entries, vec![20, 30]);
+    assert_eq!(queue.state().current, Some(0));
+    queue.enqueue(&[40]);
+    assert_eq!(queue.advance(), Some(30));
+    assert_eq!(queue.jump(2), Some(40));
+    assert_eq!(queue.jump(3), None);
+
+    // shuffle keeps the current entry, and plays each entry once per round
+    let entries = (0..100).collect::<Vec<_>>();
+    queue.set(entries.clone(), Some(50));
+    queue.set_options(QueueOptions {
+        shuffle: true,
+        repeat: RepeatMode::All,
+        ..options
+    });
+    assert_eq!(queue.current(), Some(50));
+    let mut played = (0..99).map(|_| queue.next().unwrap()).collect::<Vec<_>>();
+    played.push(50);
+    played.sort_unstable();
+    assert_eq!(played, entries);
+    assert!(queue.next().is_some());
+}
+
+#[test]
+fn test_queue_auto_advance() {
+    let dir = testdir!();
+    let path = write_sine(&dir.join("sine.wav"), 0.2);
+
+    let emitter = Arc::new(TestEmitter::default());
+    let mut player = Player::new(emitter.clone());
+    player.set_output_target(OutputTarget::Null);
+    player.run();
+
+    player
+        .set_queue_options(QueueOptions {
+            gap: 0.2,
+            ..QueueOptions::default()
+        })
+        .unwrap();
+    assert_eq!(player.set_queue(vec![1, 2], Some(0)), Some(1));
+    player.set_source(path.clone(), None, None).unwrap();
+    player.play(false).unwrap();
+
+    // advances after the gap, but not past the end of the queue
+    assert_eq!(emitter.wait_for_advanced(1), vec![2]);
+    player.set_source(path, None, None).unwrap();
+    player.play(false).unwrap();
+    assert_eq!(emitter.wait_for_advanced(2), vec![2]);
+    assert_eq!(player.get_queue().current, Some(1));
+
+    // stopping does not advance
+    player.set_queue(vec![1, 2], Some(0));
+    player.play(false).unwrap();
+    player.stop();
+    assert_eq!(emitter.wait_for_advanced(2), vec![2]);
+
+    assert!(player
+        .set_queue_options(QueueOptions {
+            gap: -1.,
+            ..QueueOptions::default()
+        })
+        .is_err());
+}
+
+#[test]
+fn test_preload() {
+    let dir = testdir!();
+    let path = write_sine(&dir.join("sine.wav"), 1.);
+
+    let preloader = Arc::new(Preloader::default());
+    preloader.preload(vec![path.clone()]);
+    let source = (0..100)
+        .find_map(|_| {
+            sleep(Duration::from_millis(10));
+            preloader.take(&path)
+        })
+        .unwrap();
+    assert!(preloader.take(&path).is_none());
+
+    // the same samples as opened directly
+    let direct = FileSource::open(&path).unwrap().collect::<Vec<_>>();
+    assert_eq!(source.collect::<Vec<_>>(), direct);
+}
+
+/// Measure the latency of switching to a source and playing it, cold and preloaded.
+///
+/// Run with `cargo test bench_switch_latency -- --ignored --nocapture`.
+#[test]
+#[ignore = "benchmark"]
+fn bench_switch_latency() {
+    const N_SOURCES: usize = 20;
+
+    let dir = testdir!();
+    let paths = (0..N_SOURCES)
+        .map(|i| write_sine(&dir.join(format!("sine_{i}.wav")), 10.))
+        .collect::<Vec<_>>();
+
+    let mut player = Player::new(Arc::new(TestEmitter::default()));
+    player.set_output_target(OutputTarget::Null);
+    player.run();
+
+    for preload in [false, true] {
+        let mut latencies = paths
+            .iter()
+            .map(|path| {
+                if preload {
+                    player.preload(vec![path.clone()]);
+                    sleep(Duration::from_millis(200));
+                }
+                let start = Instant::now();
+                player.set_source(path.clone(), None, None).unwrap();
+                player.play(false).unwrap();
+                start.elapsed()
+            })
+            .collect::<Vec<_>>();
+        latencies.sort_unstable();
+        println!(
+            "preload = {preload}: median {:?}, max {:?}",
+            latencies[N_SOURCES / 2],
+            latencies[N_SOURCES - 1]
+        );
+    }
+}
@@ -0,0 +1,180 @@
+#[cfg(test)]
+mod tests;
+
+use std::f64::consts::PI;
+
+/// The number of input frames on each side of an output frame at the original rate,
+/// which is widened in proportion when downsampling.
+const HALF_TAPS: usize = 32;
+/// The number of fractional positions between two input frames the kernel is tabulated at.
+const N_PHASES: usize = 256;
+/// The cutoff frequency relative to the Nyquist frequency of the lower sample rate,
+/// leaving room for the transition band of the filter.
+const CUTOFF: f64 = 0.9;
+/// The number of frames kept before the next output frame, enough for the taps up to a step of 4,
+/// e.g. the fastest playback rate, so that changing the step does not cut the taps short.
+const HISTORY_FRAMES: usize = HALF_TAPS * 4;
+
+/// Resamples interleaved samples with a windowed-sinc interpolation filter,
+/// which is tabulated for a number of phases (polyphase) and low-passes when downsampling.
+///
+/// Samples are processed in chunks of any size, e.g. the packets of a decoder.
+pub struct Resampler {
+    n_channels: usize,
+    /// The number of input frames per output frame.
+    step: f64,
+    /// The number of taps on each side of an output frame.
+    half_taps: usize,
+    /// The taps for each of the `N_PHASES + 1` phases, from the first input frame used.
+    kernel: Vec<f32>,
+    /// The taps of the output frame being interpolated, between two phases.
+    taps: Vec<f32>,
+    /// The position of the next output frame in `buffer`, in frames.
+    pos: f64,
+    /// The input samples not consumed yet, and the frames before the next output frame it uses.
+    buffer: Vec<f32>,
+}
+
+impl Resampler {
+    pub fn new(n_channels: usize, from_sample_rate: u32, to_sample_rate: u32) -> Self {
+        let mut resampler = Self {
+            n_channels,
+            step: 0.,
+            half_taps: 0,
+            kernel: Vec::new(),
+            taps: Vec::new(),
+            pos: 0.,
+            buffer: Vec::new(),
+        };
+        resampler.set_step(f64::from(from_sample_rate) / f64::from(to_sample_rate));
+        resampler
+    }
+
+    /// Set the number of input frames per output frame, e.g. to change the playback rate.
+    #[allow(clippy::float_cmp)]
+    pub fn set_step(&mut self, step: f64) {
+        let bandwidth = |step: f64| step.recip().min(1.);
+        let rebuild = self.kernel.is_empty() || bandwidth(step) != bandwidth(self.step);
+        self.step = step;
+        if rebuild {
+            self.build_kernel();
+        }
+    }
+
+    /// Tabulate the Blackman-windowed sinc for the current step.
+    #[allow(clippy::cast_possible_truncation)]
+    #[allow(clippy::cast_sign_loss)]
+    #[allow(clippy::cast_precision_loss)]
+    fn build_kernel(&mut self) {
+        // the lower of the input and the output rates, relative to the input rate
+        let bandwidth = self.step.recip().min(1.);
+        let cutoff = CUTOFF * bandwidth;
+        self.half_taps = (HALF_TAPS as f64 / bandwidth).ceil() as usize;
+        let n_taps = self.half_taps * 2;
+        let width = self.half_taps as f64;
+
+        self.kernel.clear();
+        for phase in 0..=N_PHASES {
+            let frac = phase as f64 / N_PHASES as f64;
+            let start = self.kernel.len();
+            self.kernel.extend((0..n_taps).map(|k| {
+                // the distance from the output frame to the input frame of the tap
+                let x = k as f64 - (width - 1.) - frac;
+                let sinc = if x == 0. {
+                    1.
+                } else {
+                    (PI * cutoff * x).sin() / (PI * cutoff * x)
+                };
+                let t = x / width;
+                let window = 0.42 + 0.5 * (PI * t).cos() + 0.08 * (2. * PI * t).cos();
+                (sinc * window) as f32
+            }));
+
+            // normalize the gain at DC to 1
+            let taps = &mut self.kernel[start..];
+            let sum = taps.iter().sum::<f32>();
+            for tap in taps {
+                *tap /= sum;
+            }
+        }
+        self.taps.resize(n_taps, 0.);
+    }
+
+    /// Discard all buffered samples, e.g. after seeking.
+    pub fn reset(&mut self) {
+        self.buffer.clear();
+        self.pos = 0.;
+    }
+
+    /// Resample `input` and append the resampled samples to `output`.
+    #[allow(clippy::cast_possible_truncation)]
+    #[allow(clippy::cast_sign_loss)]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn process(&mut self, input: &[f32], output: &mut Vec<f32>) {
+        self.buffer.extend_from_slice(input);
+        let n_frames = self.buffer.len() / self.n_channels;
+
+        // interpolate while the frames after the next output frame are available
+        while self.pos as usize + self.half_taps < n_frames {
+            self.interpolate(output);
+            self.pos += self.step;
+        }
+
+        // drop the consumed frames, keeping the frames before the next output frame
+        let consumed = (self.pos as usize + 1)
+            .saturating_sub(self.half_taps.max(HISTORY_FRAMES))
+            .min(n_frames);
+        self.buffer.drain(..consumed * self.n_channels);
+        self.pos -= consumed as f64;
+    }
+
+    /// Resample the remaining input samples at the end of the stream, and append them to `output`.
+    #[allow(clippy::cast_precision_loss)]
+    pub fn flush(&mut self, output: &mut Vec<f32>) {
+        let n_frames = self.buffer.len() / self.n_channels;
+        // the frames after the end of the stream are silent
+        self.buffer
+            .resize((n_frames + self.half_taps) * self.n_channels, 0.);
+        while self.pos < n_frames as f64 {
+            self.interpolate(output);
+            self.pos += self.step;
+        }
+
+        self.reset();
+    }
+
+    /// Interpolate the output frame at `pos` and append it to `output`.
+    ///
+    /// The frames before the start of `buffer` are taken as silent, e.g. at the start of the stream.
+    #[allow(clippy::cast_possible_truncation)]
+    #[allow(clippy::cast_sign_loss)]
+    #[allow(clippy::cast_precision_loss)]
+    #[allow(clippy::cast_possible_wrap)]
+    fn interpolate(&mut self, output: &mut Vec<f32>) {
+        let index = self.pos as usize;
+        let phase = (self.pos - index as f64) * N_PHASES as f64;
+        let (phase, frac) = (phase as usize, (phase - phase.floor()) as f32);
+
+        // interpolate the taps between the two nearest phases
+        let n_taps = self.taps.len();
+        let taps = &self.kernel[phase * n_taps..(phase + 2) * n_taps];
+        let (current, next) = taps.split_at(n_taps);
+        for ((tap, a), b) in self.taps.iter_mut().zip(current).zip(next) {
+            *tap = a + (b - a) * frac;
+        }
+
+        // the first input frame of the taps, which may be before the start of `buffer`
+        let first = (index + 1) as isize - self.half_taps as isize;
+        let skipped = first.min(0).unsigned_abs();
+        let frames = &self.buffer[first.max(0) as usize * self.n_channels..];
+        for channel in 0..self.n_channels {
+            output.push(
+                self.taps[skipped..]
+                    .iter()
+                    .zip(frames[channel..].iter().step_by(self.n_channels))
+                    .map(|(tap, sample)| tap * sample)
+                    .sum(),
+            );
+        }
+    }
+}
@@ -0,0 +1,82 @@
+use super::Resampler;
+
+use std::f64::consts::PI;
+
+use test_log::test;
+
+/// Resample a stereo sine of one second in chunks of `chunk_frames` frames,
+/// with the channels of opposite phase.
+#[allow(clippy::cast_possible_truncation)]
+fn resample_sine(from: u32, to: u32, freq: f64, chunk_frames: usize) -> Vec<f32> {
+    let input = (0..from)
+        .flat_map(|i| {
+            let x = 0.5 * (2. * PI * freq * f64::from(i) / f64::from(from)).sin() as f32;
+            [x, -x]
+        })
+        .collect::<Vec<_>>();
+
+    let mut resampler = Resampler::new(2, from, to);
+    let mut output = Vec::new();
+    for chunk in input.chunks(chunk_frames * 2) {
+        resampler.process(chunk, &mut output);
+    }
+    resampler.flush(&mut output);
+    output
+}
+
+#[allow(clippy::cast_precision_loss)]
+fn assert_sine(output: &[f32], sample_rate: u32, freq: f64) {
+    let n_frames = output.len() / 2;
+    assert!(n_frames.abs_diff(sample_rate as usize) <= 1, "{n_frames}");
+
+    // the edges are attenuated by the silence around the stream
+    for (i, frame) in output
+        .chunks_exact(2)
+        .enumerate()
+        .take(n_frames - 64)
+        .skip(64)
+    {
+        let expected = 0.5 * (2. * PI * freq * i as f64 / f64::from(sample_rate)).sin();
+        assert!(
+            (f64::from(frame[0]) - expected).abs() < 1e-4,
+            "{i}: {frame:?}"
+        );
+        assert!((frame[0] + frame[1]).abs() < 1e-6);
+    }
+}
+
+#[test]
+fn test_upsample() {
+    let output = resample_sine(44100, 48000, 10000., 333);
+    assert_sine(&output, 48000, 10000.);
+}
+
+#[test]
+fn test_downsample() {
+    let output = resample_sine(48000, 22050, 5000., 7);
+    assert_sine(&output, 22050, 5000.);
+
+    // the frequencies above the new Nyquist frequency are filtered out instead of aliased
+    let output = resample_sine(48000, 22050, 20000., 512);
+    let peak = output[256..output.len() - 256]
+        .iter()
+        .fold(0f32, |peak, sample| peak.max(sample.abs()));
+    assert!(peak < 1e-3, "{peak}");
+}
+
+#[test]
+fn test_set_step() {
+    let mut resampler = Resampler::new(1, 48000, 48000);
+    let mut output = Vec::new();
+    resampler.process(&vec![1.; 4800], &mut output);
+    resampler.set_step(2.);
+    resampler.process(&vec![1.; 4800], &mut output);
+    resampler.flush(&mut output);
+    // the frames buffered when the step changes are resampled at the new step
+    assert!(output.len().abs_diff(4800 + 2400) <= 32, "{}", output.len());
+
+    // the gain at DC is 1 at any step, apart from the edges
+    assert!(output[64..output.len() - 64]
+        .iter()
+        .all(|sample| (sample - 1.).abs() < 1e-3));
+}
@@ -0,0 +1,93 @@
+use super::decoder::{Error, SampleDecoder};
+
+use serde::Deserialize;
+use std::path::Path;
+
+/// Options of the detection of leading and trailing silence.
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct SilenceOptions {
+    /// Frames with all samples at or below this level in dBFS are considered silent.
+    pub threshold: f32,
+    /// Leading or trailing silence shorter than this duration in seconds is not detected.
+    pub min_duration: f32,
+}
+
+impl Default for SilenceOptions {
+    fn default() -> Self {
+        Self {
+            threshold: -60.,
+            min_duration: 0.,
+        }
+    }
+}
+
+impl SilenceOptions {
+    pub fn is_valid(self) -> bool {
+        self.threshold.is_finite()
+            && self.threshold <= 0.
+            && self.min_duration.is_finite()
+            && self.min_duration >= 0.
+    }
+}
+
+/// The leading and trailing silence of a file.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Silence {
+    /// The end of the leading silence in seconds, zero if there is none.
+    pub head: f32,
+    /// The start of the trailing silence in seconds, the duration of the file if there is none.
+    pub tail: f32,
+    /// The options the silence was detected with.
+    pub options: SilenceOptions,
+}
+
+/// Detect the leading and trailing silence of the default audio track of a file.
+///
+/// A file that is silent as a whole has no leading or trailing silence, so nothing is skipped.
+#[allow(clippy::cast_precision_loss)]
+pub fn detect_silence(path: &Path, options: SilenceOptions) -> Result<Silence, Error> {
+    let mut decoder = SampleDecoder::open(path)?;
+    let spec = *decoder.spec();
+    let threshold = 10f32.powf(options.threshold / 20.);
+
+    let mut n_frames = 0u64;
+    // the first frame above the threshold, and the one after the last
+    let mut start = None;
+    let mut end = 0;
+
+    while let Some(samples) = decoder.next_samples()? {
+        for frame in samples.chunks_exact(spec.n_channels) {
+            if frame.iter().any(|sample| sample.abs() > threshold) {
+                start.get_or_insert(n_frames);
+                end = n_frames + 1;
+            }
+            n_frames += 1;
+        }
+    }
+
+    let to_secs = |frames: u64| frames as f32 / spec.sample_rate as f32;
+    let duration = to_secs(n_frames);
+    let Some(start) = start else {
+        return Ok(Silence {
+            head: 0.,
+            tail: duration,
+            options,
+        });
+    };
+
+    let mut head = to_secs(start);
+    if head < options.min_duration {
+        head = 0.;
+    }
+    let mut tail = to_secs(end);
+    if duration - tail < options.min_duration {
+        tail = duration;
+    }
+
+    Ok(Silence {
+        head,
+        tail,
+        options,
+    })
+}
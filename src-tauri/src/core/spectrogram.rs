@@ -0,0 +1,398 @@
+#[cfg(test)]
+mod tests;
+
+use super::decoder::SampleDecoder;
+use super::player::Window;
+
+use log::{debug, info, warn};
+use rustfft::num_complex::Complex;
+use rustfft::FftPlanner;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::slice::from_raw_parts;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::spawn;
+use thiserror::Error;
+
+/// The number of columns sent at a time.
+const BATCH_COLUMNS: usize = 64;
+const MIN_FFT_SIZE: usize = 256;
+const MAX_FFT_SIZE: usize = 16384;
+/// The lowest frequency of the log scale in Hz.
+const LOG_MIN_FREQ: f32 = 20.;
+/// The level of the bins without any energy, in dBFS.
+const MIN_DB: f32 = -120.;
+
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error("source not set")]
+    SourceNotSet,
+    #[error("invalid spectrogram options: {0:?}")]
+    InvalidOptions(SpectrogramOptions),
+    #[error(transparent)]
+    Decoder(#[from] super::decoder::Error),
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum FrequencyScale {
+    /// The bins of the FFT as is.
+    #[default]
+    Linear,
+    /// Bands spaced logarithmically from 20 Hz to Nyquist.
+    Log,
+    /// Bands of triangular filters spaced evenly on the mel scale from 0 Hz to Nyquist.
+    Mel,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SpectrogramOptions {
+    /// The number of samples of each FFT, a power of two between 256 and 16384.
+    pub fft_size: usize,
+    /// The number of frames between the starts of consecutive columns, at most `fft_size`.
+    pub hop: usize,
+    pub window: Window,
+    pub scale: FrequencyScale,
+    /// The number of bands of the log and mel scales, ignored for the linear scale.
+    pub n_bands: usize,
+}
+
+impl Default for SpectrogramOptions {
+    fn default() -> Self {
+        Self {
+            fft_size: 2048,
+            hop: 512,
+            window: Window::default(),
+            scale: FrequencyScale::default(),
+            n_bands: 256,
+        }
+    }
+}
+
+impl SpectrogramOptions {
+    fn is_valid(self) -> bool {
+        self.fft_size.is_power_of_two()
+            && (MIN_FFT_SIZE..=MAX_FFT_SIZE).contains(&self.fft_size)
+            && (1..=self.fft_size).contains(&self.hop)
+            && (self.scale == FrequencyScale::Linear || self.n_bands > 0)
+    }
+
+    /// The number of values of each column.
+    fn n_bins(self) -> usize {
+        match self.scale {
+            FrequencyScale::Linear => self.fft_size / 2 + 1,
+            FrequencyScale::Log | FrequencyScale::Mel => self.n_bands,
+        }
+    }
+}
+
+/// The layout of the spectrogram of the source, for the frontend to allocate it.
+#[derive(Clone, Copy, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SpectrogramInfo {
+    /// The number of columns, if the number of frames of the source is known.
+    pub n_columns: Option<u64>,
+    /// The number of values of each column, from low to high frequencies.
+    pub n_bins: usize,
+    pub sample_rate: u32,
+}
+
+pub struct SpectrogramGenerator {
+    source_path: Arc<Mutex<Option<PathBuf>>>,
+    reset: Arc<AtomicBool>,
+    options: SpectrogramOptions,
+}
+
+impl SpectrogramGenerator {
+    pub fn new() -> Self {
+        Self {
+            source_path: Arc::new(None.into()),
+            reset: Arc::new(false.into()),
+            options: SpectrogramOptions::default(),
+        }
+    }
+
+    pub fn set_source(&mut self, path: Option<PathBuf>) {
+        let mut source_path = self.source_path.lock().unwrap();
+        *source_path = path;
+        self.reset.store(true, Ordering::Release);
+    }
+
+    pub fn get_options(&self) -> SpectrogramOptions {
+        self.options
+    }
+
+    /// Set the options of the spectrogram, which stops the generation in progress
+    /// as its columns do not match the new options.
+    pub fn set_options(&mut self, options: SpectrogramOptions) -> Result<(), Error> {
+        if !options.is_valid() {
+            return Err(Error::InvalidOptions(options));
+        }
+        self.options = options;
+        self.reset.store(true, Ordering::Release);
+        Ok(())
+    }
+
+    pub fn prepare_spectrogram(&self) -> Result<SpectrogramInfo, Error> {
+        let path = self.source_path.lock().unwrap();
+        let path = path.as_ref().ok_or(Error::SourceNotSet)?;
+        let decoder = SampleDecoder::open(path)?;
+        let spec = decoder.spec();
+
+        let hop = self.options.hop as u64;
+        Ok(SpectrogramInfo {
+            n_columns: spec.n_frames.map(|n_frames| n_frames.div_ceil(hop)),
+            n_bins: self.options.n_bins(),
+            sample_rate: spec.sample_rate,
+        })
+    }
+
+    /// Generate the spectrogram in background, and call `on_data_available` with batches of columns.
+    ///
+    /// Each column is `n_bins` levels in dBFS as `f32`, from low to high frequencies.
+    /// The column `i` is centered at the frame `i * hop`.
+    pub fn request_spectrogram<F>(&self, on_data_available: F) -> Result<(), Error>
+    where
+        F: Fn(&[u8]) + Send + 'static,
+    {
+        let mut decoder = SampleDecoder::open(
+            self.source_path
+                .lock()
+                .unwrap()
+                .as_ref()
+                .ok_or(Error::SourceNotSet)?,
+        )?;
+        self.reset.store(false, Ordering::Release);
+
+        let n_channels = decoder.spec().n_channels;
+        let options = self.options;
+        let mut analyzer = ColumnAnalyzer::new(options, decoder.spec().sample_rate);
+
+        debug!("spectrogram options: {options:?}");
+
+        let reset = self.reset.clone();
+        spawn(move || {
+            debug!("start spectrogram generation");
+
+            // samples averaged over all channels per frame, centered on the first column
+            let mut frames = vec![0f32; options.fft_size / 2];
+            let mut data = Vec::with_capacity(BATCH_COLUMNS * options.n_bins());
+            let mut n_decoded = 0;
+            let mut n_columns = 0;
+
+            loop {
+                if reset.load(Ordering::Acquire) {
+                    info!("Spectrogram generator: source reset");
+                    break;
+                }
+
+                #[allow(clippy::cast_precision_loss)]
+                let end = match decoder.next_samples() {
+                    Ok(Some(samples)) => {
+                        n_decoded += samples.len() / n_channels;
+                        frames.extend(
+                            samples
+                                .chunks_exact(n_channels)
+                                .map(|frame| frame.iter().sum::<f32>() / n_channels as f32),
+                        );
+                        false
+                    }
+                    Ok(None) => true, // end of stream
+                    Err(err) => {
+                        // about to break
+                        warn!("Spectrogram generator: stopped generating spectrogram, because of unrecoverable error: {err}");
+                        true
+                    }
+                };
+                if end {
+                    // pad the last columns
+                    frames.resize(frames.len() + options.fft_size / 2, 0.);
+                }
+
+                // consume the windows available, up to the column of the last frame
+                let mut start = 0;
+                while start + options.fft_size <= frames.len()
+                    && (!end || n_columns * options.hop < n_decoded)
+                {
+                    analyzer.analyze(&frames[start..start + options.fft_size], &mut data);
+                    start += options.hop;
+                    n_columns += 1;
+
+                    if data.len() >= BATCH_COLUMNS * options.n_bins() {
+                        on_data_available(as_bytes(&data));
+                        data.clear();
+                    }
+                }
+                frames.drain(..start.min(frames.len()));
+
+                if end {
+                    if !data.is_empty() {
+                        on_data_available(as_bytes(&data));
+                    }
+                    break;
+                }
+            }
+
+            debug!("spectrogram generation done");
+        });
+
+        Ok(())
+    }
+}
+
+fn as_bytes(data: &[f32]) -> &[u8] {
+    unsafe { from_raw_parts(data.as_ptr().cast::<u8>(), size_of_val(data)) }
+}
+
+/// Computes the columns of the spectrogram.
+struct ColumnAnalyzer {
+    options: SpectrogramOptions,
+    fft: Arc<dyn rustfft::Fft<f32>>,
+    /// The window coefficients, scaled so that a full scale sine is 0 dBFS.
+    window: Vec<f32>,
+    /// The weights of the FFT bins of each band of the mel scale, as the index of the first bin
+    /// and the weights from it.
+    mel_filters: Vec<(usize, Vec<f32>)>,
+    /// The range of the FFT bins of each band of the log scale.
+    log_bands: Vec<(usize, usize)>,
+    buffer: Vec<Complex<f32>>,
+    scratch: Vec<Complex<f32>>,
+    power: Vec<f32>,
+}
+
+impl ColumnAnalyzer {
+    fn new(options: SpectrogramOptions, sample_rate: u32) -> Self {
+        let size = options.fft_size;
+        let fft = FftPlanner::new().plan_fft_forward(size);
+        let mut window = options.window.coefficients(size);
+        let gain = 2. / window.iter().sum::<f32>();
+        window.iter_mut().for_each(|w| *w *= gain);
+
+        #[allow(clippy::cast_precision_loss)]
+        let bin_width = sample_rate as f32 / size as f32;
+        let n_fft_bins = size / 2 + 1;
+        let (mel_filters, log_bands) = match options.scale {
+            FrequencyScale::Linear => (Vec::new(), Vec::new()),
+            FrequencyScale::Log => (
+                Vec::new(),
+                log_bands(options.n_bands, bin_width, n_fft_bins),
+            ),
+            FrequencyScale::Mel => (
+                mel_filters(options.n_bands, bin_width, n_fft_bins),
+                Vec::new(),
+            ),
+        };
+
+        Self {
+            options,
+            scratch: vec![Complex::default(); fft.get_inplace_scratch_len()],
+            fft,
+            window,
+            mel_filters,
+            log_bands,
+            buffer: vec![Complex::default(); size],
+            power: vec![0.; n_fft_bins],
+        }
+    }
+
+    /// Analyze a window of `fft_size` frames and append its column to `columns`.
+    fn analyze(&mut self, frames: &[f32], columns: &mut Vec<f32>) {
+        for ((value, sample), w) in self.buffer.iter_mut().zip(frames).zip(&self.window) {
+            *value = Complex::new(sample * w, 0.);
+        }
+        self.fft
+            .process_with_scratch(&mut self.buffer, &mut self.scratch);
+        for (power, value) in self.power.iter_mut().zip(&self.buffer) {
+            *power = value.norm_sqr();
+        }
+
+        let to_db = |power: f32| (10. * power.log10()).max(MIN_DB);
+        match self.options.scale {
+            FrequencyScale::Linear => columns.extend(self.power.iter().map(|&p| to_db(p))),
+            FrequencyScale::Log => columns.extend(self.log_bands.iter().map(|&(start, end)| {
+                to_db(
+                    self.power[start..end]
+                        .iter()
+                        .fold(0f32, |max, &p| max.max(p)),
+                )
+            })),
+            FrequencyScale::Mel => {
+                columns.extend(self.mel_filters.iter().map(|(start, weights)| {
+                    let power = weights
+                        .iter()
+                        .zip(&self.power[*start..])
+                        .map(|(w, p)| w * p)
+                        .sum::<f32>();
+                    to_db(power)
+                }));
+            }
+        }
+    }
+}
+
+/// The range of the FFT bins of each band spaced logarithmically from [`LOG_MIN_FREQ`] to Nyquist,
+/// with at least one bin per band.
+#[allow(clippy::cast_precision_loss)]
+#[allow(clippy::cast_possible_truncation)]
+#[allow(clippy::cast_sign_loss)]
+fn log_bands(n_bands: usize, bin_width: f32, n_fft_bins: usize) -> Vec<(usize, usize)> {
+    let nyquist = bin_width * (n_fft_bins - 1) as f32;
+    let ratio = (nyquist / LOG_MIN_FREQ).ln();
+    let edge = |band: usize| {
+        let freq = LOG_MIN_FREQ * (ratio * band as f32 / n_bands as f32).exp();
+        ((freq / bin_width).round() as usize).min(n_fft_bins - 1)
+    };
+    (0..n_bands)
+        .map(|band| {
+            let start = edge(band);
+            (start, edge(band + 1).max(start + 1).min(n_fft_bins))
+        })
+        .collect()
+}
+
+/// The triangular filters of the bands spaced evenly on the mel scale from 0 Hz to Nyquist,
+/// as the index of the first FFT bin and the weights from it.
+#[allow(clippy::cast_precision_loss)]
+fn mel_filters(n_bands: usize, bin_width: f32, n_fft_bins: usize) -> Vec<(usize, Vec<f32>)> {
+    let to_mel = |freq: f32| 2595. * (1. + freq / 700.).log10();
+    let to_freq = |mel: f32| 700. * (10f32.powf(mel / 2595.) - 1.);
+
+    let max_mel = to_mel(bin_width * (n_fft_bins - 1) as f32);
+    // the centers of the bands, with the edges of the first and the last band
+    let centers = (0..n_bands + 2)
+        .map(|i| to_freq(max_mel * i as f32 / (n_bands + 1) as f32) / bin_width)
+        .collect::<Vec<_>>();
+
+    centers
+        .windows(3)
+        .map(|bands| {
+            let [low, center, high] = [bands[0], bands[1], bands[2]];
+            let weight = |bin: usize| {
+                let bin = bin as f32;
+                if bin <= center {
+                    (bin - low) / (center - low)
+                } else {
+                    (high - bin) / (high - center)
+                }
+                .max(0.)
+            };
+            let weights = (0..n_fft_bins).map(weight).collect::<Vec<_>>();
+            match weights.iter().position(|&w| w > 0.) {
+                Some(start) => {
+                    let end = weights.iter().rposition(|&w| w > 0.).unwrap() + 1;
+                    (start, weights[start..end].to_vec())
+                }
+                // narrower than a bin, take the nearest bin
+                None => (nearest_bin(center, n_fft_bins), vec![1.]),
+            }
+        })
+        .collect()
+}
+
+#[allow(clippy::cast_possible_truncation)]
+#[allow(clippy::cast_sign_loss)]
+fn nearest_bin(bin: f32, n_fft_bins: usize) -> usize {
+    (bin.round() as usize).min(n_fft_bins - 1)
+}
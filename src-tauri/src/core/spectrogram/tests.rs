@@ -0,0 +1,114 @@
+use super::{
+    log_bands, mel_filters, ColumnAnalyzer, FrequencyScale, SpectrogramGenerator,
+    SpectrogramOptions,
+};
+use crate::core::wav::{SampleFormat, WavSpec, WavWriter};
+
+use std::f32::consts::PI;
+use std::sync::mpsc::channel;
+use std::time::Duration;
+
+use test_log::test;
+use testdir::testdir;
+
+#[test]
+fn test_scales() {
+    let n_fft_bins = 1025;
+    let bin_width = 48000. / 2048.;
+
+    let bands = log_bands(100, bin_width, n_fft_bins);
+    assert_eq!(bands.len(), 100);
+    assert!(bands.iter().all(|&(start, end)| start < end));
+    assert!(bands.windows(2).all(|pair| pair[0].0 <= pair[1].0));
+    assert!(bands.last().unwrap().1 >= n_fft_bins - 1);
+
+    let filters = mel_filters(64, bin_width, n_fft_bins);
+    assert_eq!(filters.len(), 64);
+    assert!(filters
+        .iter()
+        .all(|(start, weights)| start + weights.len() <= n_fft_bins && !weights.is_empty()));
+    assert!(filters.windows(2).all(|pair| pair[0].0 <= pair[1].0));
+}
+
+#[test]
+fn test_column() {
+    let options = SpectrogramOptions {
+        fft_size: 1024,
+        hop: 256,
+        ..SpectrogramOptions::default()
+    };
+    assert!(!SpectrogramOptions { hop: 0, ..options }.is_valid());
+    let mut analyzer = ColumnAnalyzer::new(options, 48000);
+
+    // a full scale sine at the frequency of bin 64
+    #[allow(clippy::cast_precision_loss)]
+    let frames = (0..1024)
+        .map(|i| (2. * PI * 64. * i as f32 / 1024.).sin())
+        .collect::<Vec<_>>();
+    let mut column = Vec::new();
+    analyzer.analyze(&frames, &mut column);
+    assert_eq!(column.len(), 513);
+    assert!(column[64].abs() < 0.1, "{}", column[64]);
+    assert!(column[200] < -60.);
+}
+
+#[test]
+fn test_generate() {
+    const SAMPLE_RATE: u32 = 48000;
+    const N_FRAMES: usize = 48000;
+
+    let dir = testdir!();
+    let path = dir.join("sine.wav");
+    let mut writer = WavWriter::create(
+        &path,
+        WavSpec {
+            n_channels: 2,
+            sample_rate: SAMPLE_RATE,
+            sample_format: SampleFormat::Int16,
+        },
+    )
+    .unwrap();
+    #[allow(clippy::cast_precision_loss)]
+    let samples = (0..N_FRAMES)
+        .flat_map(|i| {
+            let x = 0.5 * (2. * PI * 1000. * i as f32 / SAMPLE_RATE as f32).sin();
+            [x, x]
+        })
+        .collect::<Vec<_>>();
+    writer.write_samples(&samples).unwrap();
+    writer.finalize().unwrap();
+
+    let mut generator = SpectrogramGenerator::new();
+    assert!(generator
+        .set_options(SpectrogramOptions {
+            fft_size: 1000,
+            ..SpectrogramOptions::default()
+        })
+        .is_err());
+    generator
+        .set_options(SpectrogramOptions {
+            fft_size: 1024,
+            hop: 480,
+            scale: FrequencyScale::Mel,
+            n_bands: 40,
+            ..SpectrogramOptions::default()
+        })
+        .unwrap();
+    generator.set_source(Some(path));
+
+    let info = generator.prepare_spectrogram().unwrap();
+    assert_eq!(info.n_columns, Some(100));
+    assert_eq!(info.n_bins, 40);
+    assert_eq!(info.sample_rate, SAMPLE_RATE);
+
+    let (tx, rx) = channel();
+    generator
+        .request_spectrogram(move |data| tx.send(data.len()).unwrap())
+        .unwrap();
+    // the sender is dropped when the generation is done
+    let mut n_bytes = 0;
+    while let Ok(len) = rx.recv_timeout(Duration::from_secs(3)) {
+        n_bytes += len;
+    }
+    assert_eq!(n_bytes, 100 * 40 * size_of::<f32>());
+}
@@ -0,0 +1,188 @@
+#[cfg(test)]
+mod tests;
+
+use std::f32::consts::PI;
+
+/// Duration of an analysis frame in seconds.
+const FRAME_DURATION: f32 = 0.04;
+/// The step of the candidate frames compared when searching for the best alignment.
+const CORRELATION_STEP: usize = 2;
+
+/// Changes the tempo of interleaved samples without changing the pitch,
+/// by WSOLA (waveform similarity based overlap-add).
+///
+/// Samples are processed in chunks of any size, like [`super::resampler::Resampler`].
+pub struct TimeStretcher {
+    n_channels: usize,
+    /// The length of an analysis frame in frames.
+    frame_len: usize,
+    /// The synthesis hop, i.e. half of `frame_len`.
+    hop: usize,
+    /// How far an analysis frame may be moved from its nominal position to align it, in frames.
+    tolerance: usize,
+    window: Vec<f32>,
+    /// The number of input frames per output frame.
+    tempo: f64,
+    /// The input samples not consumed yet.
+    buffer: Vec<f32>,
+    /// The nominal position of the next analysis frame in `buffer`, in frames.
+    pos: f64,
+    /// The position in `buffer` of the natural continuation of the last analysis frame,
+    /// which the next analysis frame is aligned to.
+    natural_pos: Option<usize>,
+    /// The overlap-added output of `frame_len` frames, of which the first `hop` frames are complete.
+    output_buffer: Vec<f32>,
+}
+
+impl TimeStretcher {
+    #[allow(clippy::cast_possible_truncation)]
+    #[allow(clippy::cast_precision_loss)]
+    #[allow(clippy::cast_sign_loss)]
+    pub fn new(n_channels: usize, sample_rate: u32, tempo: f64) -> Self {
+        let hop = ((sample_rate as f32 * FRAME_DURATION / 2.) as usize).max(1);
+        let frame_len = hop * 2;
+        // periodic Hann window, which sums to 1 when overlapped by half
+        let window = (0..frame_len)
+            .map(|i| 0.5 - 0.5 * (2. * PI * i as f32 / frame_len as f32).cos())
+            .collect();
+
+        Self {
+            n_channels,
+            frame_len,
+            hop,
+            tolerance: frame_len / 4,
+            window,
+            tempo,
+            buffer: Vec::new(),
+            pos: 0.,
+            natural_pos: None,
+            output_buffer: vec![0.; frame_len * n_channels],
+        }
+    }
+
+    pub fn set_tempo(&mut self, tempo: f64) {
+        self.tempo = tempo;
+    }
+
+    /// Stretch `input` and append the stretched samples to `output`.
+    #[allow(clippy::cast_possible_truncation)]
+    #[allow(clippy::cast_sign_loss)]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn process(&mut self, input: &[f32], output: &mut Vec<f32>) {
+        self.buffer.extend_from_slice(input);
+
+        loop {
+            let n_frames = self.buffer.len() / self.n_channels;
+            let nominal_pos = self.pos.round() as usize;
+            let first_candidate = nominal_pos.saturating_sub(self.tolerance);
+            let last_candidate = nominal_pos + self.tolerance;
+
+            // wait until all candidate frames and the natural continuation are available
+            let required = last_candidate.max(self.natural_pos.unwrap_or(0)) + self.frame_len;
+            if required > n_frames {
+                break;
+            }
+
+            let frame_pos = match self.natural_pos {
+                Some(natural_pos) => {
+                    self.find_best_candidate(first_candidate, last_candidate, natural_pos)
+                }
+                None => nominal_pos,
+            };
+            self.overlap_add(frame_pos, output);
+
+            self.natural_pos = Some(frame_pos + self.hop);
+            self.pos += self.hop as f64 * self.tempo;
+
+            // drop the frames not needed anymore
+            let consumed = (self.pos.round() as usize)
+                .saturating_sub(self.tolerance)
+                .min(frame_pos + self.hop);
+            self.buffer.drain(..consumed * self.n_channels);
+            self.pos -= consumed as f64;
+            self.natural_pos = self.natural_pos.map(|pos| pos - consumed);
+        }
+    }
+
+    /// Stretch the remaining input samples at the end of the stream, and append them to `output`.
+    #[allow(clippy::cast_precision_loss)]
+    pub fn flush(&mut self, output: &mut Vec<f32>) {
+        if self.buffer.is_empty() && self.natural_pos.is_none() {
+            // nothing processed since the last reset
+            return;
+        }
+
+        // pad with silence until all remaining input frames have been overlap-added
+        let padding = vec![0.; (self.frame_len + self.tolerance * 2) * self.n_channels];
+        let mut n_remaining = self.buffer.len() / self.n_channels;
+        while self.pos < n_remaining as f64 {
+            let n_buffered = self.buffer.len() + padding.len();
+            let n_output = output.len();
+            self.process(&padding, output);
+            if output.len() == n_output {
+                break;
+            }
+            n_remaining =
+                n_remaining.saturating_sub((n_buffered - self.buffer.len()) / self.n_channels);
+        }
+        output.extend_from_slice(&self.output_buffer[..self.hop * self.n_channels]);
+
+        self.reset();
+    }
+
+    /// Discard all buffered samples, e.g. after seeking.
+    pub fn reset(&mut self) {
+        self.buffer.clear();
+        self.pos = 0.;
+        self.natural_pos = None;
+        self.output_buffer.fill(0.);
+    }
+
+    /// Find the candidate frame most similar to the natural continuation of the last frame,
+    /// by cross-correlation of the overlapping part of the channels summed.
+    fn find_best_candidate(&self, first: usize, last: usize, natural_pos: usize) -> usize {
+        let mono = |pos: usize| {
+            self.buffer[pos * self.n_channels..(pos + 1) * self.n_channels]
+                .iter()
+                .sum::<f32>()
+        };
+        let natural = (0..self.hop)
+            .step_by(CORRELATION_STEP)
+            .map(|i| mono(natural_pos + i))
+            .collect::<Vec<_>>();
+
+        let correlation = |candidate: usize| {
+            natural
+                .iter()
+                .enumerate()
+                .map(|(i, x)| x * mono(candidate + i * CORRELATION_STEP))
+                .sum::<f32>()
+        };
+
+        // prefer the natural continuation, e.g. for silence
+        let mut best = natural_pos.clamp(first, last);
+        let mut best_correlation = correlation(best);
+        for candidate in first..=last {
+            let correlation = correlation(candidate);
+            if correlation > best_correlation {
+                best = candidate;
+                best_correlation = correlation;
+            }
+        }
+        best
+    }
+
+    /// Overlap-add the analysis frame at `frame_pos`, and append the completed frames to `output`.
+    fn overlap_add(&mut self, frame_pos: usize, output: &mut Vec<f32>) {
+        let frame = &self.buffer
+            [frame_pos * self.n_channels..(frame_pos + self.frame_len) * self.n_channels];
+        for (i, (acc, x)) in self.output_buffer.iter_mut().zip(frame).enumerate() {
+            *acc += self.window[i / self.n_channels] * x;
+        }
+
+        let hop_samples = self.hop * self.n_channels;
+        output.extend_from_slice(&self.output_buffer[..hop_samples]);
+        self.output_buffer.copy_within(hop_samples.., 0);
+        self.output_buffer[self.frame_len * self.n_channels - hop_samples..].fill(0.);
+    }
+}
@@ -0,0 +1,94 @@
+use super::{TimeStretcher, FRAME_DURATION};
+
+use std::f32::consts::PI;
+
+use test_log::test;
+
+const SAMPLE_RATE: u32 = 48000;
+
+/// Stretch a stereo sine of one second at `tempo` in chunks of `chunk_frames` frames.
+#[allow(clippy::cast_precision_loss)]
+fn stretch_sine(freq: f32, tempo: f64, chunk_frames: usize) -> Vec<f32> {
+    let input = (0..SAMPLE_RATE)
+        .flat_map(|i| {
+            let x = 0.5 * (2. * PI * freq * i as f32 / SAMPLE_RATE as f32).sin();
+            [x, x]
+        })
+        .collect::<Vec<_>>();
+
+    let mut stretcher = TimeStretcher::new(2, SAMPLE_RATE, tempo);
+    let mut output = Vec::new();
+    for chunk in input.chunks(chunk_frames * 2) {
+        stretcher.process(chunk, &mut output);
+    }
+    stretcher.flush(&mut output);
+    output
+}
+
+/// Estimate the frequency of the left channel from its zero crossings, away from the edges.
+#[allow(clippy::cast_precision_loss)]
+fn estimate_freq(output: &[f32]) -> f32 {
+    let left = output.iter().step_by(2).copied().collect::<Vec<_>>();
+    let middle = &left[left.len() / 4..left.len() * 3 / 4];
+    let n_crossings = middle
+        .windows(2)
+        .filter(|pair| (pair[0] < 0.) != (pair[1] < 0.))
+        .count();
+    n_crossings as f32 / 2. / (middle.len() as f32 / SAMPLE_RATE as f32)
+}
+
+#[test]
+#[allow(
+    clippy::cast_precision_loss,
+    clippy::cast_possible_truncation,
+    clippy::cast_sign_loss
+)]
+fn test_tempo() {
+    for tempo in [0.5, 0.8, 1.25, 2.] {
+        let output = stretch_sine(440., tempo, 512);
+
+        // the duration is divided by the tempo, give or take the analysis frames padded at the end
+        let n_frames = output.len() / 2;
+        let expected = (f64::from(SAMPLE_RATE) / tempo) as usize;
+        let tolerance = (3. * FRAME_DURATION * SAMPLE_RATE as f32) as usize;
+        assert!(
+            n_frames.abs_diff(expected) <= tolerance,
+            "{tempo}: {n_frames}"
+        );
+
+        // the pitch is kept
+        let freq = estimate_freq(&output);
+        assert!((freq - 440.).abs() < 440. * 0.02, "{tempo}: {freq}");
+        let peak = output.iter().fold(0f32, |peak, x| peak.max(x.abs()));
+        assert!((peak - 0.5).abs() < 0.05, "{tempo}: {peak}");
+    }
+}
+
+#[test]
+fn test_chunk_size() {
+    let output = stretch_sine(440., 1.5, 512);
+    assert_eq!(stretch_sine(440., 1.5, 7), output);
+    assert_eq!(stretch_sine(440., 1.5, 48000), output);
+}
+
+#[test]
+fn test_reset() {
+    let input = vec![0.5; 4800 * 2];
+    let mut stretcher = TimeStretcher::new(2, SAMPLE_RATE, 2.);
+    let mut stale = Vec::new();
+    stretcher.process(&input, &mut stale);
+
+    // nothing of the input before the reset is output after it
+    stretcher.reset();
+    let mut output = Vec::new();
+    stretcher.flush(&mut output);
+    assert!(output.is_empty());
+
+    // nor after the end of the stream, e.g. when the end is read again
+    stretcher.process(&input, &mut output);
+    stretcher.flush(&mut output);
+    let n_output = output.len();
+    stretcher.process(&[], &mut output);
+    stretcher.flush(&mut output);
+    assert_eq!(output.len(), n_output);
+}
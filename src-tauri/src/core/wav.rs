@@ -0,0 +1,135 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Seek, SeekFrom, Write};
+use std::path::Path;
+
+use serde::Deserialize;
+
+/// Size of the RIFF header and the `fmt ` chunk, i.e. the offset of the samples in the file.
+const HEADER_SIZE: u32 = 44;
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum SampleFormat {
+    Int16,
+    Int24,
+    Float32,
+}
+
+impl SampleFormat {
+    fn bytes_per_sample(self) -> u16 {
+        match self {
+            Self::Int16 => 2,
+            Self::Int24 => 3,
+            Self::Float32 => 4,
+        }
+    }
+
+    fn format_tag(self) -> u16 {
+        match self {
+            Self::Int16 | Self::Int24 => 1, // WAVE_FORMAT_PCM
+            Self::Float32 => 3,             // WAVE_FORMAT_IEEE_FLOAT
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct WavSpec {
+    pub n_channels: usize,
+    pub sample_rate: u32,
+    pub sample_format: SampleFormat,
+}
+
+/// Writes interleaved `f32` samples to a WAV file.
+///
+/// The sizes in the header are only valid after [`WavWriter::finalize`] is called.
+pub struct WavWriter<W: Write + Seek> {
+    writer: W,
+    sample_format: SampleFormat,
+    n_data_bytes: u64,
+    buffer: Vec<u8>,
+}
+
+impl WavWriter<BufWriter<File>> {
+    pub fn create(path: &Path, spec: WavSpec) -> io::Result<Self> {
+        Self::new(BufWriter::new(File::create(path)?), spec)
+    }
+}
+
+impl<W: Write + Seek> WavWriter<W> {
+    pub fn new(mut writer: W, spec: WavSpec) -> io::Result<Self> {
+        let n_channels = u16::try_from(spec.n_channels)
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "too many channels"))?;
+        let block_align = n_channels * spec.sample_format.bytes_per_sample();
+        let byte_rate = spec.sample_rate * u32::from(block_align);
+
+        writer.write_all(b"RIFF")?;
+        writer.write_all(&0u32.to_le_bytes())?; // placeholder, written in `finalize`
+        writer.write_all(b"WAVE")?;
+
+        writer.write_all(b"fmt ")?;
+        writer.write_all(&16u32.to_le_bytes())?;
+        writer.write_all(&spec.sample_format.format_tag().to_le_bytes())?;
+        writer.write_all(&n_channels.to_le_bytes())?;
+        writer.write_all(&spec.sample_rate.to_le_bytes())?;
+        writer.write_all(&byte_rate.to_le_bytes())?;
+        writer.write_all(&block_align.to_le_bytes())?;
+        writer.write_all(&(spec.sample_format.bytes_per_sample() * 8).to_le_bytes())?;
+
+        writer.write_all(b"data")?;
+        writer.write_all(&0u32.to_le_bytes())?; // placeholder, written in `finalize`
+
+        Ok(Self {
+            writer,
+            sample_format: spec.sample_format,
+            n_data_bytes: 0,
+            buffer: Vec::new(),
+        })
+    }
+
+    /// Write interleaved samples. Samples out of `[-1.0, 1.0]` are clipped for integer formats.
+    #[allow(clippy::cast_possible_truncation)]
+    pub fn write_samples(&mut self, samples: &[f32]) -> io::Result<()> {
+        self.buffer.clear();
+        for &sample in samples {
+            match self.sample_format {
+                SampleFormat::Int16 => {
+                    let sample = (sample.clamp(-1., 1.) * f32::from(i16::MAX)).round() as i16;
+                    self.buffer.extend_from_slice(&sample.to_le_bytes());
+                }
+                SampleFormat::Int24 => {
+                    let sample = (sample.clamp(-1., 1.) * 8_388_607.).round() as i32;
+                    self.buffer.extend_from_slice(&sample.to_le_bytes()[..3]);
+                }
+                SampleFormat::Float32 => {
+                    self.buffer.extend_from_slice(&sample.to_le_bytes());
+                }
+            }
+        }
+
+        self.writer.write_all(&self.buffer)?;
+        self.n_data_bytes += self.buffer.len() as u64;
+        Ok(())
+    }
+
+    /// Write the sizes into the header and flush the writer.
+    pub fn finalize(mut self) -> io::Result<()> {
+        let too_large = || io::Error::new(io::ErrorKind::InvalidData, "data too large for WAV");
+        let data_size = u32::try_from(self.n_data_bytes).map_err(|_| too_large())?;
+        // chunks are padded to an even size
+        let padding = data_size % 2;
+        let riff_size = (HEADER_SIZE - 8 + padding)
+            .checked_add(data_size)
+            .ok_or_else(too_large)?;
+
+        if padding == 1 {
+            self.writer.write_all(&[0])?;
+        }
+
+        self.writer.seek(SeekFrom::Start(4))?;
+        self.writer.write_all(&riff_size.to_le_bytes())?;
+        self.writer
+            .seek(SeekFrom::Start(u64::from(HEADER_SIZE) - 4))?;
+        self.writer.write_all(&data_size.to_le_bytes())?;
+        self.writer.flush()
+    }
+}
@@ -1,17 +1,27 @@
-use super::player::get_format_reader;
+mod cache;
+mod mipmap;
+#[cfg(test)]
+mod tests;
+
+pub use cache::WaveformCache;
+use mipmap::BucketBuilder;
+pub use mipmap::{Bucket, Mipmap};
+
+use super::analysis::CLIP_LEVEL;
+use super::database::FileInfo;
+use super::decoder::SampleDecoder;
+use super::EntryId;
 
 use log::{debug, info, warn};
+use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
 use std::slice::from_raw_parts;
-use std::sync::atomic::AtomicBool;
+use std::sync::atomic::{AtomicBool, AtomicU64};
 use std::sync::{Arc, Mutex};
 use std::thread::spawn;
-use symphonia::core::codecs::audio::AudioDecoderOptions;
-use symphonia::core::codecs::CodecParameters;
-
-use symphonia::core::formats::{FormatReader, Track, TrackType};
 use thiserror::Error;
 
+/// The number of buckets of each channel sent at once.
 const BATCH_SAMPLES: usize = 1024;
 const SAMPLING_STEP: usize = 512;
 
@@ -19,220 +29,429 @@ const SAMPLING_STEP: usize = 512;
 pub enum Error {
     #[error("source not set")]
     SourceNotSet,
-    #[error("tracks not found for source: {0}")]
-    TracksNotFound(String),
     #[error(transparent)]
-    Symphonia(#[from] symphonia::core::errors::Error),
-    #[error("codec parameters missing")]
-    CodecParamsMissing,
+    Decoder(#[from] super::decoder::Error),
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WaveformOptions {
+    /// Whether the waveform is generated for each channel, rather than for the channels mixed down.
+    pub per_channel: bool,
+}
+
+impl WaveformOptions {
+    /// The number of channels of the waveform of a source of `n_channels` channels.
+    fn n_lanes(self, n_channels: usize) -> usize {
+        if self.per_channel {
+            n_channels
+        } else {
+            1
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WaveformInfo {
+    /// The number of buckets of each channel,
+    /// or `None` if the number of frames of the source is unknown until it is decoded.
+    pub n_buckets: Option<u32>,
+    /// The number of channels of the waveform, 1 unless generated per channel.
+    pub n_channels: usize,
+    pub sample_rate: u32,
+    /// The number of frames of each bucket, for estimating the number of buckets if unknown.
+    pub frames_per_bucket: usize,
 }
 
 pub struct WaveformGenerator {
     source_path: Arc<Mutex<Option<PathBuf>>>,
+    /// The entry of the source, whose waveform is cached.
+    entry_id: Option<EntryId>,
+    cache: Option<WaveformCache>,
+    options: WaveformOptions,
+    /// The complete waveform of the source and the file it was generated from, once generated.
+    mipmap: Arc<Mutex<Option<(FileInfo, Arc<Mipmap>)>>>,
+    /// Incremented whenever the mipmap is reset, so that a mipmap loaded meanwhile is not kept.
+    generation: Arc<AtomicU64>,
     reset: Arc<AtomicBool>,
 }
 
+/// The source of the waveform and the options it is read with, taken from the generator,
+/// so that its range is decoded without locking the generator.
+pub struct WaveformSource {
+    path: PathBuf,
+    entry_id: Option<EntryId>,
+    cache: Option<WaveformCache>,
+    options: WaveformOptions,
+    mipmap: Arc<Mutex<Option<(FileInfo, Arc<Mipmap>)>>>,
+    /// The generation of the mipmap when taken, to tell if the source has been changed since.
+    generation: u64,
+    current_generation: Arc<AtomicU64>,
+}
+
 impl WaveformGenerator {
     pub fn new() -> Self {
         Self {
             source_path: Arc::new(None.into()),
+            entry_id: None,
+            cache: None,
+            options: WaveformOptions::default(),
+            mipmap: Arc::new(None.into()),
+            generation: Arc::new(0.into()),
             reset: Arc::new(false.into()),
         }
     }
 
-    pub fn set_source(&mut self, path: Option<PathBuf>) {
+    /// Set the entry and the path of its file as the source.
+    pub fn set_source(&mut self, source: Option<(EntryId, PathBuf)>) {
         let mut source_path = self.source_path.lock().unwrap();
-        *source_path = path;
+        (self.entry_id, *source_path) = source.unzip();
+        // reset while locked, so that a generation stopped by it never keeps its waveform
+        let mut mipmap = self.mipmap.lock().unwrap();
+        *mipmap = None;
+        self.generation
+            .fetch_add(1, std::sync::atomic::Ordering::Release);
         self.reset.store(true, std::sync::atomic::Ordering::Release);
     }
 
-    fn get_default_track<'reader>(
-        &self,
-        reader: &'reader dyn FormatReader,
-    ) -> Result<&'reader Track, Error> {
-        let track = reader.default_track(TrackType::Audio).ok_or_else(|| {
-            Error::TracksNotFound(
-                self.source_path
-                    .lock()
-                    .unwrap()
-                    .as_ref()
-                    .unwrap()
-                    .to_string_lossy()
-                    .to_string(),
-            )
-        })?;
-        Ok(track)
+    /// Set the cache of the waveforms of the open database, if any.
+    pub fn set_cache(&mut self, cache: Option<WaveformCache>) {
+        self.cache = cache;
+    }
+
+    pub fn get_options(&self) -> WaveformOptions {
+        self.options
     }
 
-    pub fn prepare_waveform(&self) -> Result<u32, Error> {
-        let path = self.source_path.lock().unwrap();
-        let path = path.as_ref().ok_or(Error::SourceNotSet)?;
-        let reader = get_format_reader(path)?;
+    /// Set the options of the waveform, which stops the generation in progress
+    /// as its buckets do not match the new options.
+    pub fn set_options(&mut self, options: WaveformOptions) {
+        if options == self.options {
+            return;
+        }
+        self.options = options;
+        let mut mipmap = self.mipmap.lock().unwrap();
+        *mipmap = None;
+        self.generation
+            .fetch_add(1, std::sync::atomic::Ordering::Release);
+        self.reset.store(true, std::sync::atomic::Ordering::Release);
+    }
 
-        let track = self.get_default_track(reader.as_ref())?;
-        let n_frames = track.num_frames.unwrap();
-        let waveform_samples_num: u32 = (n_frames / SAMPLING_STEP as u64).try_into().unwrap();
-        let data_length = waveform_samples_num;
+    /// Take the source with the options its waveform is read with.
+    pub fn source(&self) -> Result<WaveformSource, Error> {
+        let source_path = self.source_path.lock().unwrap();
+        let path = source_path.clone().ok_or(Error::SourceNotSet)?;
+        Ok(WaveformSource {
+            path,
+            entry_id: self.entry_id,
+            cache: self.cache.clone(),
+            options: self.options,
+            mipmap: self.mipmap.clone(),
+            // read while the path is locked, as it is incremented when the path is set
+            generation: self.generation.load(std::sync::atomic::Ordering::Acquire),
+            current_generation: self.generation.clone(),
+        })
+    }
 
-        Ok(data_length)
+    /// Get the number of buckets and channels of the waveform of the source.
+    ///
+    /// The number of buckets is only known beforehand if the number of frames of the source is,
+    /// otherwise the end of the waveform is marked by [`Self::request_waveform`].
+    pub fn prepare_waveform(&self) -> Result<WaveformInfo, Error> {
+        let source = self.source()?;
+        if let Some(mipmap) = source.get_mipmap() {
+            return Ok(WaveformInfo {
+                n_buckets: mipmap.n_buckets().try_into().ok(),
+                n_channels: mipmap.n_channels(),
+                sample_rate: mipmap.sample_rate(),
+                frames_per_bucket: SAMPLING_STEP,
+            });
+        }
+        let decoder = SampleDecoder::open(&source.path)?;
+        let spec = decoder.spec();
+
+        Ok(WaveformInfo {
+            n_buckets: spec
+                .n_frames
+                .and_then(|n_frames| (n_frames / SAMPLING_STEP as u64).try_into().ok()),
+            n_channels: self.options.n_lanes(spec.n_channels),
+            sample_rate: spec.sample_rate,
+            frames_per_bucket: SAMPLING_STEP,
+        })
     }
 
+    /// Send the buckets of the waveform of the source in batches as they are generated,
+    /// or at once if it is generated before or cached, and cache it once complete.
+    ///
+    /// Each bucket is sent as its [`Bucket::N_VALUES`] values,
+    /// and the buckets of the channels are interleaved.
+    /// The waveform is followed by empty data once complete, or stopped by an error,
+    /// so that a number of buckets estimated beforehand can be corrected.
     pub fn request_waveform<F>(&self, on_data_available: F) -> Result<(), Error>
     where
         F: Fn(&[u8]) + Send + 'static,
     {
-        let mut reader = get_format_reader(
-            self.source_path
-                .lock()
-                .unwrap()
-                .as_ref()
-                .ok_or(Error::SourceNotSet)?,
-        )?;
+        let source = self.source()?;
         self.reset
             .store(false, std::sync::atomic::Ordering::Release);
 
-        let track = self.get_default_track(reader.as_ref())?;
-        let track_id = track.id;
+        if let Some(mipmap) = source.get_mipmap() {
+            on_data_available(as_bytes(&to_values(mipmap.buckets())));
+            on_data_available(&[]);
+            return Ok(());
+        }
 
-        let params = track
-            .codec_params
-            .as_ref()
-            .and_then(CodecParameters::audio)
-            .ok_or(Error::CodecParamsMissing)?;
-        let n_frames = track.num_frames.unwrap();
-        let n_channels = params.channels.as_ref().unwrap().count();
-        let src_samples_per_batch = n_channels * SAMPLING_STEP * BATCH_SAMPLES;
-        let waveform_samples_num: u32 = (n_frames / SAMPLING_STEP as u64).try_into().unwrap();
+        let path = source.path;
+        // read before decoding, so that a file modified meanwhile is not cached as up to date
+        let file_info = FileInfo::read(&path).ok();
+        let mut decoder = SampleDecoder::open(&path)?;
+        let cache = self.cache.clone().zip(self.entry_id);
+        let options = self.options;
 
-        debug!("n_frames: {n_frames}");
-        debug!("n_channels: {n_channels}");
-        debug!("waveform_samples_num: {waveform_samples_num}");
+        let n_channels = decoder.spec().n_channels;
+        let n_lanes = self.options.n_lanes(n_channels);
+        let sample_rate = decoder.spec().sample_rate;
 
-        let mut decoder = symphonia::default::get_codecs()
-            .make_audio_decoder(params, &AudioDecoderOptions::default())?;
+        debug!("n_frames: {:?}", decoder.spec().n_frames);
+        debug!("n_channels: {n_channels}, n_lanes: {n_lanes}");
 
         let reset = self.reset.clone();
+        let mipmap = self.mipmap.clone();
         spawn(move || {
             debug!("start waveform generation");
 
-            let n_channels_i32 = i32::try_from(n_channels).unwrap();
-            let n_samples = usize::try_from(n_frames).unwrap() * n_channels;
-            let mut samples = vec![0i16; n_samples];
-            let mut available_samples_head = 0; // The index of the next sample to be processed.
-            let mut available_samples_tail = 0; // The index of the last sample to be processed.
+            // the buckets of each channel not complete yet
+            let mut builders = vec![BucketBuilder::default(); n_lanes];
+            let mut n_bucket_frames = 0;
+            // the buckets not sent yet
+            let mut batch = Vec::<Bucket>::new();
+            // the whole waveform, to be kept and cached
+            let mut buckets = Vec::<Bucket>::new();
+            let mut complete = false;
 
-            // Decode all packets, ignoring all decode errors.
             loop {
                 if reset.load(std::sync::atomic::Ordering::Acquire) {
                     info!("Waveform generator: source reset");
                     break;
                 }
 
-                // loop for packets
-                let end = match reader.next_packet() {
+                let end = match decoder.next_samples() {
+                    Ok(Some(samples)) => {
+                        for frame in samples.chunks_exact(n_channels) {
+                            push_frame(&mut builders, frame);
+                            n_bucket_frames += 1;
+                            if n_bucket_frames == SAMPLING_STEP {
+                                batch.extend(builders.iter_mut().map(BucketBuilder::take));
+                                n_bucket_frames = 0;
+                            }
+                        }
+                        false
+                    }
+                    Ok(None) => {
+                        // end of stream
+                        complete = true;
+                        true
+                    }
                     Err(err) => {
                         // about to break
                         warn!("Waveform generator: stopped generating waveform, because of unrecoverable error: {err}");
                         true
                     }
+                };
 
-                    Ok(Some(packet)) => {
-                        // If the packet does not belong to the selected track, skip over it.
-                        if packet.track_id() != track_id {
-                            continue;
-                        }
-
-                        // Decode the packet into audio samples.
-                        match decoder.decode(&packet) {
-                            Ok(audio_buf) => {
-                                let n_new_samples = audio_buf.samples_interleaved();
-
-                                audio_buf.copy_to_slice_interleaved(
-                                    &mut samples[available_samples_tail
-                                        ..available_samples_tail + n_new_samples],
-                                );
+                // send the buckets in batches, all remaining buckets if about to break
+                if batch.len() >= BATCH_SAMPLES * n_lanes || (end && !batch.is_empty()) {
+                    on_data_available(as_bytes(&to_values(&batch)));
+                    buckets.append(&mut batch);
+                }
 
-                                available_samples_tail += n_new_samples;
+                if end {
+                    on_data_available(&[]);
+                    break;
+                }
+            }
+
+            let Some(file_info) = file_info.filter(|file_info| {
+                complete && FileInfo::read(&path).is_ok_and(|info| info == *file_info)
+            }) else {
+                debug!("waveform generation done");
+                return;
+            };
+
+            let generated = Arc::new(Mipmap::new(sample_rate, n_lanes, buckets));
+            {
+                let mut mipmap = mipmap.lock().unwrap();
+                if !reset.load(std::sync::atomic::Ordering::Acquire) {
+                    *mipmap = Some((file_info, generated.clone()));
+                }
+            }
+            if let Some((cache, entry_id)) = cache {
+                if let Err(err) =
+                    cache.store(entry_id, file_info, SAMPLING_STEP, options, &generated)
+                {
+                    warn!("Waveform generator: failed to cache waveform: {err}");
+                }
+            }
 
-                                false // continue
-                            }
+            debug!("waveform generation done");
+        });
 
-                            Err(symphonia::core::errors::Error::IoError(err)) => {
-                                // The packet failed to decode due to an IO error, skip the packet.
-                                warn!("Waveform generator: skipped packet, because of IO error: {err}");
-                                false // continue
-                            }
-                            Err(symphonia::core::errors::Error::DecodeError(err)) => {
-                                // The packet failed to decode due to invalid data, skip the packet.
-                                warn!("Waveform generator: skipped packet, because of decode error: {err}");
-                                false // continue
-                            }
+        Ok(())
+    }
 
-                            Err(err) => {
-                                // about to break
-                                warn!("Waveform generator: stopped generating wavefrom, because of unrecoverable error: {err}");
-                                true
-                            }
-                        }
-                    }
+    /// Split the waveform of the source from `start` to `end` in seconds into `n_buckets` buckets,
+    /// as by [`WaveformSource::request_range`].
+    pub fn request_waveform_range(
+        &self,
+        start: f64,
+        end: f64,
+        n_buckets: usize,
+    ) -> Result<Vec<Bucket>, Error> {
+        self.source()?.request_range(start, end, n_buckets)
+    }
+}
 
-                    Ok(None) => true, // end of stream
-                };
+impl WaveformSource {
+    /// Get the complete waveform of the source, generated before or cached,
+    /// if the file has not been modified since.
+    fn get_mipmap(&self) -> Option<Arc<Mipmap>> {
+        let file_info = FileInfo::read(&self.path).ok()?;
+        if let Some((info, mipmap)) = self.mipmap.lock().unwrap().as_ref() {
+            if *info == file_info && self.is_current() {
+                return Some(mipmap.clone());
+            }
+        }
 
-                if reset.load(std::sync::atomic::Ordering::Acquire) {
-                    info!("Waveform generator: source reset");
-                    break;
-                }
+        let cached = Arc::new(self.cache.as_ref()?.load(
+            self.entry_id?,
+            &self.path,
+            SAMPLING_STEP,
+            self.options,
+        )?);
+        debug!("cached waveform found, len: {}", cached.buckets().len());
+        let mut mipmap = self.mipmap.lock().unwrap();
+        // checked while locked, as the mipmap is reset while locked
+        if self.is_current() {
+            *mipmap = Some((file_info, cached.clone()));
+        }
+        Some(cached)
+    }
 
-                // consume available samples
-                loop {
-                    let available_samples_num = if end {
-                        // about to break, consume all remaining samples
-                        available_samples_tail.saturating_sub(available_samples_head)
-                    } else {
-                        // decoding not ended, whether there are enough data for a batch
-                        if available_samples_tail >= available_samples_head + src_samples_per_batch
-                        {
-                            src_samples_per_batch
-                        } else {
-                            0
-                        }
-                    };
+    /// Whether the source and the options have not been changed since taken.
+    fn is_current(&self) -> bool {
+        self.current_generation
+            .load(std::sync::atomic::Ordering::Acquire)
+            == self.generation
+    }
 
-                    if available_samples_num == 0 {
-                        break;
-                    }
+    /// Split the waveform of the source from `start` to `end` in seconds into `n_buckets` buckets
+    /// of each channel, at most one per frame, interleaved as by [`WaveformGenerator::request_waveform`].
+    ///
+    /// The buckets are merged from the complete waveform if they are coarse enough,
+    /// or decoded from the region otherwise, e.g. when zoomed in.
+    #[allow(
+        clippy::cast_possible_truncation,
+        clippy::cast_precision_loss,
+        clippy::cast_sign_loss
+    )]
+    pub fn request_range(
+        &self,
+        start: f64,
+        end: f64,
+        n_buckets: usize,
+    ) -> Result<Vec<Bucket>, Error> {
+        let to_frame = |sample_rate: u32, secs: f64| (secs.max(0.) * f64::from(sample_rate)) as u64;
+
+        if let Some(mipmap) = self.get_mipmap() {
+            let sample_rate = mipmap.sample_rate();
+            let buckets = mipmap.range(
+                to_frame(sample_rate, start),
+                to_frame(sample_rate, end),
+                n_buckets,
+            );
+            if let Some(buckets) = buckets {
+                return Ok(buckets);
+            }
+        }
 
-                    #[allow(clippy::cast_possible_truncation)]
-                    #[allow(clippy::cast_precision_loss)]
-                    let data: Vec<f32> = samples
-                        [available_samples_head..available_samples_head + available_samples_num]
-                        // average samples of all channels per frame
-                        .chunks_exact(n_channels)
-                        .map(|chunk| {
-                            let sum: i32 = chunk.iter().map(|x| i32::from(*x)).sum();
-                            (sum / n_channels_i32) as i16
-                        })
-                        .collect::<Vec<_>>()
-                        // get min and max for each chunk
-                        .chunks_exact(SAMPLING_STEP)
-                        .map(|chunk| {
-                            let max = chunk.iter().map(|x| (i32::from(*x)).abs()).max().unwrap();
-                            max as f32 / f32::from(i16::MAX)
-                        })
-                        .collect();
-
-                    on_data_available(unsafe {
-                        from_raw_parts(data.as_ptr().cast::<u8>(), data.len() * size_of::<f32>())
-                    });
-
-                    available_samples_head += src_samples_per_batch;
+        let mut decoder = SampleDecoder::open(&self.path)?;
+        let n_channels = decoder.spec().n_channels;
+        let n_lanes = self.options.n_lanes(n_channels);
+        let sample_rate = decoder.spec().sample_rate;
+        let start = to_frame(sample_rate, start);
+        let end = to_frame(sample_rate, end).max(start);
+        let n_frames = end - start;
+        let n_buckets = n_buckets.min(n_frames.try_into().unwrap_or(usize::MAX));
+        if n_buckets == 0 {
+            return Ok(Vec::new());
+        }
+        debug!("decode waveform range: {start}..{end}");
+
+        let mut buckets = Vec::with_capacity(n_buckets * n_lanes);
+        if decoder
+            .spec()
+            .n_frames
+            .is_some_and(|n_frames| start >= n_frames)
+        {
+            // silence beyond the end of the file
+            buckets.resize(n_buckets * n_lanes, Bucket::SILENCE);
+            return Ok(buckets);
+        }
+        if start > 0 {
+            decoder.seek(start)?;
+        }
+        let mut builders = vec![BucketBuilder::default(); n_lanes];
+        // the frame from the start
+        let mut frame_index = 0;
+        'decode: while let Some(samples) = decoder.next_samples()? {
+            for frame in samples.chunks_exact(n_channels) {
+                if frame_index == n_frames {
+                    break 'decode;
                 }
-            } // loop
-
-            debug!("waveform generation done");
-        });
+                // the buckets are split evenly
+                let index = (frame_index * n_buckets as u64 / n_frames) as usize;
+                while buckets.len() < index * n_lanes {
+                    buckets.extend(builders.iter_mut().map(BucketBuilder::take));
+                }
+                push_frame(&mut builders, frame);
+                frame_index += 1;
+            }
+        }
+        // silence beyond the end of the file
+        while buckets.len() < n_buckets * n_lanes {
+            buckets.extend(builders.iter_mut().map(BucketBuilder::take));
+        }
+        Ok(buckets)
+    }
+}
 
-        Ok(())
+/// Push the samples of a frame to the bucket of each channel,
+/// or their average to the only bucket if the channels are mixed down,
+/// counting the overs.
+#[allow(clippy::cast_precision_loss)]
+fn push_frame(builders: &mut [BucketBuilder], frame: &[f32]) {
+    let is_over = |sample: f32| sample.abs() >= CLIP_LEVEL;
+    if let [builder] = builders {
+        // an over of any channel, even if the channels cancel out
+        builder.push(
+            frame.iter().sum::<f32>() / frame.len() as f32,
+            frame.iter().any(|&sample| is_over(sample)),
+        );
+    } else {
+        for (builder, &sample) in builders.iter_mut().zip(frame) {
+            builder.push(sample, is_over(sample));
+        }
     }
 }
+
+fn to_values(buckets: &[Bucket]) -> Vec<f32> {
+    buckets
+        .iter()
+        .flat_map(|bucket| bucket.to_values())
+        .collect()
+}
+
+fn as_bytes(data: &[f32]) -> &[u8] {
+    unsafe { from_raw_parts(data.as_ptr().cast::<u8>(), size_of_val(data)) }
+}
@@ -0,0 +1,144 @@
+use super::{Bucket, Mipmap, WaveformOptions};
+use crate::core::database::{FileInfo, CACHE_DIR};
+use crate::core::EntryId;
+
+use log::{debug, warn};
+use std::fs::{create_dir_all, read, remove_file, rename, write};
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+/// Identifies the cache files, followed by the version of the format.
+const MAGIC: &[u8; 4] = b"SMWF";
+const VERSION: u32 = 4;
+/// The length of the header: the magic, the version, the file size, the file mtime,
+/// the sampling step, whether per channel, the sample rate, the number of channels
+/// and the number of buckets.
+const HEADER_LEN: usize = 4 + 4 + 8 + 8 + 4 + 4 + 4 + 4 + 4;
+const BUCKET_LEN: usize = Bucket::N_VALUES * size_of::<f32>();
+
+/// The waveforms of the entries of a database, cached in its cache folder.
+///
+/// Each waveform is keyed by the size and the modification time of the file it was computed from,
+/// so a waveform of a modified file is never served, even if it has not been invalidated.
+#[derive(Clone, Debug)]
+pub struct WaveformCache {
+    dir: PathBuf,
+}
+
+impl WaveformCache {
+    /// The cache of the database at `base_path`.
+    pub fn new(base_path: &Path) -> Self {
+        Self {
+            dir: base_path.join(CACHE_DIR).join("waveform"),
+        }
+    }
+
+    fn entry_path(&self, entry_id: EntryId) -> PathBuf {
+        self.dir.join(format!("{entry_id}.bin"))
+    }
+
+    /// Load the waveform of the entry computed with `sampling_step` and `options`,
+    /// if it was computed from the file at `path` as it is now.
+    pub fn load(
+        &self,
+        entry_id: EntryId,
+        path: &Path,
+        sampling_step: usize,
+        options: WaveformOptions,
+    ) -> Option<Mipmap> {
+        let file_info = FileInfo::read(path).ok()?;
+        let data = match read(self.entry_path(entry_id)) {
+            Ok(data) => data,
+            Err(err) if err.kind() == ErrorKind::NotFound => return None,
+            Err(err) => {
+                warn!("Failed to read the cached waveform of entry {entry_id}: {err}");
+                return None;
+            }
+        };
+
+        let (header, values) = data.split_at_checked(HEADER_LEN)?;
+        let u32_at = |i: usize| u32::from_le_bytes(header[i..i + 4].try_into().unwrap());
+        let u64_at = |i: usize| u64::from_le_bytes(header[i..i + 8].try_into().unwrap());
+        let i64_at = |i: usize| i64::from_le_bytes(header[i..i + 8].try_into().unwrap());
+        if &header[..4] != MAGIC
+            || u32_at(4) != VERSION
+            || u64_at(8) != file_info.size
+            || i64_at(16) != file_info.modified
+            || u32_at(24) as usize != sampling_step
+            || (u32_at(28) != 0) != options.per_channel
+            || values.len() != u32_at(40) as usize * BUCKET_LEN
+        {
+            debug!("cached waveform of entry {entry_id} is stale");
+            return None;
+        }
+
+        let buckets = values
+            .chunks_exact(BUCKET_LEN)
+            .map(|bucket| {
+                Bucket::from_values(std::array::from_fn(|i| {
+                    let value = &bucket[i * size_of::<f32>()..(i + 1) * size_of::<f32>()];
+                    f32::from_le_bytes(value.try_into().unwrap())
+                }))
+            })
+            .collect();
+        Some(Mipmap::new(u32_at(32), u32_at(36) as usize, buckets))
+    }
+
+    /// Store the waveform of the entry, computed with `sampling_step` and `options` from its file
+    /// as it was when `file_info` was read.
+    pub fn store(
+        &self,
+        entry_id: EntryId,
+        file_info: FileInfo,
+        sampling_step: usize,
+        options: WaveformOptions,
+        mipmap: &Mipmap,
+    ) -> std::io::Result<()> {
+        let buckets = mipmap.buckets();
+        let mut data = Vec::with_capacity(HEADER_LEN + buckets.len() * BUCKET_LEN);
+        data.extend_from_slice(MAGIC);
+        data.extend_from_slice(&VERSION.to_le_bytes());
+        data.extend_from_slice(&file_info.size.to_le_bytes());
+        data.extend_from_slice(&file_info.modified.to_le_bytes());
+        data.extend_from_slice(
+            &u32::try_from(sampling_step)
+                .unwrap_or(u32::MAX)
+                .to_le_bytes(),
+        );
+        data.extend_from_slice(&u32::from(options.per_channel).to_le_bytes());
+        data.extend_from_slice(&mipmap.sample_rate().to_le_bytes());
+        data.extend_from_slice(
+            &u32::try_from(mipmap.n_channels())
+                .unwrap_or(u32::MAX)
+                .to_le_bytes(),
+        );
+        data.extend_from_slice(
+            &u32::try_from(buckets.len())
+                .unwrap_or(u32::MAX)
+                .to_le_bytes(),
+        );
+        for value in buckets.iter().flat_map(|bucket| bucket.to_values()) {
+            data.extend_from_slice(&value.to_le_bytes());
+        }
+
+        // write to a temporary file first, so that a partial file is never loaded
+        create_dir_all(&self.dir)?;
+        let path = self.entry_path(entry_id);
+        let temp_path = path.with_extension("tmp");
+        write(&temp_path, data)?;
+        rename(temp_path, path)?;
+        debug!("cached waveform of entry {entry_id}");
+        Ok(())
+    }
+
+    /// Remove the cached waveform of the entry, e.g. after its file is modified.
+    pub fn invalidate(&self, entry_id: EntryId) {
+        match remove_file(self.entry_path(entry_id)) {
+            Ok(()) => debug!("invalidated cached waveform of entry {entry_id}"),
+            Err(err) if err.kind() == ErrorKind::NotFound => {}
+            Err(err) => {
+                warn!("Failed to invalidate the cached waveform of entry {entry_id}: {err}")
+            }
+        }
+    }
+}
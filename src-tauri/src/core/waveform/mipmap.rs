@@ -0,0 +1,215 @@
+use super::SAMPLING_STEP;
+
+/// The number of buckets of a level merged into one bucket of the next level.
+const LEVEL_FACTOR: usize = 4;
+
+/// The minimum, the maximum and the RMS of the samples of a range of frames,
+/// and the number of overs among them.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Bucket {
+    pub min: f32,
+    pub max: f32,
+    pub rms: f32,
+    pub n_clipped: u32,
+}
+
+impl Bucket {
+    pub const SILENCE: Self = Self {
+        min: 0.,
+        max: 0.,
+        rms: 0.,
+        n_clipped: 0,
+    };
+
+    /// The number of `f32` values of a bucket, as sent to the frontend.
+    pub const N_VALUES: usize = 4;
+
+    #[allow(clippy::cast_precision_loss)]
+    pub fn to_values(self) -> [f32; Self::N_VALUES] {
+        [self.min, self.max, self.rms, self.n_clipped as f32]
+    }
+
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    pub fn from_values(values: [f32; Self::N_VALUES]) -> Self {
+        let [min, max, rms, n_clipped] = values;
+        Self {
+            min,
+            max,
+            rms,
+            n_clipped: n_clipped as u32,
+        }
+    }
+
+    /// Merge buckets of the same number of frames into one.
+    #[allow(clippy::cast_precision_loss)]
+    fn merge(buckets: &[Self]) -> Self {
+        let (min, max, sum_squares, n_clipped) = buckets.iter().fold(
+            (f32::INFINITY, f32::NEG_INFINITY, 0., 0u32),
+            |(min, max, sum_squares, n_clipped), bucket| {
+                (
+                    min.min(bucket.min),
+                    max.max(bucket.max),
+                    sum_squares + bucket.rms * bucket.rms,
+                    n_clipped.saturating_add(bucket.n_clipped),
+                )
+            },
+        );
+        Self {
+            min,
+            max,
+            rms: (sum_squares / buckets.len() as f32).sqrt(),
+            n_clipped,
+        }
+    }
+}
+
+/// Accumulates the samples of a bucket.
+#[derive(Clone, Copy)]
+pub struct BucketBuilder {
+    min: f32,
+    max: f32,
+    sum_squares: f64,
+    n_samples: usize,
+    n_clipped: u32,
+}
+
+impl Default for BucketBuilder {
+    fn default() -> Self {
+        Self {
+            min: f32::INFINITY,
+            max: f32::NEG_INFINITY,
+            sum_squares: 0.,
+            n_samples: 0,
+            n_clipped: 0,
+        }
+    }
+}
+
+impl BucketBuilder {
+    /// Push a sample, which is counted as an over if `clipped`.
+    pub fn push(&mut self, sample: f32, clipped: bool) {
+        self.min = self.min.min(sample);
+        self.max = self.max.max(sample);
+        self.sum_squares += f64::from(sample).powi(2);
+        self.n_samples += 1;
+        self.n_clipped += u32::from(clipped);
+    }
+
+    /// Take the bucket of the samples pushed, or silence if none, and start a new one.
+    #[allow(clippy::cast_possible_truncation, clippy::cast_precision_loss)]
+    pub fn take(&mut self) -> Bucket {
+        let builder = std::mem::take(self);
+        if builder.n_samples == 0 {
+            return Bucket::SILENCE;
+        }
+        Bucket {
+            min: builder.min,
+            max: builder.max,
+            rms: (builder.sum_squares / builder.n_samples as f64).sqrt() as f32,
+            n_clipped: builder.n_clipped,
+        }
+    }
+}
+
+/// The waveform of a whole file at several resolutions, for zooming without decoding it again.
+///
+/// The first level has a bucket per [`SAMPLING_STEP`] frames,
+/// and each next level merges [`LEVEL_FACTOR`] buckets of the previous one.
+/// The buckets of the channels are interleaved as the samples of frames.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Mipmap {
+    sample_rate: u32,
+    n_channels: usize,
+    levels: Vec<Vec<Bucket>>,
+}
+
+impl Mipmap {
+    pub fn new(sample_rate: u32, n_channels: usize, buckets: Vec<Bucket>) -> Self {
+        let n_channels = n_channels.max(1);
+        let mut levels = vec![buckets];
+        while levels.last().unwrap().len() > n_channels {
+            let level = merge_channels(levels.last().unwrap(), n_channels, LEVEL_FACTOR);
+            levels.push(level);
+        }
+        Self {
+            sample_rate,
+            n_channels,
+            levels,
+        }
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    pub fn n_channels(&self) -> usize {
+        self.n_channels
+    }
+
+    /// The number of buckets of each channel of the first level.
+    pub fn n_buckets(&self) -> usize {
+        self.levels[0].len() / self.n_channels
+    }
+
+    /// The buckets of the first level.
+    pub fn buckets(&self) -> &[Bucket] {
+        &self.levels[0]
+    }
+
+    /// Split the frames from `start` to `end` into `n_buckets` buckets of each channel,
+    /// merged from the coarsest level that is fine enough.
+    ///
+    /// Returns `None` if the buckets are finer than the first level.
+    #[allow(clippy::cast_possible_truncation, clippy::cast_precision_loss)]
+    pub fn range(&self, start: u64, end: u64, n_buckets: usize) -> Option<Vec<Bucket>> {
+        let frames_per_bucket = end.saturating_sub(start) as f64 / n_buckets as f64;
+        if n_buckets == 0 || frames_per_bucket < SAMPLING_STEP as f64 {
+            return None;
+        }
+
+        let mut step = SAMPLING_STEP as f64;
+        let mut level = &self.levels[0];
+        for next in &self.levels[1..] {
+            if step * LEVEL_FACTOR as f64 > frames_per_bucket {
+                break;
+            }
+            step *= LEVEL_FACTOR as f64;
+            level = next;
+        }
+
+        let level_len = level.len() / self.n_channels;
+        let mut buckets = Vec::with_capacity(n_buckets * self.n_channels);
+        for i in 0..n_buckets {
+            let from = start as f64 + i as f64 * frames_per_bucket;
+            let to = from + frames_per_bucket;
+            let from = ((from / step).floor() as usize).min(level_len);
+            let to = ((to / step).ceil() as usize).clamp(from, level_len);
+            if from == to {
+                // beyond the end of the file
+                buckets.extend(std::iter::repeat_n(Bucket::SILENCE, self.n_channels));
+            } else {
+                let merged = &level[from * self.n_channels..to * self.n_channels];
+                buckets.extend(merge_channels(merged, self.n_channels, to - from));
+            }
+        }
+        Some(buckets)
+    }
+}
+
+/// Merge every `factor` buckets of each channel of the interleaved buckets.
+fn merge_channels(buckets: &[Bucket], n_channels: usize, factor: usize) -> Vec<Bucket> {
+    buckets
+        .chunks(factor * n_channels)
+        .flat_map(|chunk| {
+            (0..n_channels).map(move |channel| {
+                let channel_buckets = chunk
+                    .iter()
+                    .skip(channel)
+                    .step_by(n_channels)
+                    .copied()
+                    .collect::<Vec<_>>();
+                Bucket::merge(&channel_buckets)
+            })
+        })
+        .collect()
+}
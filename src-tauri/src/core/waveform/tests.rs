@@ -0,0 +1,450 @@
+use super::{Bucket, Mipmap, WaveformCache, WaveformGenerator, WaveformOptions, SAMPLING_STEP};
+use crate::core::analysis::CLIP_LEVEL;
+use crate::core::database::FileInfo;
+use crate::core::wav::{SampleFormat, WavSpec, WavWriter};
+
+use std::f32::consts::PI;
+use std::path::Path;
+use std::sync::mpsc::channel;
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+use test_log::test;
+use testdir::testdir;
+
+const SAMPLE_RATE: u32 = 48000;
+
+/// Write a stereo sine, the right channel scaled by `right_gain` from the left channel.
+fn write_sine(path: &Path, n_frames: usize, right_gain: f32) {
+    let mut writer = WavWriter::create(
+        path,
+        WavSpec {
+            n_channels: 2,
+            sample_rate: SAMPLE_RATE,
+            sample_format: SampleFormat::Int16,
+        },
+    )
+    .unwrap();
+    #[allow(clippy::cast_precision_loss)]
+    let samples = (0..n_frames)
+        .flat_map(|i| {
+            let x = 0.5 * (2. * PI * 1000. * i as f32 / SAMPLE_RATE as f32).sin();
+            [x, x * right_gain]
+        })
+        .collect::<Vec<_>>();
+    writer.write_samples(&samples).unwrap();
+    writer.finalize().unwrap();
+}
+
+/// Request the waveform and collect its buckets until its end is marked by empty data.
+fn collect_waveform(generator: &WaveformGenerator) -> Vec<Bucket> {
+    let (sender, receiver) = channel();
+    generator
+        .request_waveform(move |data| {
+            let values = data
+                .chunks_exact(size_of::<f32>())
+                .map(|value| f32::from_le_bytes(value.try_into().unwrap()))
+                .collect::<Vec<_>>();
+            let buckets = values
+                .chunks_exact(Bucket::N_VALUES)
+                .map(|values| Bucket::from_values(values.try_into().unwrap()))
+                .collect::<Vec<_>>();
+            sender.send(buckets).unwrap();
+        })
+        .unwrap();
+
+    let mut waveform = Vec::new();
+    loop {
+        let buckets = receiver.recv_timeout(Duration::from_secs(3)).unwrap();
+        if buckets.is_empty() {
+            return waveform;
+        }
+        waveform.extend(buckets);
+    }
+}
+
+#[test]
+fn test_cache() {
+    let dir = testdir!();
+    let path = dir.join("sine.wav");
+    write_sine(&path, 4800, 1.);
+
+    let cache = WaveformCache::new(&dir);
+    let options = WaveformOptions::default();
+    let waveform = Mipmap::new(
+        SAMPLE_RATE,
+        1,
+        vec![
+            Bucket {
+                min: -0.25,
+                max: 0.5,
+                rms: 0.125,
+                n_clipped: 0,
+            };
+            3
+        ],
+    );
+    assert_eq!(cache.load(1, &path, SAMPLING_STEP, options), None);
+
+    let file_info = FileInfo::read(&path).unwrap();
+    cache
+        .store(1, file_info, SAMPLING_STEP, options, &waveform)
+        .unwrap();
+    assert_eq!(
+        cache.load(1, &path, SAMPLING_STEP, options),
+        Some(waveform.clone())
+    );
+    assert_eq!(cache.load(2, &path, SAMPLING_STEP, options), None);
+    assert_eq!(cache.load(1, &path, SAMPLING_STEP * 2, options), None);
+    let per_channel = WaveformOptions { per_channel: true };
+    assert_eq!(cache.load(1, &path, SAMPLING_STEP, per_channel), None);
+
+    // stale after the file is modified
+    write_sine(&path, 9600, 1.);
+    assert_eq!(cache.load(1, &path, SAMPLING_STEP, options), None);
+
+    let file_info = FileInfo::read(&path).unwrap();
+    cache
+        .store(1, file_info, SAMPLING_STEP, options, &waveform)
+        .unwrap();
+    assert!(cache.load(1, &path, SAMPLING_STEP, options).is_some());
+    cache.invalidate(1);
+    assert_eq!(cache.load(1, &path, SAMPLING_STEP, options), None);
+    // invalidating a missing waveform is fine
+    cache.invalidate(1);
+}
+
+#[test]
+fn test_generate_cached() {
+    const N_FRAMES: usize = 48000;
+
+    let dir = testdir!();
+    let path = dir.join("sine.wav");
+    write_sine(&path, N_FRAMES, 1.);
+
+    let cache = WaveformCache::new(&dir);
+    let options = WaveformOptions::default();
+    let mut generator = WaveformGenerator::new();
+    generator.set_cache(Some(cache.clone()));
+    generator.set_source(Some((1, path.clone())));
+
+    let n_values = N_FRAMES / SAMPLING_STEP;
+    let info = generator.prepare_waveform().unwrap();
+    assert_eq!(info.n_buckets, Some(u32::try_from(n_values).unwrap()));
+    assert_eq!(info.sample_rate, SAMPLE_RATE);
+    assert_eq!(info.n_channels, 1);
+    let waveform = collect_waveform(&generator);
+    assert_eq!(waveform.len(), n_values);
+    assert!(waveform.iter().all(|bucket| (bucket.max - 0.5).abs() < 0.01
+        && (bucket.min + 0.5).abs() < 0.01
+        && (bucket.rms - 0.5 / 2f32.sqrt()).abs() < 0.01));
+
+    // cached once the generation is complete
+    let start = Instant::now();
+    while cache.load(1, &path, SAMPLING_STEP, options).is_none() {
+        assert!(
+            start.elapsed() < Duration::from_secs(3),
+            "waveform not cached"
+        );
+        sleep(Duration::from_millis(10));
+    }
+    assert_eq!(
+        generator.prepare_waveform().unwrap().n_buckets,
+        info.n_buckets
+    );
+    assert_eq!(collect_waveform(&generator), waveform);
+}
+
+#[test]
+fn test_mipmap() {
+    let buckets = (0..64)
+        .map(|i| {
+            #[allow(clippy::cast_precision_loss)]
+            let max = i as f32 / 64.;
+            Bucket {
+                min: -max,
+                max,
+                rms: max / 2.,
+                n_clipped: 0,
+            }
+        })
+        .collect::<Vec<_>>();
+    let mipmap = Mipmap::new(SAMPLE_RATE, 1, buckets.clone());
+    assert_eq!(mipmap.buckets(), buckets);
+
+    // finer than the first level
+    let n_frames = (64 * SAMPLING_STEP) as u64;
+    assert_eq!(mipmap.range(0, n_frames, 128), None);
+
+    let range = mipmap.range(0, n_frames, 64).unwrap();
+    assert_eq!(range.len(), 64);
+    assert!(range.iter().zip(&buckets).all(|(bucket, expected)| {
+        (bucket.max - expected.max).abs() < f32::EPSILON && (bucket.rms - expected.rms).abs() < 1e-6
+    }));
+
+    // merged from the coarser levels
+    let range = mipmap.range(0, n_frames, 4).unwrap();
+    assert_eq!(range.len(), 4);
+    for (i, bucket) in range.iter().enumerate() {
+        let merged = &buckets[i * 16..(i + 1) * 16];
+        assert!((bucket.max - merged[15].max).abs() < f32::EPSILON);
+        assert!((bucket.min - merged[15].min).abs() < f32::EPSILON);
+    }
+
+    // silence beyond the end
+    let range = mipmap.range(n_frames, n_frames * 2, 4).unwrap();
+    assert!(range.iter().all(|&bucket| bucket == Bucket::SILENCE));
+}
+
+/// Write a mono 16-bit FLAC sine with verbatim subframes,
+/// and without the total number of samples, so that the number of frames is unknown.
+fn write_flac_sine_without_length(path: &Path, n_frames: usize) {
+    const BLOCK_SIZE: usize = 5120;
+
+    fn crc8(data: &[u8]) -> u8 {
+        data.iter().fold(0, |crc, &byte| {
+            (0..8).fold(crc ^ byte, |crc, _| {
+                if crc & 0x80 == 0 {
+                    crc << 1
+                } else {
+                    (crc << 1) ^ 0x07
+                }
+            })
+        })
+    }
+
+    fn crc16(data: &[u8]) -> u16 {
+        data.iter().fold(0, |crc, &byte| {
+            (0..8).fold(crc ^ (u16::from(byte) << 8), |crc, _| {
+                if crc & 0x8000 == 0 {
+                    crc << 1
+                } else {
+                    (crc << 1) ^ 0x8005
+                }
+            })
+        })
+    }
+
+    let block_size = u16::try_from(BLOCK_SIZE).unwrap();
+    let mut data = b"fLaC".to_vec();
+    // the last metadata block, STREAMINFO of 34 bytes
+    data.extend([0x80, 0, 0, 34]);
+    data.extend(block_size.to_be_bytes()); // minimum block size
+    data.extend(block_size.to_be_bytes()); // maximum block size
+    data.extend([0; 6]); // unknown frame sizes
+                         // the sample rate, 1 channel and 16 bits per sample, and 0 samples, i.e. unknown
+    data.extend(((u64::from(SAMPLE_RATE) << 44) | (15 << 36)).to_be_bytes());
+    data.extend([0; 16]); // no MD5 signature
+
+    #[allow(clippy::cast_possible_truncation, clippy::cast_precision_loss)]
+    let samples = (0..n_frames)
+        .map(|i| {
+            let x = 0.5 * (2. * PI * 1000. * i as f32 / SAMPLE_RATE as f32).sin();
+            (x * 32767.).round() as i16
+        })
+        .collect::<Vec<_>>();
+    for (index, block) in samples.chunks(BLOCK_SIZE).enumerate() {
+        let start = data.len();
+        // the frame header of a fixed block size, read at the end of the header,
+        // the sample rate of STREAMINFO, 1 channel and 16 bits per sample
+        data.extend([0xFF, 0xF8, 0x70, 0x08, u8::try_from(index).unwrap()]);
+        data.extend(u16::try_from(block.len() - 1).unwrap().to_be_bytes());
+        data.push(crc8(&data[start..]));
+        // a verbatim subframe
+        data.push(0x02);
+        data.extend(block.iter().flat_map(|sample| sample.to_be_bytes()));
+        data.extend(crc16(&data[start..]).to_be_bytes());
+    }
+
+    std::fs::write(path, data).unwrap();
+}
+
+#[test]
+fn test_unknown_length() {
+    const N_FRAMES: usize = SAMPLING_STEP * 100;
+
+    let dir = testdir!();
+    let path = dir.join("sine.flac");
+    write_flac_sine_without_length(&path, N_FRAMES);
+
+    let mut generator = WaveformGenerator::new();
+    generator.set_source(Some((1, path)));
+
+    let info = generator.prepare_waveform().unwrap();
+    assert_eq!(info.n_buckets, None);
+    assert_eq!(info.sample_rate, SAMPLE_RATE);
+
+    // streamed until the end of the file
+    let waveform = collect_waveform(&generator);
+    assert_eq!(waveform.len(), N_FRAMES / SAMPLING_STEP);
+    assert!(waveform
+        .iter()
+        .all(|bucket| (bucket.max - 0.5).abs() < 0.01 && (bucket.min + 0.5).abs() < 0.01));
+}
+
+#[test]
+fn test_range() {
+    const N_FRAMES: usize = 48000;
+
+    let dir = testdir!();
+    let path = dir.join("sine.wav");
+    write_sine(&path, N_FRAMES, 1.);
+
+    let mut generator = WaveformGenerator::new();
+    generator.set_source(Some((1, path)));
+
+    // decoded from the region, one bucket per frame at most
+    let range = generator.request_waveform_range(0.5, 0.5625, 4000).unwrap();
+    assert_eq!(range.len(), 3000);
+    assert!(range
+        .iter()
+        .all(|bucket| (bucket.min - bucket.max).abs() < f32::EPSILON));
+
+    // a quarter of the period of the 1 kHz sine per bucket
+    let range = generator.request_waveform_range(0.25, 0.26, 40).unwrap();
+    assert_eq!(range.len(), 40);
+    assert!(range.iter().all(|bucket| bucket.max - bucket.min > 0.1));
+    let peak = range.iter().fold(0f32, |peak, bucket| peak.max(bucket.max));
+    assert!((peak - 0.5).abs() < 0.01, "{peak}");
+
+    // merged from the complete waveform once generated
+    let waveform = collect_waveform(&generator);
+    let range = generator.request_waveform_range(0., 1., 10).unwrap();
+    assert_eq!(range.len(), 10);
+    assert!(range
+        .iter()
+        .all(|bucket| (bucket.max - 0.5).abs() < 0.01 && (bucket.min + 0.5).abs() < 0.01));
+    assert!(!waveform.is_empty());
+
+    // silence beyond the end of the file
+    let range = generator.request_waveform_range(1.5, 2.5, 10).unwrap();
+    assert_eq!(range.len(), 10);
+    assert!(range.iter().all(|&bucket| bucket == Bucket::SILENCE));
+}
+
+#[test]
+fn test_range_of_source_taken() {
+    let dir = testdir!();
+    let long = dir.join("long.wav");
+    let short = dir.join("short.wav");
+    write_sine(&long, 48000, 1.);
+    write_sine(&short, 4800, 1.);
+
+    let mut generator = WaveformGenerator::new();
+    generator.set_source(Some((1, long)));
+    let source = generator.source().unwrap();
+    generator.set_source(Some((2, short)));
+
+    // decoded from the source taken, unaffected by the source set since
+    let range = source.request_range(0.5, 0.6, 10).unwrap();
+    assert_eq!(range.len(), 10);
+    assert!(range.iter().all(|bucket| bucket.max - bucket.min > 0.1));
+
+    // silence beyond the end of the source set since
+    let range = generator.request_waveform_range(0.5, 0.6, 10).unwrap();
+    assert!(range.iter().all(|&bucket| bucket == Bucket::SILENCE));
+}
+
+#[test]
+fn test_per_channel() {
+    const N_FRAMES: usize = 48000;
+
+    let dir = testdir!();
+    let path = dir.join("out_of_phase.wav");
+    write_sine(&path, N_FRAMES, -1.);
+
+    let mut generator = WaveformGenerator::new();
+    generator.set_source(Some((1, path)));
+
+    // the channels cancel out when mixed down
+    let waveform = collect_waveform(&generator);
+    assert_eq!(waveform.len(), N_FRAMES / SAMPLING_STEP);
+    assert!(waveform.iter().all(|bucket| bucket.max.abs() < 0.01));
+
+    generator.set_options(WaveformOptions { per_channel: true });
+    let info = generator.prepare_waveform().unwrap();
+    assert_eq!(info.n_channels, 2);
+    assert_eq!(
+        info.n_buckets,
+        Some(u32::try_from(N_FRAMES / SAMPLING_STEP).unwrap())
+    );
+    let waveform = collect_waveform(&generator);
+    assert_eq!(waveform.len(), N_FRAMES / SAMPLING_STEP * 2);
+    assert!(waveform
+        .iter()
+        .all(|bucket| (bucket.max - 0.5).abs() < 0.01 && (bucket.min + 0.5).abs() < 0.01));
+
+    let range = generator.request_waveform_range(0.25, 0.5, 8).unwrap();
+    assert_eq!(range.len(), 16);
+    let range = generator.request_waveform_range(0.25, 0.2505, 8).unwrap();
+    assert_eq!(range.len(), 16);
+    // the channels of each frame are opposite
+    assert!(range
+        .chunks_exact(2)
+        .all(|pair| (pair[0].max + pair[1].min).abs() < 0.01));
+}
+
+/// Generate the waveform of a mono sine of `amplitude` written in `sample_format`.
+fn sine_waveform(dir: &Path, sample_format: SampleFormat, amplitude: f32) -> Vec<Bucket> {
+    const N_FRAMES: usize = 4800;
+
+    let path = dir.join(format!("{sample_format:?}_{amplitude}.wav"));
+    let mut writer = WavWriter::create(
+        &path,
+        WavSpec {
+            n_channels: 1,
+            sample_rate: SAMPLE_RATE,
+            sample_format,
+        },
+    )
+    .unwrap();
+    #[allow(clippy::cast_precision_loss)]
+    let samples = (0..N_FRAMES)
+        .map(|i| amplitude * (2. * PI * 1000. * i as f32 / SAMPLE_RATE as f32).sin())
+        .collect::<Vec<_>>();
+    writer.write_samples(&samples).unwrap();
+    writer.finalize().unwrap();
+
+    let mut generator = WaveformGenerator::new();
+    generator.set_source(Some((1, path)));
+    let waveform = collect_waveform(&generator);
+    assert_eq!(waveform.len(), N_FRAMES / SAMPLING_STEP);
+    waveform
+}
+
+#[test]
+fn test_sample_formats() {
+    let dir = testdir!();
+    let peak = |waveform: &[Bucket]| waveform.iter().fold(0f32, |peak, b| peak.max(b.max));
+    let n_clipped = |waveform: &[Bucket]| waveform.iter().map(|b| b.n_clipped).sum::<u32>();
+
+    // below the resolution of 16-bit samples, but not of 24-bit samples
+    let amplitude = 1e-5;
+    assert!(peak(&sine_waveform(&dir, SampleFormat::Int16, amplitude)) < f32::EPSILON);
+    let waveform = sine_waveform(&dir, SampleFormat::Int24, amplitude);
+    assert!(
+        (peak(&waveform) - amplitude).abs() < 1e-6,
+        "{}",
+        peak(&waveform)
+    );
+    assert!(waveform.iter().all(|b| b.rms > 0.));
+    assert_eq!(n_clipped(&waveform), 0);
+
+    // full scale samples are overs
+    let waveform = sine_waveform(&dir, SampleFormat::Int24, 1.);
+    assert!(peak(&waveform) >= CLIP_LEVEL);
+    assert!(n_clipped(&waveform) > 0);
+    let waveform = sine_waveform(&dir, SampleFormat::Int16, 0.9);
+    assert_eq!(n_clipped(&waveform), 0);
+
+    // float samples beyond full scale are kept
+    let waveform = sine_waveform(&dir, SampleFormat::Float32, 1.5);
+    assert!((peak(&waveform) - 1.5).abs() < 0.01, "{}", peak(&waveform));
+    assert!(waveform.iter().all(|b| (b.min + 1.5).abs() < 0.01));
+    // the samples of the sine beyond 2/3 of its amplitude
+    let n_samples = waveform.len() * SAMPLING_STEP;
+    let ratio = f64::from(n_clipped(&waveform)) / f64::from(u32::try_from(n_samples).unwrap());
+    assert!((ratio - 0.54).abs() < 0.05, "{ratio}");
+    let waveform = sine_waveform(&dir, SampleFormat::Float32, 0.5);
+    assert_eq!(n_clipped(&waveform), 0);
+}
@@ -1,10 +1,20 @@
 mod core;
 mod response;
 
-use core::database::{DatabaseEmitter, FolderId};
+use core::database::{
+    DatabaseEmitter, ExportOptions, ExportProgress, ExportResult, FolderId, HashMode,
+    ImportOptions, ImportProgress, ImportResult, Region,
+};
 use core::migrator::{migrate_from, MigrateFrom, MigratorResult};
-use core::player::{PlayerEmitter, PlayerState};
-use core::{Database, EntryId, Filter, Player, TagId, WaveformGenerator};
+use core::player::{
+    ChannelOptions, FadeOptions, LoopRegion, MeterLevels, OutputDevice, OutputTarget, PlaybackRate,
+    PlaybackRateRange, PlayerEmitter, PlayerPosition, PlayerState, QueueOptions, QueueState,
+    SpectrumOptions, PLAYBACK_RATE_RANGE,
+};
+use core::silence::SilenceOptions;
+use core::spectrogram::{SpectrogramInfo, SpectrogramOptions};
+use core::waveform::{WaveformCache, WaveformInfo, WaveformOptions};
+use core::{Database, EntryId, Filter, Player, SpectrogramGenerator, TagId, WaveformGenerator};
 use response::{to_serializable_map, Error};
 use std::thread::spawn;
 
@@ -25,7 +35,10 @@ use tauri::{LogicalPosition, TitleBarStyle};
 struct AppData {
     database: RwLock<Option<Arc<Database<AppEmitter>>>>,
     player: RwLock<Player>,
+    /// The entry set as the source of the player.
+    player_entry_id: Mutex<Option<EntryId>>,
     waveform_generator: Mutex<WaveformGenerator>,
+    spectrogram_generator: Mutex<SpectrogramGenerator>,
     emitter: Arc<AppEmitter>,
 }
 
@@ -124,6 +137,33 @@ impl PlayerEmitter for AppEmitter {
         debug!("Emit: player_state_updated, {state:?}");
         self.app.emit("player_state_updated", state).unwrap();
     }
+
+    fn on_player_position_updated(&self, position: PlayerPosition) {
+        trace!("Emit: player_position_updated, {position:?}");
+        self.app.emit("player_position_updated", position).unwrap();
+    }
+
+    fn on_player_error(&self, error: &core::player::Error) {
+        debug!("Emit: player_error, {error:?}");
+        self.app.emit("player_error", error.to_string()).unwrap();
+    }
+
+    fn on_player_queue_updated(&self, state: QueueState) {
+        debug!("Emit: player_queue_updated, {state:?}");
+        self.app.emit("player_queue_updated", state).unwrap();
+    }
+
+    fn on_queue_advanced(&self, entry_id: EntryId) {
+        // not in the player thread, which would wait for itself to set the source
+        let app = self.app.clone();
+        spawn(move || {
+            let state = app.state::<AppData>();
+            if let Err(err) = play_entry(entry_id, &app, &state) {
+                warn!("Failed to play entry {entry_id} of the queue: {err}");
+                app.emit("player_error", err.to_string()).unwrap();
+            }
+        });
+    }
 }
 
 impl DatabaseEmitter for AppEmitter {
@@ -181,7 +221,14 @@ async fn open_database(path: String, state: State<'_, AppData>) -> Result<(), Er
     if let Some(database) = database.as_ref() {
         database.close();
     }
-    database.replace(Database::open(path.into(), state.emitter.clone())?);
+    let path = PathBuf::from(path);
+    let database = database.insert(Database::open(path.clone(), state.emitter.clone())?);
+    database.start_analysis();
+    state
+        .waveform_generator
+        .lock()
+        .unwrap()
+        .set_cache(Some(WaveformCache::new(&path)));
 
     state.player.write().unwrap().run();
 
@@ -197,7 +244,14 @@ async fn create_database(path: String, state: State<'_, AppData>) -> Result<(),
     if let Some(database) = database.as_ref() {
         database.close();
     }
-    database.replace(Database::create(path.into(), state.emitter.clone())?);
+    let path = PathBuf::from(path);
+    let database = database.insert(Database::create(path.clone(), state.emitter.clone())?);
+    database.start_analysis();
+    state
+        .waveform_generator
+        .lock()
+        .unwrap()
+        .set_cache(Some(WaveformCache::new(&path)));
 
     state.player.write().unwrap().run();
 
@@ -215,7 +269,11 @@ async fn close_database(state: State<'_, AppData>) -> Result<(), Error> {
     }
 
     state.player.read().unwrap().terminate();
-    state.waveform_generator.lock().unwrap().set_source(None);
+    state.player_entry_id.lock().unwrap().take();
+    let mut waveform_generator = state.waveform_generator.lock().unwrap();
+    waveform_generator.set_source(None);
+    waveform_generator.set_cache(None);
+    state.spectrogram_generator.lock().unwrap().set_source(None);
 
     trace!("close_database done");
     Ok(())
@@ -238,6 +296,7 @@ async fn refresh(state: State<'_, AppData>) -> Result<(), Error> {
 
     get_database!(database, state.database);
     database.refresh()?;
+    database.start_analysis();
 
     trace!("refresh done");
     Ok(())
@@ -416,15 +475,29 @@ async fn filter(filter: Filter, state: State<'_, AppData>) -> Result<Option<Vec<
     Ok(entry_ids)
 }
 
+#[tauri::command]
+async fn find_duplicates(
+    mode: HashMode,
+    state: State<'_, AppData>,
+) -> Result<Vec<Vec<EntryId>>, Error> {
+    trace!("find_duplicates: {mode:?}");
+
+    get_database!(database, state.database);
+    let duplicates = database.find_duplicates(mode)?;
+
+    trace!("find_duplicates done");
+    Ok(duplicates)
+}
+
 // ========== Waveform ==========
 
 #[tauri::command]
-async fn prepare_waveform(state: State<'_, AppData>) -> Result<u32, Error> {
+async fn prepare_waveform(state: State<'_, AppData>) -> Result<WaveformInfo, Error> {
     trace!("prepare_waveform");
     let waveform_generator = state.waveform_generator.lock().unwrap();
-    let data_length = waveform_generator.prepare_waveform()?;
+    let info = waveform_generator.prepare_waveform()?;
     trace!("prepare_waveform done");
-    Ok(data_length)
+    Ok(info)
 }
 
 #[tauri::command]
@@ -445,28 +518,199 @@ async fn request_waveform(
     Ok(())
 }
 
+#[tauri::command]
+async fn request_waveform_range(
+    start: f64,
+    end: f64,
+    buckets: usize,
+    state: State<'_, AppData>,
+) -> Result<Response, Error> {
+    trace!("request_waveform_range: {start:?}, {end:?}, {buckets:?}");
+
+    // decoded without locking the generator, which would block the other waveform requests
+    let source = state.waveform_generator.lock().unwrap().source()?;
+    let buckets = source.request_range(start, end, buckets)?;
+    let data = buckets
+        .iter()
+        .flat_map(|bucket| bucket.to_values())
+        .flat_map(f32::to_le_bytes)
+        .collect::<Vec<_>>();
+
+    trace!("request_waveform_range done");
+    Ok(Response::new(data))
+}
+
+#[tauri::command]
+async fn set_waveform_options(
+    options: WaveformOptions,
+    state: State<'_, AppData>,
+) -> Result<(), Error> {
+    trace!("set_waveform_options: {options:?}");
+
+    state
+        .waveform_generator
+        .lock()
+        .unwrap()
+        .set_options(options);
+
+    trace!("set_waveform_options done");
+    Ok(())
+}
+
+#[tauri::command]
+async fn get_waveform_options(state: State<'_, AppData>) -> Result<WaveformOptions, Error> {
+    Ok(state.waveform_generator.lock().unwrap().get_options())
+}
+
+// ========== Spectrogram ==========
+
+#[tauri::command]
+async fn prepare_spectrogram(state: State<'_, AppData>) -> Result<SpectrogramInfo, Error> {
+    trace!("prepare_spectrogram");
+    let spectrogram_generator = state.spectrogram_generator.lock().unwrap();
+    let info = spectrogram_generator.prepare_spectrogram()?;
+    trace!("prepare_spectrogram done");
+    Ok(info)
+}
+
+#[tauri::command]
+async fn request_spectrogram(
+    state: State<'_, AppData>,
+    channel: Channel<InvokeResponseBody>,
+) -> Result<(), Error> {
+    trace!("request_spectrogram");
+
+    let spectrogram_generator = state.spectrogram_generator.lock().unwrap();
+    spectrogram_generator.request_spectrogram(move |data| {
+        debug!("send spectrogram data, len: {}", data.len());
+        let response = InvokeResponseBody::Raw(data.to_vec());
+        channel.send(response).unwrap();
+    })?;
+
+    trace!("request_spectrogram done");
+    Ok(())
+}
+
+#[tauri::command]
+async fn set_spectrogram_options(
+    options: SpectrogramOptions,
+    state: State<'_, AppData>,
+) -> Result<(), Error> {
+    trace!("set_spectrogram_options: {options:?}");
+
+    state
+        .spectrogram_generator
+        .lock()
+        .unwrap()
+        .set_options(options)?;
+
+    trace!("set_spectrogram_options done");
+    Ok(())
+}
+
+#[tauri::command]
+async fn get_spectrogram_options(state: State<'_, AppData>) -> Result<SpectrogramOptions, Error> {
+    Ok(state.spectrogram_generator.lock().unwrap().get_options())
+}
+
 // ========== Player ==========
 
 #[tauri::command]
-async fn set_player_source(entry_id: EntryId, state: State<'_, AppData>) -> Result<(), Error> {
+async fn set_player_source(
+    entry_id: EntryId,
+    app: AppHandle,
+    state: State<'_, AppData>,
+) -> Result<(), Error> {
     trace!("set_player_source: {entry_id:?}");
 
-    let path = {
+    set_player_entry(entry_id, &app, &state)?;
+
+    trace!("set_player_source done");
+    Ok(())
+}
+
+/// Set the entry as the source of the player and the waveform generator.
+fn set_player_entry(entry_id: EntryId, app: &AppHandle, state: &AppData) -> Result<(), Error> {
+    let silence_options = state.player.read().unwrap().get_silence_options();
+    let (path, loudness, silence) = {
         get_database!(database, state.database);
         get_data!(data, database);
-        data.get_entry_path(entry_id).unwrap()
+        let entry = data
+            .get_entry(entry_id)
+            .ok_or(Error::EntryNotFound(entry_id))?;
+        (
+            data.get_entry_path(entry_id).unwrap(),
+            entry.loudness,
+            entry
+                .silence
+                .filter(|silence| silence.options == silence_options),
+        )
     };
     debug!("path: {}", path.display());
 
-    state.player.write().unwrap().set_source(path.clone())?;
+    state
+        .player
+        .write()
+        .unwrap()
+        .set_source(path.clone(), loudness, silence)?;
+    state.player_entry_id.lock().unwrap().replace(entry_id);
+
+    if loudness.is_none() || silence.is_none() {
+        analyze_player_source(
+            app.clone(),
+            entry_id,
+            path.clone(),
+            loudness.is_none(),
+            silence.is_none().then_some(silence_options),
+        );
+    }
 
+    state
+        .spectrogram_generator
+        .lock()
+        .unwrap()
+        .set_source(Some(path.clone()));
     state
         .waveform_generator
         .lock()
         .unwrap()
-        .set_source(path.into());
+        .set_source(Some((entry_id, path)));
 
-    trace!("set_player_source done");
+    Ok(())
+}
+
+/// Set the entry as the source and play it, skipping silence as when last played,
+/// and preload the entry the queue would advance to.
+fn play_entry(entry_id: EntryId, app: &AppHandle, state: &AppData) -> Result<(), Error> {
+    set_player_entry(entry_id, app, state)?;
+
+    let player = state.player.read().unwrap();
+    player.play(player.is_skipping_silence())?;
+
+    if let Some(upcoming) = player.get_upcoming_in_queue() {
+        get_database!(database, state.database);
+        get_data!(data, database);
+        player.preload(data.get_entry_path(upcoming).into_iter().collect());
+    }
+    Ok(())
+}
+
+/// Preload the entries likely to be played next, the most likely first.
+#[tauri::command]
+async fn preload_entries(entry_ids: Vec<EntryId>, state: State<'_, AppData>) -> Result<(), Error> {
+    trace!("preload_entries: {entry_ids:?}");
+
+    let paths = {
+        get_database!(database, state.database);
+        get_data!(data, database);
+        entry_ids
+            .into_iter()
+            .filter_map(|entry_id| data.get_entry_path(entry_id))
+            .collect()
+    };
+    state.player.read().unwrap().preload(paths);
+
+    trace!("preload_entries done");
     Ok(())
 }
 
@@ -510,11 +754,293 @@ async fn stop(state: State<'_, AppData>) -> Result<(), Error> {
 
     state.player.read().unwrap().stop();
     state.waveform_generator.lock().unwrap().set_source(None);
+    state.spectrogram_generator.lock().unwrap().set_source(None);
 
     trace!("stop done");
     Ok(())
 }
 
+#[tauri::command]
+async fn set_loop_region(
+    region: Option<LoopRegion>,
+    state: State<'_, AppData>,
+) -> Result<(), Error> {
+    trace!("set_loop_region: {region:?}");
+
+    state.player.read().unwrap().set_loop_region(region)?;
+
+    trace!("set_loop_region done");
+    Ok(())
+}
+
+#[tauri::command]
+async fn set_looping(enabled: bool, state: State<'_, AppData>) -> Result<(), Error> {
+    trace!("set_looping: {enabled:?}");
+
+    state.player.read().unwrap().set_looping(enabled);
+
+    trace!("set_looping done");
+    Ok(())
+}
+
+#[tauri::command]
+async fn set_playback_rate(rate: PlaybackRate, state: State<'_, AppData>) -> Result<(), Error> {
+    trace!("set_playback_rate: {rate:?}");
+
+    state.player.read().unwrap().set_playback_rate(rate)?;
+
+    trace!("set_playback_rate done");
+    Ok(())
+}
+
+#[tauri::command]
+async fn get_playback_rate(state: State<'_, AppData>) -> Result<PlaybackRate, Error> {
+    Ok(state.player.read().unwrap().get_playback_rate())
+}
+
+#[tauri::command]
+async fn get_playback_rate_range() -> PlaybackRateRange {
+    PLAYBACK_RATE_RANGE
+}
+
+#[tauri::command]
+async fn get_queue(state: State<'_, AppData>) -> Result<QueueState, Error> {
+    Ok(state.player.read().unwrap().get_queue())
+}
+
+/// Replace the queue with the entries, and play the entry at `start` if given.
+#[tauri::command]
+async fn set_queue(
+    entry_ids: Vec<EntryId>,
+    start: Option<usize>,
+    app: AppHandle,
+    state: State<'_, AppData>,
+) -> Result<(), Error> {
+    trace!("set_queue: {} entries, start = {start:?}", entry_ids.len());
+
+    let entry_id = state.player.read().unwrap().set_queue(entry_ids, start);
+    if let Some(entry_id) = entry_id {
+        play_entry(entry_id, &app, &state)?;
+    }
+
+    trace!("set_queue done");
+    Ok(())
+}
+
+#[tauri::command]
+async fn enqueue(entry_ids: Vec<EntryId>, state: State<'_, AppData>) -> Result<(), Error> {
+    trace!("enqueue: {entry_ids:?}");
+
+    state.player.read().unwrap().enqueue(&entry_ids);
+
+    trace!("enqueue done");
+    Ok(())
+}
+
+#[tauri::command]
+async fn remove_from_queue(index: usize, state: State<'_, AppData>) -> Result<(), Error> {
+    trace!("remove_from_queue: {index:?}");
+
+    state.player.read().unwrap().remove_from_queue(index)?;
+
+    trace!("remove_from_queue done");
+    Ok(())
+}
+
+#[tauri::command]
+async fn clear_queue(state: State<'_, AppData>) -> Result<(), Error> {
+    trace!("clear_queue");
+
+    state.player.read().unwrap().clear_queue();
+
+    trace!("clear_queue done");
+    Ok(())
+}
+
+#[tauri::command]
+async fn jump_in_queue(
+    index: usize,
+    app: AppHandle,
+    state: State<'_, AppData>,
+) -> Result<(), Error> {
+    trace!("jump_in_queue: {index:?}");
+
+    let entry_id = state.player.read().unwrap().jump_in_queue(index)?;
+    play_entry(entry_id, &app, &state)?;
+
+    trace!("jump_in_queue done");
+    Ok(())
+}
+
+/// Play the next entry of the queue, returning `false` at the end of the queue.
+#[tauri::command]
+async fn next_in_queue(app: AppHandle, state: State<'_, AppData>) -> Result<bool, Error> {
+    trace!("next_in_queue");
+
+    let entry_id = state.player.read().unwrap().next_in_queue();
+    if let Some(entry_id) = entry_id {
+        play_entry(entry_id, &app, &state)?;
+    }
+
+    trace!("next_in_queue done");
+    Ok(entry_id.is_some())
+}
+
+/// Play the previous entry of the queue, returning `false` at the start of the queue.
+#[tauri::command]
+async fn previous_in_queue(app: AppHandle, state: State<'_, AppData>) -> Result<bool, Error> {
+    trace!("previous_in_queue");
+
+    let entry_id = state.player.read().unwrap().previous_in_queue();
+    if let Some(entry_id) = entry_id {
+        play_entry(entry_id, &app, &state)?;
+    }
+
+    trace!("previous_in_queue done");
+    Ok(entry_id.is_some())
+}
+
+#[tauri::command]
+async fn set_queue_options(options: QueueOptions, state: State<'_, AppData>) -> Result<(), Error> {
+    trace!("set_queue_options: {options:?}");
+
+    state.player.read().unwrap().set_queue_options(options)?;
+
+    trace!("set_queue_options done");
+    Ok(())
+}
+
+#[tauri::command]
+async fn set_channel_options(
+    options: ChannelOptions,
+    state: State<'_, AppData>,
+) -> Result<(), Error> {
+    trace!("set_channel_options: {options:?}");
+
+    state.player.read().unwrap().set_channel_options(options);
+
+    trace!("set_channel_options done");
+    Ok(())
+}
+
+#[tauri::command]
+async fn get_channel_options(state: State<'_, AppData>) -> Result<ChannelOptions, Error> {
+    Ok(state.player.read().unwrap().get_channel_options())
+}
+
+#[tauri::command]
+async fn set_fade_options(options: FadeOptions, state: State<'_, AppData>) -> Result<(), Error> {
+    trace!("set_fade_options: {options:?}");
+
+    state.player.read().unwrap().set_fade_options(options)?;
+
+    trace!("set_fade_options done");
+    Ok(())
+}
+
+#[tauri::command]
+async fn get_fade_options(state: State<'_, AppData>) -> Result<FadeOptions, Error> {
+    Ok(state.player.read().unwrap().get_fade_options())
+}
+
+#[tauri::command]
+async fn subscribe_meter(
+    channel: Channel<MeterLevels>,
+    state: State<'_, AppData>,
+) -> Result<(), Error> {
+    trace!("subscribe_meter");
+
+    state
+        .player
+        .read()
+        .unwrap()
+        .subscribe_meter(move |levels| channel.send(levels).is_ok());
+
+    trace!("subscribe_meter done");
+    Ok(())
+}
+
+#[tauri::command]
+async fn unsubscribe_meter(state: State<'_, AppData>) -> Result<(), Error> {
+    trace!("unsubscribe_meter");
+
+    state.player.read().unwrap().unsubscribe_meter();
+
+    trace!("unsubscribe_meter done");
+    Ok(())
+}
+
+/// Push the spectrum of the playback as raw bytes in little-endian:
+/// the sample rate as `u32`, then the level of each bin in dBFS as `f32`.
+#[tauri::command]
+async fn subscribe_spectrum(
+    channel: Channel<InvokeResponseBody>,
+    state: State<'_, AppData>,
+) -> Result<(), Error> {
+    trace!("subscribe_spectrum");
+
+    state
+        .player
+        .read()
+        .unwrap()
+        .subscribe_spectrum(move |spectrum| {
+            let mut data = Vec::with_capacity(4 + spectrum.bins.len() * 4);
+            data.extend_from_slice(&spectrum.sample_rate.to_le_bytes());
+            for bin in spectrum.bins {
+                data.extend_from_slice(&bin.to_le_bytes());
+            }
+            channel.send(InvokeResponseBody::Raw(data)).is_ok()
+        });
+
+    trace!("subscribe_spectrum done");
+    Ok(())
+}
+
+#[tauri::command]
+async fn unsubscribe_spectrum(state: State<'_, AppData>) -> Result<(), Error> {
+    trace!("unsubscribe_spectrum");
+
+    state.player.read().unwrap().unsubscribe_spectrum();
+
+    trace!("unsubscribe_spectrum done");
+    Ok(())
+}
+
+#[tauri::command]
+async fn set_spectrum_options(
+    options: SpectrumOptions,
+    state: State<'_, AppData>,
+) -> Result<(), Error> {
+    trace!("set_spectrum_options: {options:?}");
+
+    state.player.read().unwrap().set_spectrum_options(options)?;
+
+    trace!("set_spectrum_options done");
+    Ok(())
+}
+
+#[tauri::command]
+async fn get_spectrum_options(state: State<'_, AppData>) -> Result<SpectrumOptions, Error> {
+    Ok(state.player.read().unwrap().get_spectrum_options())
+}
+
+#[tauri::command]
+async fn set_position_update_rate(
+    rate: Option<f32>,
+    state: State<'_, AppData>,
+) -> Result<(), Error> {
+    trace!("set_position_update_rate: {rate:?}");
+
+    state
+        .player
+        .read()
+        .unwrap()
+        .set_position_update_rate(rate)?;
+
+    trace!("set_position_update_rate done");
+    Ok(())
+}
+
 #[tauri::command]
 async fn set_volume(volume: f32, state: State<'_, AppData>) -> Result<(), Error> {
     trace!("set_volume: {volume:?}");
@@ -525,6 +1051,99 @@ async fn set_volume(volume: f32, state: State<'_, AppData>) -> Result<(), Error>
     Ok(())
 }
 
+#[tauri::command]
+async fn set_loudness_normalization(
+    target_loudness: Option<f32>,
+    state: State<'_, AppData>,
+) -> Result<(), Error> {
+    trace!("set_loudness_normalization: {target_loudness:?}");
+
+    state
+        .player
+        .write()
+        .unwrap()
+        .set_loudness_normalization(target_loudness);
+
+    trace!("set_loudness_normalization done");
+    Ok(())
+}
+
+#[tauri::command]
+async fn set_silence_detection(
+    options: SilenceOptions,
+    app: AppHandle,
+    state: State<'_, AppData>,
+) -> Result<(), Error> {
+    trace!("set_silence_detection: {options:?}");
+
+    state.player.write().unwrap().set_silence_options(options)?;
+
+    // detect the silence of the current source again
+    let entry_id = *state.player_entry_id.lock().unwrap();
+    if let Some(entry_id) = entry_id {
+        get_database!(database, state.database);
+        get_data!(data, database);
+        if let Some(path) = data.get_entry_path(entry_id) {
+            analyze_player_source(app, entry_id, path, false, Some(options));
+        }
+    }
+
+    trace!("set_silence_detection done");
+    Ok(())
+}
+
+/// Detect the silence and measure the loudness of the player source in background,
+/// to be applied when ready.
+///
+/// The silence is detected first, as it is needed to start playing.
+fn analyze_player_source(
+    app: AppHandle,
+    entry_id: EntryId,
+    path: PathBuf,
+    measure_loudness: bool,
+    silence_options: Option<SilenceOptions>,
+) {
+    spawn(move || {
+        let state = app.state::<AppData>();
+        let Some(database) = state.database.read().unwrap().clone() else {
+            return;
+        };
+
+        if let Some(options) = silence_options {
+            match database.get_silence(entry_id, options) {
+                Ok(Some(silence)) => state.player.read().unwrap().set_silence(&path, silence),
+                Ok(None) => {}
+                Err(err) => warn!("Failed to detect silence of entry {entry_id}: {err}"),
+            }
+        }
+
+        if measure_loudness {
+            match database.get_loudness(entry_id) {
+                Ok(Some(loudness)) => state.player.read().unwrap().set_loudness(&path, loudness),
+                Ok(None) => {}
+                Err(err) => warn!("Failed to measure loudness of entry {entry_id}: {err}"),
+            }
+        }
+    });
+}
+
+#[tauri::command]
+async fn get_output_devices() -> Result<Vec<OutputDevice>, Error> {
+    Ok(core::player::get_output_devices()?)
+}
+
+/// Set the output device to play on by its name, or `None` to follow the default device.
+#[tauri::command]
+async fn set_output_device(name: Option<String>, state: State<'_, AppData>) -> Result<(), Error> {
+    trace!("set_output_device: {name:?}");
+
+    let target = name.map_or(OutputTarget::Default, OutputTarget::Device);
+    state.player.read().unwrap().set_output_target(target);
+
+    trace!("set_output_device done");
+    Ok(())
+}
+
 #[tauri::command]
 async fn get_playing_pos(state: State<'_, AppData>) -> Result<f32, Error> {
     Ok(state.player.read().unwrap().get_pos())
@@ -538,11 +1157,60 @@ async fn import_file(path: String, force: bool, state: State<'_, AppData>) -> Re
 
     get_database!(database, state.database);
     database.import_file(Path::new(&path), force)?;
+    database.start_analysis();
 
     trace!("import_file done");
     Ok(())
 }
 
+#[tauri::command]
+async fn import_paths(
+    paths: Vec<PathBuf>,
+    target_folder_id: FolderId,
+    options: ImportOptions,
+    channel: Channel<ImportProgress>,
+    state: State<'_, AppData>,
+) -> Result<Vec<ImportResult>, Error> {
+    trace!(
+        "import_paths: {paths:?}, target_folder_id = {target_folder_id:?}, options = {options:?}"
+    );
+
+    get_database!(database, state.database);
+    let results = database.import_paths(&paths, target_folder_id, options, |progress| {
+        channel.send(progress).unwrap_or_else(|err| {
+            warn!("Failed to send import progress: {err}");
+        });
+    })?;
+    database.start_analysis();
+
+    trace!("import_paths done");
+    Ok(results)
+}
+
+#[tauri::command]
+async fn export_entries(
+    entry_ids: Vec<EntryId>,
+    target_dir: PathBuf,
+    options: ExportOptions,
+    channel: Channel<ExportProgress>,
+    state: State<'_, AppData>,
+) -> Result<Vec<ExportResult>, Error> {
+    trace!(
+        "export_entries: {} entries, target_dir = {target_dir:?}, options = {options:?}",
+        entry_ids.len()
+    );
+
+    get_database!(database, state.database);
+    let results = database.export_entries(&entry_ids, &target_dir, &options, |progress| {
+        channel.send(progress).unwrap_or_else(|err| {
+            warn!("Failed to send export progress: {err}");
+        });
+    })?;
+
+    trace!("export_entries done");
+    Ok(results)
+}
+
 #[tauri::command]
 async fn delete_file(entry_id: EntryId, state: State<'_, AppData>) -> Result<(), Error> {
     trace!("delete_file: {entry_id:?}");
@@ -585,23 +1253,50 @@ async fn move_folder(
     Ok(())
 }
 
+#[tauri::command]
+async fn create_folder(
+    parent_id: FolderId,
+    name: String,
+    state: State<'_, AppData>,
+) -> Result<FolderId, Error> {
+    trace!("create_folder: parent_id = {parent_id:?}, name = {name:?}");
+    get_database!(database, state.database);
+
+    let folder_id = database.create_folder(parent_id, &name)?;
+
+    trace!("create_folder done");
+    Ok(folder_id)
+}
+
+#[tauri::command]
+async fn delete_folder(folder_id: FolderId, state: State<'_, AppData>) -> Result<(), Error> {
+    trace!("delete_folder: {folder_id:?}");
+    get_database!(database, state.database);
+
+    database.delete_folder(folder_id)?;
+
+    trace!("delete_folder done");
+    Ok(())
+}
+
 #[tauri::command]
 async fn spot(
     entry_id: EntryId,
     save_path: Option<&str>,
     open_in_application: Option<&str>,
+    region: Option<Region>,
     force: bool,
     state: State<'_, AppData>,
 ) -> Result<(), Error> {
-    trace!("spot: {entry_id:?}");
+    trace!("spot: {entry_id:?}, region = {region:?}");
 
     get_database!(database, state.database);
-    get_data!(data, database);
 
-    data.spot(
+    database.spot(
         entry_id,
         save_path.map(Path::new),
         open_in_application.map(Path::new),
+        region.as_ref(),
         force,
     )?;
 
@@ -609,6 +1304,24 @@ async fn spot(
     Ok(())
 }
 
+#[tauri::command]
+async fn render_region(
+    entry_id: EntryId,
+    region: Region,
+    save_path: Option<&str>,
+    force: bool,
+    state: State<'_, AppData>,
+) -> Result<PathBuf, Error> {
+    trace!("render_region: {entry_id:?}, region = {region:?}, save_path = {save_path:?}");
+
+    get_database!(database, state.database);
+
+    let path = database.render_region(entry_id, &region, save_path.map(Path::new), force)?;
+
+    trace!("render_region done");
+    Ok(path)
+}
+
 #[tauri::command]
 async fn reveal_entry(
     entry_id: EntryId,
@@ -652,7 +1365,9 @@ fn setup_state(app: &App) {
     app.manage(AppData {
         database: None.into(),
         player: Player::new(emitter.clone()).into(),
+        player_entry_id: None.into(),
         waveform_generator: WaveformGenerator::new().into(),
+        spectrogram_generator: SpectrogramGenerator::new().into(),
         emitter,
     });
 }
@@ -721,20 +1436,63 @@ pub fn run() {
             add_tag_for_entry,
             remove_tag_for_entry,
             filter,
+            find_duplicates,
             prepare_waveform,
             request_waveform,
+            request_waveform_range,
+            set_waveform_options,
+            get_waveform_options,
+            prepare_spectrogram,
+            request_spectrogram,
+            set_spectrogram_options,
+            get_spectrogram_options,
             set_player_source,
+            preload_entries,
             seek,
             play,
             pause,
             stop,
+            set_loop_region,
+            set_looping,
+            set_playback_rate,
+            get_playback_rate,
+            get_playback_rate_range,
+            get_queue,
+            set_queue,
+            enqueue,
+            remove_from_queue,
+            clear_queue,
+            jump_in_queue,
+            next_in_queue,
+            previous_in_queue,
+            set_queue_options,
+            set_channel_options,
+            get_channel_options,
+            set_fade_options,
+            get_fade_options,
+            subscribe_meter,
+            unsubscribe_meter,
+            subscribe_spectrum,
+            unsubscribe_spectrum,
+            set_spectrum_options,
+            get_spectrum_options,
+            set_position_update_rate,
             set_volume,
+            set_loudness_normalization,
+            set_silence_detection,
+            get_output_devices,
+            set_output_device,
             get_playing_pos,
             import_file,
+            import_paths,
+            export_entries,
             delete_file,
             move_file,
             move_folder,
+            create_folder,
+            delete_folder,
             spot,
+            render_region,
             reveal_entry,
             reveal_folder
         ])
@@ -13,12 +13,16 @@ use thiserror::Error;
 pub enum Error {
     #[error("database not open")]
     DatabaseNotOpen,
+    #[error("entry not found: {0}")]
+    EntryNotFound(crate::core::EntryId),
     #[error(transparent)]
     Database(#[from] crate::core::database::Error),
     #[error("player error: {0}")]
     Player(#[from] crate::core::player::Error),
     #[error("waveform error: {0}")]
     Waveform(#[from] crate::core::waveform::Error),
+    #[error("spectrogram error: {0}")]
+    Spectrogram(#[from] crate::core::spectrogram::Error),
     #[error("migrator error: {0}")]
     Migrator(#[from] crate::core::migrator::Error),
     #[error("opener error: {0}")]
@@ -82,7 +86,7 @@ impl Serialize for Entry {
     where
         S: Serializer,
     {
-        let mut state = serializer.serialize_struct("Entry", 7)?;
+        let mut state = serializer.serialize_struct("Entry", 14)?;
         state.serialize_field("id", &self.id)?;
         state.serialize_field("fileName", &self.file_name.to_string_lossy())?;
         state.serialize_field("folderId", &self.folder_id)?;
@@ -100,6 +104,26 @@ impl Serialize for Entry {
                 state.serialize_field("duration", duration)?;
             }
         }
+        if let Some(loudness) = &self.loudness {
+            // non-finite values (i.e. silence) are not representable in JSON
+            if loudness.integrated.is_finite() {
+                state.serialize_field("loudness", &loudness.integrated)?;
+            }
+            if loudness.true_peak.is_finite() {
+                state.serialize_field("truePeak", &loudness.true_peak)?;
+            }
+        }
+        if let Some(analysis) = &self.analysis {
+            if analysis.peak.is_finite() {
+                state.serialize_field("peak", &analysis.peak)?;
+            }
+            if analysis.rms.is_finite() {
+                state.serialize_field("rms", &analysis.rms)?;
+            }
+            state.serialize_field("loudnessRange", &analysis.loudness_range)?;
+            state.serialize_field("dcOffset", &analysis.dc_offset)?;
+            state.serialize_field("clippedSamples", &analysis.clipped_samples)?;
+        }
         state.end()
     }
 }
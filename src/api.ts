@@ -20,6 +20,13 @@ export type Entry = {
   artist?: string;
   album?: string;
   duration?: number;
+  loudness?: number;
+  truePeak?: number;
+  peak?: number;
+  rms?: number;
+  loudnessRange?: number;
+  dcOffset?: number;
+  clippedSamples?: number;
 };
 
 export type Folder = {
@@ -41,8 +48,130 @@ export type Tag = {
 export type PlayerState = {
   playing: boolean;
   pos: number;
+  loopRegion: LoopRegion | null;
 };
 
+export type LoopRegion = {
+  start: number;
+  end: number;
+};
+
+export type PlaybackRate =
+  | { mode: "varispeed"; speed: number }
+  | { mode: "independent"; speed: number; semitones: number };
+
+export type PlaybackRateRange = {
+  minSpeed: number;
+  maxSpeed: number;
+  minSemitones: number;
+  maxSemitones: number;
+  semitoneStep: number;
+};
+
+export type PlayerPosition = {
+  pos: number;
+  frames: number;
+  sampleRate: number;
+};
+
+export type RepeatMode = "off" | "one" | "all";
+
+export type QueueOptions = {
+  shuffle: boolean;
+  repeat: RepeatMode;
+  autoAdvance: boolean;
+  gap: number;
+};
+
+export type QueueState = {
+  entries: number[];
+  current: number | null;
+  options: QueueOptions;
+};
+
+export type ChannelOptions = {
+  solo: number | null;
+  mono: boolean;
+  swap: boolean;
+  invert: boolean;
+  ambisonic: boolean;
+};
+
+export type FadeOptions = {
+  /** The duration of the fade-in in seconds. */
+  fadeIn: number;
+  /** The duration of the fade-out in seconds. */
+  fadeOut: number;
+};
+
+/** The levels of each channel as linear amplitudes. */
+export type MeterLevels = {
+  peak: number[];
+  rms: number[];
+  /** The highest peak of the last 1.5 seconds. */
+  peakHold: number[];
+};
+
+export type SpectrumWindow =
+  | "rectangular"
+  | "hann"
+  | "hamming"
+  | "blackman"
+  | "blackmanHarris";
+
+export type SpectrumOptions = {
+  /** A power of two between 256 and 16384. */
+  fftSize: number;
+  window: SpectrumWindow;
+};
+
+export type FrequencyScale = "linear" | "log" | "mel";
+
+export type SpectrogramOptions = {
+  /** A power of two between 256 and 16384. */
+  fftSize: number;
+  /** The number of frames between the columns, at most `fftSize`. */
+  hop: number;
+  window: SpectrumWindow;
+  scale: FrequencyScale;
+  /** The number of bands of the log and mel scales. */
+  nBands: number;
+};
+
+export type SpectrogramInfo = {
+  /** `null` if the number of frames of the source is unknown. */
+  nColumns: number | null;
+  nBins: number;
+  sampleRate: number;
+};
+
+export type WaveformOptions = {
+  /** Whether the waveform is generated for each channel, rather than mixed down. */
+  perChannel: boolean;
+};
+
+export type WaveformInfo = {
+  /** `null` if the number of frames of the source is unknown. */
+  nBuckets: number | null;
+  /** The number of channels of the waveform, 1 unless generated per channel. */
+  nChannels: number;
+  sampleRate: number;
+  /** The number of frames of each bucket. */
+  framesPerBucket: number;
+};
+
+export type OutputDevice = {
+  name: string;
+  isDefault: boolean;
+};
+
+export type SilenceOptions = {
+  threshold: number;
+  minDuration: number;
+};
+
+export type HashMode = "content" | "audio";
+
 export type FilterArg = {
   search: string;
   tagIds: number[];
@@ -50,6 +179,81 @@ export type FilterArg = {
   noTags: boolean;
   folderId: number | null;
   includeSubfolders: boolean;
+  loudness?: ValueRange;
+  peak?: ValueRange;
+  rms?: ValueRange;
+  loudnessRange?: ValueRange;
+  /** The absolute value of the DC offset. */
+  dcOffset?: ValueRange;
+  clipped?: boolean;
+};
+
+export type ValueRange = {
+  min?: number;
+  max?: number;
+};
+
+// ========== Import ==========
+
+export type ImportOptions = {
+  mode: "copy" | "move";
+  collisionPolicy: "skip" | "overwrite" | "rename";
+  checkDuplicates?: boolean;
+};
+
+export type ImportStatus = {
+  kind: "imported" | "overwritten" | "renamed" | "skipped" | "failed";
+  message?: string;
+};
+
+export type ImportResult = {
+  srcPath: string;
+  dstPath: string | null;
+  status: ImportStatus;
+  duplicates: number[];
+};
+
+export type ImportProgress = {
+  processed: number;
+  total: number;
+  result: ImportResult;
+};
+
+export type ExportOptions = {
+  preserveStructure?: boolean;
+  convert?: {
+    sampleFormat: "int16" | "int24" | "float32";
+    /** From 8000 to 768000 Hz. The original sample rate is kept if not set. */
+    sampleRate?: number;
+  };
+  renamePattern?: string;
+  manifest?: "csv" | "json";
+  collisionPolicy: "skip" | "overwrite" | "rename";
+};
+
+export type ExportStatus = {
+  kind: "exported" | "overwritten" | "renamed" | "skipped" | "failed";
+  message?: string;
+};
+
+export type ExportResult = {
+  entryId: number;
+  dstPath: string | null;
+  status: ExportStatus;
+};
+
+export type ExportProgress = {
+  processed: number;
+  total: number;
+  result: ExportResult;
+};
+
+export type Region = {
+  start: number;
+  end: number;
+  fadeIn?: number;
+  fadeOut?: number;
+  suffix?: string;
 };
 
 // ========== Migrator ==========
@@ -142,10 +346,18 @@ export const api = {
     return invoke("filter", { filter });
   },
 
+  findDuplicates(mode: HashMode): Promise<number[][]> {
+    return invoke("find_duplicates", { mode });
+  },
+
   setPlayerSource(entryId: number): Promise<void> {
     return invoke("set_player_source", { entryId });
   },
 
+  preloadEntries(entryIds: number[]): Promise<void> {
+    return invoke("preload_entries", { entryIds });
+  },
+
   seek(pos: number): Promise<void> {
     return invoke("seek", { pos });
   },
@@ -166,22 +378,201 @@ export const api = {
     return invoke("get_playing_pos");
   },
 
+  getOutputDevices(): Promise<OutputDevice[]> {
+    return invoke("get_output_devices");
+  },
+
+  setOutputDevice(name: string | null): Promise<void> {
+    return invoke("set_output_device", { name });
+  },
+
   setVolume(volume: number): Promise<void> {
     return invoke("set_volume", { volume });
   },
 
-  prepareWaveform(): Promise<number> {
+  setLoopRegion(region: LoopRegion | null): Promise<void> {
+    return invoke("set_loop_region", { region });
+  },
+
+  setLooping(enabled: boolean): Promise<void> {
+    return invoke("set_looping", { enabled });
+  },
+
+  setPlaybackRate(rate: PlaybackRate): Promise<void> {
+    return invoke("set_playback_rate", { rate });
+  },
+
+  getPlaybackRate(): Promise<PlaybackRate> {
+    return invoke("get_playback_rate");
+  },
+
+  getPlaybackRateRange(): Promise<PlaybackRateRange> {
+    return invoke("get_playback_rate_range");
+  },
+
+  getQueue(): Promise<QueueState> {
+    return invoke("get_queue");
+  },
+
+  setQueue(entryIds: number[], start: number | null): Promise<void> {
+    return invoke("set_queue", { entryIds, start });
+  },
+
+  enqueue(entryIds: number[]): Promise<void> {
+    return invoke("enqueue", { entryIds });
+  },
+
+  removeFromQueue(index: number): Promise<void> {
+    return invoke("remove_from_queue", { index });
+  },
+
+  clearQueue(): Promise<void> {
+    return invoke("clear_queue");
+  },
+
+  jumpInQueue(index: number): Promise<void> {
+    return invoke("jump_in_queue", { index });
+  },
+
+  nextInQueue(): Promise<boolean> {
+    return invoke("next_in_queue");
+  },
+
+  previousInQueue(): Promise<boolean> {
+    return invoke("previous_in_queue");
+  },
+
+  setQueueOptions(options: QueueOptions): Promise<void> {
+    return invoke("set_queue_options", { options });
+  },
+
+  setChannelOptions(options: ChannelOptions): Promise<void> {
+    return invoke("set_channel_options", { options });
+  },
+
+  getChannelOptions(): Promise<ChannelOptions> {
+    return invoke("get_channel_options");
+  },
+
+  setFadeOptions(options: FadeOptions): Promise<void> {
+    return invoke("set_fade_options", { options });
+  },
+
+  getFadeOptions(): Promise<FadeOptions> {
+    return invoke("get_fade_options");
+  },
+
+  subscribeMeter(channel: Channel<MeterLevels>): Promise<void> {
+    return invoke("subscribe_meter", { channel });
+  },
+
+  unsubscribeMeter(): Promise<void> {
+    return invoke("unsubscribe_meter");
+  },
+
+  /**
+   * Subscribe to the spectrum of the playback, pushed as raw bytes in little-endian:
+   * the sample rate as `u32`, then the level of each bin from DC to Nyquist in dBFS as `f32`.
+   */
+  subscribeSpectrum(channel: Channel<ArrayBuffer>): Promise<void> {
+    return invoke("subscribe_spectrum", { channel });
+  },
+
+  unsubscribeSpectrum(): Promise<void> {
+    return invoke("unsubscribe_spectrum");
+  },
+
+  setSpectrumOptions(options: SpectrumOptions): Promise<void> {
+    return invoke("set_spectrum_options", { options });
+  },
+
+  getSpectrumOptions(): Promise<SpectrumOptions> {
+    return invoke("get_spectrum_options");
+  },
+
+  setPositionUpdateRate(rate: number | null): Promise<void> {
+    return invoke("set_position_update_rate", { rate });
+  },
+
+  setLoudnessNormalization(targetLoudness: number | null): Promise<void> {
+    return invoke("set_loudness_normalization", { targetLoudness });
+  },
+
+  setSilenceDetection(options: SilenceOptions): Promise<void> {
+    return invoke("set_silence_detection", { options });
+  },
+
+  prepareWaveform(): Promise<WaveformInfo> {
     return invoke("prepare_waveform");
   },
 
+  /**
+   * Receive the buckets of the waveform progressively, each 4 `f32` values: the minimum,
+   * the maximum, the RMS and the number of clipped samples, with the buckets of the channels
+   * interleaved, followed by an empty message once complete.
+   */
   requestWaveform(channel: Channel<ArrayBuffer>): Promise<number> {
     return invoke("request_waveform", { channel });
   },
 
+  requestWaveformRange(
+    start: number,
+    end: number,
+    buckets: number,
+  ): Promise<ArrayBuffer> {
+    return invoke("request_waveform_range", { start, end, buckets });
+  },
+
+  setWaveformOptions(options: WaveformOptions): Promise<void> {
+    return invoke("set_waveform_options", { options });
+  },
+
+  getWaveformOptions(): Promise<WaveformOptions> {
+    return invoke("get_waveform_options");
+  },
+
+  prepareSpectrogram(): Promise<SpectrogramInfo> {
+    return invoke("prepare_spectrogram");
+  },
+
+  /**
+   * Receive the columns of the spectrogram progressively, each `nBins` levels in dBFS
+   * as `f32`, from low to high frequencies.
+   */
+  requestSpectrogram(channel: Channel<ArrayBuffer>): Promise<void> {
+    return invoke("request_spectrogram", { channel });
+  },
+
+  setSpectrogramOptions(options: SpectrogramOptions): Promise<void> {
+    return invoke("set_spectrogram_options", { options });
+  },
+
+  getSpectrogramOptions(): Promise<SpectrogramOptions> {
+    return invoke("get_spectrogram_options");
+  },
+
   importFile(path: string, force = false): Promise<void> {
     return invoke("import_file", { path, force });
   },
 
+  importPaths(
+    paths: string[],
+    targetFolderId: number,
+    options: ImportOptions,
+    channel: Channel<ImportProgress>,
+  ): Promise<ImportResult[]> {
+    return invoke("import_paths", { paths, targetFolderId, options, channel });
+  },
+
+  exportEntries(
+    entryIds: number[],
+    targetDir: string,
+    options: ExportOptions,
+    channel: Channel<ExportProgress>,
+  ): Promise<ExportResult[]> {
+    return invoke("export_entries", { entryIds, targetDir, options, channel });
+  },
+
   deleteFile(entryId: number): Promise<void> {
     return invoke("delete_file", { entryId });
   },
@@ -194,20 +585,39 @@ export const api = {
     return invoke("move_folder", { folderId, newParentId });
   },
 
+  createFolder(parentId: number, name: string): Promise<number> {
+    return invoke("create_folder", { parentId, name });
+  },
+
+  deleteFolder(folderId: number): Promise<void> {
+    return invoke("delete_folder", { folderId });
+  },
+
   spot(
     entryId: number,
     savePath?: string,
     openInApplication?: string,
     force = false,
+    region?: Region,
   ): Promise<void> {
     return invoke("spot", {
       entryId,
       savePath,
       openInApplication,
+      region,
       force,
     });
   },
 
+  renderRegion(
+    entryId: number,
+    region: Region,
+    savePath?: string,
+    force = false,
+  ): Promise<string> {
+    return invoke("render_region", { entryId, region, savePath, force });
+  },
+
   revealEntry(entryId: number): Promise<void> {
     return invoke("reveal_entry", { entryId });
   },